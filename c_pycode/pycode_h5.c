@@ -6,6 +6,7 @@
 #include <stdlib.h>
 
 hid_t InfoChannelMemoryType;
+hid_t InfoEventMemoryType;
 hid_t HDF5StringType;
 
 #define CAST(X, Y) (Y)(X)
@@ -46,14 +47,55 @@ phaseh5_error pycodeh5_init() {
   H5Tinsert(InfoChannelMemoryType, "LowPassFilterCutOff\0", offsetof(InfoChannel, low_pass_filter_cutoff), HDF5StringType); 
   H5Tinsert(InfoChannelMemoryType, "LowPassFilterOrder\0", offsetof(InfoChannel, low_pass_filter_order), H5T_NATIVE_INT);
 
+  InfoEventMemoryType = H5Tcreate(H5T_COMPOUND, sizeof(InfoEvent));
+  H5Tinsert(InfoEventMemoryType, "EventID\0", offsetof(InfoEvent, event_id), H5T_NATIVE_INT);
+  H5Tinsert(InfoEventMemoryType, "GroupID\0", offsetof(InfoEvent, group_id), H5T_NATIVE_INT);
+  H5Tinsert(InfoEventMemoryType, "Label\0", offsetof(InfoEvent, label), HDF5StringType);
+  H5Tinsert(InfoEventMemoryType, "SourceChannelIDs\0", offsetof(InfoEvent, source_channel_ids), HDF5StringType);
+  H5Tinsert(InfoEventMemoryType, "SourceChannelLabels\0", offsetof(InfoEvent, source_channel_labels), HDF5StringType);
+
   return OK;
 }
 
 void pycodeh5_close() {
   H5Tclose(InfoChannelMemoryType);
+  H5Tclose(InfoEventMemoryType);
   H5Tclose(HDF5StringType);
 }
 
+//==============================================================================
+//                      ATTRIBUTES RELATED FUNCTIONS
+//==============================================================================
+
+/// read a string attribute, both if stored as a variable or a fixed length
+/// string, and copy it null terminated in a buffer of `len` chars
+herr_t read_string_attribute(hid_t attribute, char *buf, size_t len) {
+  hid_t datatype = H5Aget_type(attribute);
+  if (datatype <= 0) {
+    return -1;
+  }
+
+  herr_t res;
+  if (H5Tis_variable_str(datatype) > 0) {
+    char *attribute_buf = NULL;
+    res = H5Aread(attribute, datatype, (void *)&attribute_buf);
+    if (res >= 0) {
+      strncpy(buf, attribute_buf, len - 1);
+      H5free_memory(attribute_buf);
+    }
+  } else {
+    hid_t memtype = H5Tcopy(H5T_C_S1);
+    H5Tset_size(memtype, len);
+    H5Tset_strpad(memtype, H5T_STR_NULLTERM);
+    res = H5Aread(attribute, memtype, (void *)buf);
+    H5Tclose(memtype);
+  }
+  buf[len - 1] = 0;
+
+  H5Tclose(datatype);
+  return res;
+}
+
 //==============================================================================
 //                      ANALOG STREAMS RELATED FUNCTIONS 
 //==============================================================================
@@ -81,19 +123,33 @@ phaseh5_error open_analog(AnalogStream *analog_stream,
     return OPEN_LABEL_ATTRIBUTE_FAIL;
   }
 
-  hid_t label_datatype = H5Aget_type(label_attribute);
-  if (label_datatype <= 0) {
-    return OPEN_LABEL_DATATYPE_FAIL;
-  }
-
-  herr_t res = H5Aread(label_attribute, label_datatype, (void *)analog_stream);
+  herr_t res = read_string_attribute(label_attribute, (char *)analog_stream->label,
+                                     ANALOG_LABEL_STRING_LEN);
   if (res < 0) {
     return READ_LABEL_ATTRIBUTE_FAIL;
   }
 
-  H5Tclose(label_datatype);
   H5Aclose(label_attribute);
 
+  // GET THE ANALOG STREAM DATA SUBTYPE (Electrode, Auxiliary, Digital), if
+  // present. Otherwise it's left empty
+  // ----------------------------------------------------------------------
+  res = H5Aexists(analog_stream_group, "DataSubType");
+  if (res > 0) {
+    hid_t subtype_attribute = H5Aopen(analog_stream_group, "DataSubType", H5P_DEFAULT);
+    if (subtype_attribute <= 0) {
+      return OPEN_DATA_SUBTYPE_ATTRIBUTE_FAIL;
+    }
+
+    res = read_string_attribute(subtype_attribute, (char *)analog_stream->data_subtype,
+                                ANALOG_LABEL_STRING_LEN);
+    if (res < 0) {
+      return READ_DATA_SUBTYPE_ATTRIBUTE_FAIL;
+    }
+
+    H5Aclose(subtype_attribute);
+  }
+
   // ----------------------------------------------------------------------
   // PARSE THE InfoChannel dataset
   // ----------------------------------------------------------------------
@@ -117,13 +173,34 @@ phaseh5_error open_analog(AnalogStream *analog_stream,
   hsize_t memory_rank[] = {analog_stream->n_channels};
   hid_t memspace_id = H5Screate_simple(1, memory_rank, NULL);
 
+  analog_stream->info_channels = (InfoChannel*)calloc(analog_stream->n_channels, sizeof(InfoChannel));
+  if (analog_stream->info_channels == NULL) {
+    return OPEN_ALLOCATE_INFO_CHANNELS_FAIL;
+  }
+
   res = H5Dread(info_channel_dataset, InfoChannelMemoryType, memspace_id,
                 H5S_ALL, H5P_DEFAULT, (void *)analog_stream->info_channels);
 
+  H5Sclose(memspace_id);
   if (res < 0) {
     return READ_INFO_CHANNELS_FAIL;
   }
 
+  // test that all the channels of the stream have the same sampling period.
+  // The tick is the sampling period in microseconds, so the sampling
+  // frequency is 1e6 / tick: a tick of 100 is 10 kHz and a tick of 50 is
+  // 20 kHz. The former `tick * 100` was right only for the 10 kHz recordings
+  for (hsize_t i = 1; i<analog_stream->n_channels; ++i) {
+    if (analog_stream->info_channels[0].tick != analog_stream->info_channels[i].tick) {
+      return MULTIPLE_SAMPLING_FREQUENCIES;
+    }
+  }
+  if (analog_stream->n_channels > 0 && analog_stream->info_channels[0].tick > 0) {
+    analog_stream->sampling_frequency = 1e6f / analog_stream->info_channels[0].tick;
+  } else {
+    analog_stream->sampling_frequency = 0;
+  }
+
   H5Tclose(info_channel_datatype);
   H5Sclose(info_channel_dataspace);
   H5Dclose(info_channel_dataset);
@@ -166,22 +243,43 @@ herr_t open_analogs_callback(hid_t group,
   CallbackAnalogsRets* analog_rets_c = CAST(analogs_rets, CallbackAnalogsRets*);
   int res = open_analog(&analog_rets_c->analog_streams[analog_rets_c->current_index],
                         analog_stream_group);
+  H5Gclose(analog_stream_group);
   if (res != OK) {
     printf("open_analogs_callback ERROR %d", res);
     return res;
   }
   analog_rets_c->current_index += 1;
 
-  H5Gclose(analog_stream_group);
   return 0;
 }
 
 phaseh5_error close_analog(AnalogStream* analog_stream) {
-  
-H5Dclose(analog_stream->channel_data_dataset);
+  if (analog_stream->info_channels != NULL) {
+    // free the strings allocated by HDF5 while reading the InfoChannel dataset
+    hsize_t dims[] = {analog_stream->n_channels};
+    hid_t memspace_id = H5Screate_simple(1, dims, NULL);
+    H5Treclaim(InfoChannelMemoryType, memspace_id, H5P_DEFAULT, analog_stream->info_channels);
+    H5Sclose(memspace_id);
+    free(analog_stream->info_channels);
+    analog_stream->info_channels = NULL;
+  }
+  // the dataset is not open if the stream failed to open
+  if (analog_stream->channel_data_dataset > 0) {
+    H5Dclose(analog_stream->channel_data_dataset);
+    analog_stream->channel_data_dataset = 0;
+  }
   return OK;
 }
 
+/// check if an analog stream contains digital data. If the DataSubType
+/// attribute is missing a stream with just one channel is considered digital
+bool is_digital(const AnalogStream* analog_stream) {
+  if (analog_stream->data_subtype[0] != 0) {
+    return strcmp(analog_stream->data_subtype, "Digital") == 0;
+  }
+  return analog_stream->n_channels == 1;
+}
+
 //==============================================================================
 //                      EVENTS RELATED FUNCTIONS 
 //==============================================================================
@@ -200,6 +298,7 @@ herr_t count_events_callback(hid_t group,
 typedef struct CallbackEventsRets {
   int current_index;
   hid_t* event_entities;
+  int* event_ids;
 } CallbackEventsRets;
 
 
@@ -209,165 +308,208 @@ herr_t open_events_callback(hid_t group,
                             void *events_rets) {
   if (!strncmp(name, "EventEntity_", sizeof("EventEntity_")/sizeof(char)-1)) {
     CallbackEventsRets* events_rets_c = CAST(events_rets, CallbackEventsRets*);
-    if (events_rets_c->current_index == MAX_EVENT_STREAMS - 1) {
-      return MAX_EVENT_STREAMS_EXCEEDED;
-    }
     hid_t entity_dataset = H5Dopen2(group, name, H5P_DEFAULT);
     if (entity_dataset <= 0) {
     return OPEN_ENTITY_DATASET_FAIL;
     }
     events_rets_c->event_entities[events_rets_c->current_index] = entity_dataset;
+    events_rets_c->event_ids[events_rets_c->current_index] =
+      atoi(name + sizeof("EventEntity_")/sizeof(char)-1);
     events_rets_c->current_index += 1;
   }
   return OK;
 }
 
+/// read the optional InfoEvent table of an event stream. A table that can't
+/// be read, e.g. with different fields, is just ignored
+phaseh5_error open_info_events(PhaseH5 *phase, hid_t events_group) {
+  phase->n_info_events = 0;
+  phase->info_events = NULL;
+
+  htri_t exists = H5Lexists(events_group, "InfoEvent", H5P_DEFAULT);
+  if (exists <= 0) {
+    return OK;
+  }
+
+  hid_t info_event_dataset = H5Dopen2(events_group, "InfoEvent", H5P_DEFAULT);
+  if (info_event_dataset <= 0) {
+    return OPEN_INFO_EVENT_DATASET_FAIL;
+  }
+
+  hid_t info_event_dataspace = H5Dget_space(info_event_dataset);
+  hsize_t n_info_events;
+  H5Sget_simple_extent_dims(info_event_dataspace, &n_info_events, NULL);
+  H5Sclose(info_event_dataspace);
+
+  InfoEvent *info_events = (InfoEvent*)calloc(n_info_events, sizeof(InfoEvent));
+  if (info_events == NULL) {
+    return OPEN_ALLOCATE_EVENTS_FAIL;
+  }
+
+  herr_t res = H5Dread(info_event_dataset, InfoEventMemoryType, H5S_ALL, H5S_ALL,
+                       H5P_DEFAULT, info_events);
+  H5Dclose(info_event_dataset);
+  if (res < 0) {
+    free(info_events);
+    return OK;
+  }
+
+  phase->n_info_events = n_info_events;
+  phase->info_events = info_events;
+  return OK;
+}
+
 //==============================================================================
 //                      PHASE RELATED FUNCTIONS 
 //==============================================================================
 void init_phase(PhaseH5 *phase) { memset(phase, 0, sizeof(PhaseH5)); }
 
-phaseh5_error phase_open(PhaseH5 *phase, const char *filename) {
-  hid_t fid = H5Fopen(filename, H5F_ACC_RDWR, H5P_DEFAULT);
+phaseh5_error phase_open(PhaseH5 *phase, const char *filename, bool read_only) {
+  // after the file is open every error jumps to the fail label, that closes
+  // the handles still open and releases what was allocated up to that point
+  phaseh5_error ret = OK;
+  hid_t data_group = H5I_INVALID_HID;
+  hid_t attribute = H5I_INVALID_HID;
+  hid_t analog_group = H5I_INVALID_HID;
+  hid_t event_stream = H5I_INVALID_HID;
+  hid_t events_group = H5I_INVALID_HID;
+
+  hid_t fid;
+  if (read_only) {
+    // disable the file locking so that more processes can read the same file
+    hid_t fapl = H5Pcreate(H5P_FILE_ACCESS);
+    if (fapl <= 0) {
+      return OPEN_FILE_ACCESS_PROPERTIES_FAIL;
+    }
+    if (H5Pset_file_locking(fapl, false, true) < 0) {
+      H5Pclose(fapl);
+      return OPEN_FILE_ACCESS_PROPERTIES_FAIL;
+    }
+    fid = H5Fopen(filename, H5F_ACC_RDONLY, fapl);
+    H5Pclose(fapl);
+  } else {
+    fid = H5Fopen(filename, H5F_ACC_RDWR, H5P_DEFAULT);
+  }
   if (fid <= 0) {
     return OPEN_FAIL;
   }
   phase->fid = fid;
+  phase->read_only = read_only;
 
-  hid_t data_group = H5Gopen2(fid, "/Data", H5P_DEFAULT);
+  data_group = H5Gopen2(fid, "/Data", H5P_DEFAULT);
   if (data_group <= 0) {
-    return OPEN_DATA_GROUP_FAIL;
+    ret = OPEN_DATA_GROUP_FAIL;
+    goto fail;
   }
 
   // GET THE DATE OF THE RECORDING
   // ----------------------------------------------------------------------
-  hid_t date_attribute = H5Aopen(data_group, "Date", H5P_DEFAULT);
-  H5Gclose(data_group);
-  if (date_attribute <= 0) {
-    return OPEN_DATE_ATTRIBUTE_FAIL;
-  }
-
-  hid_t date_datatype = H5Aget_type(date_attribute);
-  if (date_datatype <= 0) {
-    return OPEN_DATE_DATATYPE_FAIL;
+  attribute = H5Aopen(data_group, "Date", H5P_DEFAULT);
+  if (attribute <= 0) {
+    ret = OPEN_DATE_ATTRIBUTE_FAIL;
+    goto fail;
   }
 
-  herr_t res = H5Aread(date_attribute, date_datatype, (void *)phase->date);
+  herr_t res = read_string_attribute(attribute, phase->date, DATE_STRING_LEN);
   if (res < 0) {
-    return READ_DATE_ATTRIBUTE_FAIL;
-  }
+    ret = READ_DATE_ATTRIBUTE_FAIL;
+    goto fail;
+  }
+
+  H5Aclose(attribute);
+  attribute = H5I_INVALID_HID;
+
+  // the DateInTicks attribute holds the start of the recording as .NET ticks
+  // (100 ns intervals since 0001-01-01). If missing it's left to 0
+  phase->date_in_ticks = 0;
+  res = H5Aexists(data_group, "DateInTicks");
+  if (res > 0) {
+    attribute = H5Aopen(data_group, "DateInTicks", H5P_DEFAULT);
+    if (attribute <= 0) {
+      ret = OPEN_DATE_IN_TICKS_ATTRIBUTE_FAIL;
+      goto fail;
+    }
+
+    res = H5Aread(attribute, H5T_NATIVE_LLONG, (void *)&phase->date_in_ticks);
+    if (res < 0) {
+      ret = READ_DATE_IN_TICKS_ATTRIBUTE_FAIL;
+      goto fail;
+    }
 
-  H5Tclose(date_datatype);
-  H5Aclose(date_attribute);
+    H5Aclose(attribute);
+    attribute = H5I_INVALID_HID;
+  }
+  H5Gclose(data_group);
+  data_group = H5I_INVALID_HID;
 
   // ----------------------------------------------------------------------
   // PARSE THE ANALOG STREAMS
   // ----------------------------------------------------------------------
-  hid_t analog_group =
+  analog_group =
       H5Gopen2(fid, "/Data/Recording_0/AnalogStream", H5P_DEFAULT);
   if (analog_group <= 0) {
-    return OPEN_ANALOG_GROUP_FAIL;
+    ret = OPEN_ANALOG_GROUP_FAIL;
+    goto fail;
   }
 
   int n_analogs = 0;
   res = H5Literate2(analog_group, H5_INDEX_NAME, H5_ITER_NATIVE, NULL,
                     count_analogs_callback, (void *)(&n_analogs));
   if (res != 0) {
-    return res;
+    ret = res;
+    goto fail;
   }
 
-  AnalogStream *analog_streams = (AnalogStream*)calloc(n_analogs, sizeof(AnalogStream));
-  // AnalogStream analog_streams[MAX_ANALOG_STREAMS] = {0};
-  if (analog_streams == NULL) {
-    return OPEN_ALLOCATE_ANALOGS_FAIL;
+  phase->analogs = (AnalogStream*)calloc(n_analogs, sizeof(AnalogStream));
+  phase->digital_indices = (size_t*)calloc(n_analogs, sizeof(size_t));
+  if (phase->analogs == NULL || phase->digital_indices == NULL) {
+    ret = OPEN_ALLOCATE_ANALOGS_FAIL;
+    goto fail;
   }
+  phase->n_analogs = n_analogs;
 
   CallbackAnalogsRets callback_ret = {
       .current_index = 0,
-      .analog_streams = analog_streams,
+      .analog_streams = phase->analogs,
   };
 
   res = H5Literate2(analog_group, H5_INDEX_NAME, H5_ITER_NATIVE, NULL,
                     open_analogs_callback, (void *)&callback_ret);
   if (res != 0) {
-    return res;
+    ret = res;
+    goto fail;
   }
 
-  size_t raw_data_index;
-  size_t digital_index;
+  // keep all the analog streams. The digital ones are indexed apart while the
+  // first electrode stream (or the first not digital one if the DataSubType
+  // is missing) is used as raw data
   bool raw_data_set = false;
-  bool digital_set = false;
-
-  float sampling_frequency = -1;
-  long datalen = -1;
-
-  for (long int i = 0; i<n_analogs; ++i) {
-    // test that there is only a raw data stream and only a digital stream
-    // digital stream case
-    if (analog_streams[i].n_channels == 1) {
-      if (digital_set == false) {
-        digital_index = i;
-        digital_set = true;
-      }
-      else {
-        return MULTIPLE_DIGITAL_STREAMS;
-      }
-      // test that all the channels have the same sampling frequency
-      if (sampling_frequency == -1) {
-        sampling_frequency = analog_streams[i].info_channels[0].tick * 100;
-      } else if (sampling_frequency != analog_streams[i].info_channels[0].tick * 100){
-        return MULTIPLE_SAMPLING_FREQUENCIES;
-      }
-    } else {
-      // raw data stream case
-      if (raw_data_set == false) {
-        raw_data_index = i;
-        raw_data_set = true;
-      }
-      else {
-        return MULTIPLE_RAW_DATA_STREAMS; 
-      }
-      // test that all the channels have the same sampling frequency
-      if (sampling_frequency == -1) {
-        sampling_frequency = analog_streams[i].info_channels[0].tick * 100;
-      } else {
-        for (int j = 0; j<analog_streams[i].n_channels; ++j) {
-          if (sampling_frequency != analog_streams[i].info_channels[j].tick * 100){
-            return MULTIPLE_SAMPLING_FREQUENCIES;
-          }
-        }
-      }
-    }
-
-    // test that all the channels have the same datalen
-    if (datalen == -1) {
-      datalen = analog_streams[i].datalen;
-    } else {
-      if (datalen != analog_streams[i].datalen) {
-        return MULTIPLE_DATALENS;
-      }
+  phase->n_digitals = 0;
+
+  for (size_t i = 0; i<phase->n_analogs; ++i) {
+    if (is_digital(&phase->analogs[i])) {
+      phase->digital_indices[phase->n_digitals] = i;
+      phase->n_digitals += 1;
+    } else if (raw_data_set == false ||
+               (strcmp(phase->analogs[phase->raw_data_index].data_subtype, "Electrode") != 0 &&
+                strcmp(phase->analogs[i].data_subtype, "Electrode") == 0)) {
+      phase->raw_data_index = i;
+      raw_data_set = true;
     }
   }
 
-  phase->datalen = datalen;
-  phase->sampling_frequency = sampling_frequency;
-
-  // printf("raw DATA INDEX: %ld\nDIGITAL_INDEX: %ld\n", raw_data_index, digital_index);
-
   if (raw_data_set == false) {
-    return NO_RAW_DATA_STREAM;
-  } else {
-    memcpy(&phase->raw_data, analog_streams + raw_data_index, sizeof(AnalogStream));
+    ret = NO_RAW_DATA_STREAM;
+    goto fail;
   }
-  if (digital_set == false) {
-    phase->has_digital = false;
-  } else {
-    memcpy(&phase->digital, analog_streams + digital_index, sizeof(AnalogStream));
-    phase->has_digital = true;
+
+  ret = set_raw_data_stream(phase, phase->raw_data_index);
+  if (ret != OK) {
+    goto fail;
   }
 
   H5Gclose(analog_group);
-  free(analog_streams);
+  analog_group = H5I_INVALID_HID;
   
   // ----------------------------------------------------------------------
   // PARSE THE EVENT STREAMS
@@ -376,37 +518,53 @@ phaseh5_error phase_open(PhaseH5 *phase, const char *filename) {
   res = H5Lexists(fid, "/Data/Recording_0/EventStream", H5P_DEFAULT);
 
   if (res < 0) {
-    return OPEN_EVENT_STREAM_GROUP_LINK_FAIL;
+    ret = OPEN_EVENT_STREAM_GROUP_LINK_FAIL;
+    goto fail;
   } else if (res == 0) {
     phase->n_events = 0;
   } else {
-    hid_t event_stream = H5Gopen2(fid, "/Data/Recording_0/EventStream", H5P_DEFAULT);
+    event_stream = H5Gopen2(fid, "/Data/Recording_0/EventStream", H5P_DEFAULT);
     if (event_stream <= 0) {
-      return OPEN_EVENT_STREAM_GROUP_FAIL;
+      ret = OPEN_EVENT_STREAM_GROUP_FAIL;
+      goto fail;
     }
     res = H5Lexists(fid, "/Data/Recording_0/EventStream/Stream_0", H5P_DEFAULT);
     if (res < 0) {
-      return OPEN_EVENT_STREAM_STREAM_0_GROUP_LINK_FAIL;
+      ret = OPEN_EVENT_STREAM_STREAM_0_GROUP_LINK_FAIL;
+      goto fail;
     } else if (res == 0) {
       phase->n_events = 0;
     } else {
-      hid_t events_group = H5Gopen2(fid, "/Data/Recording_0/EventStream/Stream_0", H5P_DEFAULT);
+      events_group = H5Gopen2(fid, "/Data/Recording_0/EventStream/Stream_0", H5P_DEFAULT);
       if (events_group <= 0) {
-        return OPEN_EVENT_STREAM_GROUP_FAIL;
+        ret = OPEN_EVENT_STREAM_GROUP_FAIL;
+        goto fail;
       }
 
+      int n_events = 0;
       res = H5Literate2(events_group,
                         H5_INDEX_NAME,
                         H5_ITER_NATIVE,
                         NULL,
                         count_events_callback,
-                        &phase->n_events);
+                        &n_events);
       if (res != OK) {
-        return res;
+        ret = res;
+        goto fail;
+      }
+
+      phase->event_entities = (hid_t*)calloc(n_events, sizeof(hid_t));
+      phase->event_ids = (int*)calloc(n_events, sizeof(int));
+      if (phase->event_entities == NULL || phase->event_ids == NULL) {
+        ret = OPEN_ALLOCATE_EVENTS_FAIL;
+        goto fail;
       }
 
-      CallbackEventsRets events_rets;
-      events_rets.current_index = 0;
+      CallbackEventsRets events_rets = {
+          .current_index = 0,
+          .event_entities = phase->event_entities,
+          .event_ids = phase->event_ids,
+      };
       res = H5Literate2(events_group,
                         H5_INDEX_NAME,
                         H5_ITER_NATIVE,
@@ -414,53 +572,116 @@ phaseh5_error phase_open(PhaseH5 *phase, const char *filename) {
                         open_events_callback,
                         &events_rets);
 
+      phase->n_events = events_rets.current_index;
       if (res != OK) {
-        return res;
+        ret = res;
+        goto fail;
       }
 
-      for (int i=0; i<events_rets.current_index; ++i) {
-        phase->event_entities[i] = events_rets.event_entities[i];
+      ret = open_info_events(phase, events_group);
+      if (ret != OK) {
+        goto fail;
       }
+
+      H5Gclose(events_group);
+      events_group = H5I_INVALID_HID;
     }
+    H5Gclose(event_stream);
+    event_stream = H5I_INVALID_HID;
   }
   
   // ----------------------------------------------------------------------
   // PARSE THE PEAK_TRAIN GROUP
   // ----------------------------------------------------------------------
+  strcpy(phase->peak_run, DEFAULT_PEAK_RUN);
+  strcpy(phase->peaks_path, "/Data/Recording_0/Peak_Train");
 
   res = H5Lexists(fid, "/Data/Recording_0/Peak_Train", H5P_DEFAULT);
 
   if (res < 0) {
     phase->peaks_group = false;
-    return OPEN_PEAK_TRAIN_GROUP_FAIL;
+    ret = OPEN_PEAK_TRAIN_GROUP_FAIL;
+    goto fail;
+  } else if (res == 0 && read_only) {
+    // the group can't be created, peak trains will be just empty
+    phase->peaks_group = false;
   } else if (res == 0) {
-    res = H5Gcreate(fid, "/Data/Recording_0/Peak_Train", H5P_DEFAULT, H5P_DEFAULT, H5P_DEFAULT);
-    if (res < 0) {
-      return CREATE_PEAK_GROUP_FAIL;
+    hid_t peaks_group = H5Gcreate(fid, "/Data/Recording_0/Peak_Train", H5P_DEFAULT, H5P_DEFAULT, H5P_DEFAULT);
+    if (peaks_group <= 0) {
+      ret = CREATE_PEAK_GROUP_FAIL;
+      goto fail;
     }
+    H5Gclose(peaks_group);
     phase->peaks_group = true;
   } else {
     phase->peaks_group = true;
   }
 
   return OK;
+
+fail:
+  if (attribute > 0) {
+    H5Aclose(attribute);
+  }
+  if (data_group > 0) {
+    H5Gclose(data_group);
+  }
+  if (analog_group > 0) {
+    H5Gclose(analog_group);
+  }
+  if (events_group > 0) {
+    H5Gclose(events_group);
+  }
+  if (event_stream > 0) {
+    H5Gclose(event_stream);
+  }
+  // the analogs, the digital indices, the info channels and the events are
+  // released like on a complete phase, then the file is closed. A second
+  // phase_close, e.g. when the caller drops the phase, does nothing
+  phase_close(phase);
+  return ret;
 }
 
 phaseh5_error phase_close(PhaseH5* phase) {
   herr_t res;
-  close_analog(&phase->raw_data);
-  if (phase->has_digital) {
-    close_analog(&phase->digital);
+  for (size_t i=0; i<phase->n_analogs; i++) {
+    close_analog(&phase->analogs[i]);
   }
+  free(phase->analogs);
+  free(phase->digital_indices);
+  phase->analogs = NULL;
+  phase->digital_indices = NULL;
+  phase->n_analogs = 0;
+  phase->n_digitals = 0;
+
   for (int i=0; i<phase->n_events; i++) {
     res = H5Dclose(phase->event_entities[i]);
     if (res < 0) {
       return EVENT_ENTITY_DATASET_CLOSE_FAIL;
     }
   }
-  res = H5Fclose(phase->fid);
-  if (res < 0) {
-    return CLOSE_FILE_FAIL;
+  free(phase->event_entities);
+  free(phase->event_ids);
+  phase->event_entities = NULL;
+  phase->event_ids = NULL;
+  phase->n_events = 0;
+
+  if (phase->info_events != NULL) {
+    hsize_t dims[] = {phase->n_info_events};
+    hid_t memspace_id = H5Screate_simple(1, dims, NULL);
+    H5Treclaim(InfoEventMemoryType, memspace_id, H5P_DEFAULT, phase->info_events);
+    H5Sclose(memspace_id);
+    free(phase->info_events);
+    phase->info_events = NULL;
+    phase->n_info_events = 0;
+  }
+
+  if (phase->fid > 0) {
+    res = H5Fclose(phase->fid);
+    phase->fid = 0;
+    if (res < 0) {
+      return CLOSE_FILE_FAIL;
+    }
   }
   return OK;
 }
@@ -468,39 +689,67 @@ phaseh5_error phase_close(PhaseH5* phase) {
 //==============================================================================
 //                      RAW DATA I/O FUNCTIONS 
 //==============================================================================
-phaseh5_error raw_data(PhaseH5* phase, size_t index, size_t start, size_t end, int* buf) {
+phaseh5_error set_raw_data_stream(PhaseH5* phase, size_t stream) {
+  if (stream >= phase->n_analogs) {
+    return SET_RAW_DATA_STREAM_INDEX_OUT_OF_BOUNDS;
+  }
+
+  if (is_digital(&phase->analogs[stream])) {
+    return SET_RAW_DATA_STREAM_IS_DIGITAL;
+  }
+
+  phase->raw_data_index = stream;
+  phase->datalen = phase->analogs[stream].datalen;
+  phase->sampling_frequency = phase->analogs[stream].sampling_frequency;
+  return OK;
+}
+
+phaseh5_error analog_data(PhaseH5* phase, size_t stream, size_t index, size_t start, size_t end, int* buf) {
+  if (stream >= phase->n_analogs) {
+    return ANALOG_DATA_STREAM_INDEX_OUT_OF_BOUNDS;
+  }
+
+  AnalogStream *analog_stream = &phase->analogs[stream];
+
+  if (index >= analog_stream->n_channels) {
+    return ANALOG_DATA_CHANNEL_INDEX_OUT_OF_BOUNDS;
+  }
+
   if (end < start) {
     return RAW_DATA_END_BEFORE_START;
   }
 
-  if (end >= phase->datalen) {
+  // `end` is excluded, so it can be equal to the datalen to read the last sample
+  if (end > analog_stream->datalen) {
     return RAW_DATA_END_OUT_OF_BOUNDS;
   }
 
-  size_t dims[] = {end - start};
-  hid_t raw_data_dataspace = H5Dget_space(phase->raw_data.channel_data_dataset);
-  // TODO! fix one sample left over maybe here, maybe in the rust code but if
-  // you query for all the samples it gives you back all but one.
+  hsize_t dims[] = {end - start};
+  hid_t raw_data_dataspace = H5Dget_space(analog_stream->channel_data_dataset);
 
   if (raw_data_dataspace <= 0) {
     return RAW_DATA_GET_DATASPACE_FAIL;
   }
   
-  size_t _start[] = {index, start};
-  size_t _count[] = {1, end - start};
+  hsize_t _start[] = {index, start};
+  hsize_t _count[] = {1, end - start};
   herr_t res = H5Sselect_hyperslab(raw_data_dataspace, H5S_SELECT_SET, _start, NULL, _count, NULL);
 
   if (res < 0) {
+    H5Sclose(raw_data_dataspace);
     return RAW_DATA_SELECT_HYPERSLAB_FAIL;
   }
 
   hid_t read_dataspace = H5Screate_simple(1, dims, NULL);
   if (read_dataspace <= 0) {
+    H5Sclose(raw_data_dataspace);
     return RAW_DATA_CREATE_MEMORY_DATASPACE_FAIL;
   }
 
-  res = H5Dread(phase->raw_data.channel_data_dataset, H5T_NATIVE_INT, read_dataspace,
+  res = H5Dread(analog_stream->channel_data_dataset, H5T_NATIVE_INT, read_dataspace,
           raw_data_dataspace, H5P_DEFAULT, buf);
+  H5Sclose(read_dataspace);
+  H5Sclose(raw_data_dataspace);
   
   if (res < 0) {
     return RAW_DATA_READ_DATA_FAIL;
@@ -509,17 +758,81 @@ phaseh5_error raw_data(PhaseH5* phase, size_t index, size_t start, size_t end, i
   return OK;
 }
 
+phaseh5_error raw_data(PhaseH5* phase, size_t index, size_t start, size_t end, int* buf) {
+  return analog_data(phase, phase->raw_data_index, index, start, end, buf);
+}
+
+phaseh5_error raw_data_block(PhaseH5* phase, const size_t* indices, size_t n_indices, size_t start, size_t end, int* buf) {
+  AnalogStream *analog_stream = &phase->analogs[phase->raw_data_index];
+
+  if (end < start) {
+    return RAW_DATA_END_BEFORE_START;
+  }
+
+  if (end > analog_stream->datalen) {
+    return RAW_DATA_END_OUT_OF_BOUNDS;
+  }
+
+  for (size_t i=0; i<n_indices; i++) {
+    if (indices[i] >= analog_stream->n_channels) {
+      return RAW_DATA_BLOCK_CHANNEL_INDEX_OUT_OF_BOUNDS;
+    }
+  }
+
+  hid_t raw_data_dataspace = H5Dget_space(analog_stream->channel_data_dataset);
+  if (raw_data_dataspace <= 0) {
+    return RAW_DATA_GET_DATASPACE_FAIL;
+  }
+
+  // union of the rows of the channels, read in the file order
+  herr_t res = H5Sselect_none(raw_data_dataspace);
+  for (size_t i=0; i<n_indices && res >= 0; i++) {
+    hsize_t _start[] = {indices[i], start};
+    hsize_t _count[] = {1, end - start};
+    res = H5Sselect_hyperslab(raw_data_dataspace, H5S_SELECT_OR, _start, NULL, _count, NULL);
+  }
+
+  if (res < 0) {
+    H5Sclose(raw_data_dataspace);
+    return RAW_DATA_SELECT_HYPERSLAB_FAIL;
+  }
+
+  hsize_t dims[] = {n_indices, end - start};
+  hid_t read_dataspace = H5Screate_simple(2, dims, NULL);
+  if (read_dataspace <= 0) {
+    H5Sclose(raw_data_dataspace);
+    return RAW_DATA_CREATE_MEMORY_DATASPACE_FAIL;
+  }
+
+  res = H5Dread(analog_stream->channel_data_dataset, H5T_NATIVE_INT, read_dataspace,
+                raw_data_dataspace, H5P_DEFAULT, buf);
+  H5Sclose(read_dataspace);
+  H5Sclose(raw_data_dataspace);
+
+  if (res < 0) {
+    return RAW_DATA_READ_DATA_FAIL;
+  }
+
+  return OK;
+}
+
 phaseh5_error set_raw_data(PhaseH5* phase, size_t index, size_t start, size_t end, const int *buf) {
+  if (phase->read_only) {
+    return PHASE_IS_READ_ONLY;
+  }
+
   if (end < start) {
     return SET_RAW_DATA_END_BEFORE_START;
   }
 
-  if (end >= phase->datalen) {
+  AnalogStream *analog_stream = &phase->analogs[phase->raw_data_index];
+
+  if (end > analog_stream->datalen) {
     return SET_RAW_DATA_END_OUT_OF_BOUNDS;
   }
 
   // get the ChannelData dataspace
-  hid_t channel_data_dataspace = H5Dget_space(phase->raw_data.channel_data_dataset);
+  hid_t channel_data_dataspace = H5Dget_space(analog_stream->channel_data_dataset);
   if (channel_data_dataspace <= 0) {
     return SET_RAW_DATA_GET_DATASPACE_FAIL;
   }
@@ -543,7 +856,7 @@ phaseh5_error set_raw_data(PhaseH5* phase, size_t index, size_t start, size_t en
     return SET_RAW_DATA_CREATE_MEMORY_DATASPACE_FAIL;
   }
 
-  res = H5Dwrite(phase->raw_data.channel_data_dataset,
+  res = H5Dwrite(analog_stream->channel_data_dataset,
                  H5T_NATIVE_INT,
                  memory_dataspace,
                  channel_data_dataspace,
@@ -557,21 +870,28 @@ phaseh5_error set_raw_data(PhaseH5* phase, size_t index, size_t start, size_t en
   return OK;
 }
 
-phaseh5_error digital(PhaseH5* phase, size_t start, size_t end, int* buf) {
-  if (!phase->has_digital) {
+phaseh5_error digital(PhaseH5* phase, size_t index, size_t start, size_t end, int* buf) {
+  if (phase->n_digitals == 0) {
     return DIGITAL_NO_DIGITAL;
   }
+
+  if (index >= phase->n_digitals) {
+    return DIGITAL_INDEX_OUT_OF_BOUNDS;
+  }
+
+  AnalogStream *digital_stream = &phase->analogs[phase->digital_indices[index]];
   
   if (end < start) {
     return DIGITAL_END_BEFORE_START;
   }
 
-  if (end >= phase->datalen) {
+  // `end` is excluded, so it can be equal to the datalen to read the last sample
+  if (end > digital_stream->datalen) {
     return DIGITAL_END_OUT_OF_BOUNDS;
   }
 
   size_t dims[] = {end - start};
-  hid_t digital_dataspace = H5Dget_space(phase->digital.channel_data_dataset);
+  hid_t digital_dataspace = H5Dget_space(digital_stream->channel_data_dataset);
 
   if (digital_dataspace <= 0) {
     return DIGITAL_GET_DATASPACE_FAIL;
@@ -590,7 +910,7 @@ phaseh5_error digital(PhaseH5* phase, size_t start, size_t end, int* buf) {
     return DIGITAL_CREATE_MEMORY_DATASPACE_FAIL;
   }
 
-  res = H5Dread(phase->digital.channel_data_dataset, H5T_NATIVE_INT, read_dataspace,
+  res = H5Dread(digital_stream->channel_data_dataset, H5T_NATIVE_INT, read_dataspace,
           digital_dataspace, H5P_DEFAULT, buf);
   
   if (res < 0) {
@@ -600,21 +920,32 @@ phaseh5_error digital(PhaseH5* phase, size_t start, size_t end, int* buf) {
   return OK;
 }
 
-phaseh5_error set_digital(PhaseH5* phase, size_t start, size_t end, const int *buf) {
-  if (!phase->has_digital) {
+phaseh5_error set_digital(PhaseH5* phase, size_t index, size_t start, size_t end, const int *buf) {
+  if (phase->read_only) {
+    return PHASE_IS_READ_ONLY;
+  }
+
+  if (phase->n_digitals == 0) {
     return SET_DIGITAL_NO_DIGITAL;
   }
+
+  if (index >= phase->n_digitals) {
+    return SET_DIGITAL_INDEX_OUT_OF_BOUNDS;
+  }
+
+  AnalogStream *digital_stream = &phase->analogs[phase->digital_indices[index]];
   
   if (end < start) {
     return SET_DIGITAL_END_BEFORE_START;
   }
 
-  if (end >= phase->datalen) {
+  // `end` is excluded, so it can be equal to the datalen to write the last sample
+  if (end > digital_stream->datalen) {
     return SET_DIGITAL_END_OUT_OF_BOUNDS;
   }
 
   // get the ChannelData dataspace
-  hid_t channel_data_dataspace = H5Dget_space(phase->raw_data.channel_data_dataset);
+  hid_t channel_data_dataspace = H5Dget_space(digital_stream->channel_data_dataset);
   if (channel_data_dataspace <= 0) {
     return SET_DIGITAL_GET_DATASPACE_FAIL;
   }
@@ -636,7 +967,7 @@ phaseh5_error set_digital(PhaseH5* phase, size_t start, size_t end, const int *b
     return SET_DIGITAL_CREATE_MEMORY_DATASPACE_FAIL;
   }
 
-  res = H5Dwrite(phase->raw_data.channel_data_dataset,
+  res = H5Dwrite(digital_stream->channel_data_dataset,
                  H5T_NATIVE_INT,
                  memory_dataspace,
                  channel_data_dataspace,
@@ -669,7 +1000,9 @@ phaseh5_error events_len(PhaseH5* phase, size_t index, hsize_t *dim) {
   return OK;
 }
 
-phaseh5_error events(PhaseH5* phase, size_t index, LLONG_TYPE *buf) {
+/// read a row of an event entity dataset: 0 for the timestamps, 1 for the
+/// durations
+phaseh5_error events_row(PhaseH5* phase, size_t index, hsize_t row, LLONG_TYPE *buf) {
   if (index >= phase->n_events) {
     return EVENTS_INDEX_OUT_OF_BOUNDS;
   }
@@ -684,7 +1017,7 @@ phaseh5_error events(PhaseH5* phase, size_t index, LLONG_TYPE *buf) {
   if (file_dataspace <= 0) {
     return EVENTS_GET_EVENTS_DATASPACE_FAIL;
   }
-  hsize_t start[] = {0, 0};
+  hsize_t start[] = {row, 0};
   hsize_t count[] = {1, dim};
   res = H5Sselect_hyperslab(file_dataspace,
                             H5S_SELECT_SET,
@@ -704,12 +1037,14 @@ phaseh5_error events(PhaseH5* phase, size_t index, LLONG_TYPE *buf) {
   }
 
   res = H5Dread(events_dataset,
-                H5T_NATIVE_LONG,
+                H5T_NATIVE_LLONG,
                 memory_dataspace,
                 file_dataspace,
                 H5P_DEFAULT,
                 buf);
 
+  H5Sclose(memory_dataspace);
+  H5Sclose(file_dataspace);
   if (res < 0) {
     return EVENTS_READ_DATASET_FAIL;
   }
@@ -717,6 +1052,41 @@ phaseh5_error events(PhaseH5* phase, size_t index, LLONG_TYPE *buf) {
   return OK;
 }
 
+phaseh5_error events(PhaseH5* phase, size_t index, LLONG_TYPE *buf) {
+  return events_row(phase, index, 0, buf);
+}
+
+phaseh5_error event_durations(PhaseH5* phase, size_t index, LLONG_TYPE *buf) {
+  if (index >= phase->n_events) {
+    return EVENTS_INDEX_OUT_OF_BOUNDS;
+  }
+
+  // entities without the durations row are read as zero length events
+  hid_t event_dataspace = H5Dget_space(phase->event_entities[index]);
+  if (event_dataspace <= 0) {
+    return EVENTS_GET_EVENTS_DATASPACE_FAIL;
+  }
+  hsize_t dims[2];
+  herr_t res = H5Sget_simple_extent_dims(event_dataspace, dims, NULL);
+  H5Sclose(event_dataspace);
+  if (res < 0) {
+    return EVENTS_LEN_GET_DIMS_FAIL;
+  }
+  if (dims[0] < 2) {
+    memset(buf, 0, dims[1] * sizeof(LLONG_TYPE));
+    return OK;
+  }
+
+  return events_row(phase, index, 1, buf);
+}
+
+/// write in `path` the path of the peak train of `label` in the current run,
+/// followed by `suffix`. Returns false if it doesn't fit MAX_GROUP_STRING_LEN
+bool peak_train_path(const PhaseH5* phase, const char* label, const char* suffix, char* path) {
+  int len = snprintf(path, MAX_GROUP_STRING_LEN, "%s/%s%s", phase->peaks_path, label, suffix);
+  return len >= 0 && len < MAX_GROUP_STRING_LEN;
+}
+
 phaseh5_error open_peak_train_datasets(PhaseH5* phase, const char* label, hid_t* values, hid_t* samples) {
   // Check if there are peak train data in the file
   if (phase->peaks_group == 0) {
@@ -731,7 +1101,11 @@ phaseh5_error open_peak_train_datasets(PhaseH5* phase, const char* label, hid_t*
   char values_group_str[MAX_GROUP_STRING_LEN] = {0};
   char samples_group_str[MAX_GROUP_STRING_LEN] = {0};
 
-  sprintf(peak_train_group_str, "/Data/Recording_0/Peak_Train/%s/", label);
+  if (!peak_train_path(phase, label, "/", peak_train_group_str) ||
+      !peak_train_path(phase, label, "/values", values_group_str) ||
+      !peak_train_path(phase, label, "/samples", samples_group_str)) {
+    return PEAK_TRAIN_PATH_TOO_LONG;
+  }
 
   herr_t res = H5Lexists(phase->fid, peak_train_group_str, H5P_DEFAULT);
   if (res < 0) {
@@ -739,9 +1113,6 @@ phaseh5_error open_peak_train_datasets(PhaseH5* phase, const char* label, hid_t*
   } else if (res == 0) {
     return PEAK_TRAIN_NO_PEAK_GROUP;
   }
-  
-  sprintf(values_group_str, "/Data/Recording_0/Peak_Train/%s/values", label);
-  sprintf(samples_group_str, "/Data/Recording_0/Peak_Train/%s/samples", label);
 
   // Check if those links exist
   res = H5Lexists(phase->fid, values_group_str, H5P_DEFAULT);
@@ -880,6 +1251,23 @@ phaseh5_error peak_train(PhaseH5* phase, const char* label, PeakTrain* peak_trai
   return OK;
 }
 
+/// delete the attributes of the detection metadata of a peak train group, if
+/// present. A train written after the detection doesn't come only from it
+herr_t delete_peak_train_metadata(hid_t fid, const char* label_group_str) {
+  const char* names[] = {"Threshold", "Multiplier", "PeakDuration", "RefractoryTime",
+                         "Start", "End", "Created", "LibraryVersion"};
+  for (size_t i = 0; i < sizeof(names)/sizeof(names[0]); ++i) {
+    htri_t exists = H5Aexists_by_name(fid, label_group_str, names[i], H5P_DEFAULT);
+    if (exists < 0) {
+      return -1;
+    }
+    if (exists > 0 && H5Adelete_by_name(fid, label_group_str, names[i], H5P_DEFAULT) < 0) {
+      return -1;
+    }
+  }
+  return 0;
+}
+
 /*
 TODO! restructure this function
 it must:
@@ -891,22 +1279,32 @@ it must:
 - write into them
  */
 phaseh5_error set_peak_train(PhaseH5* phase, const char* label, const PeakTrain* peak_train) {
+  if (phase->read_only) {
+    return PHASE_IS_READ_ONLY;
+  }
+
   char values_group_str[MAX_GROUP_STRING_LEN];
   char samples_group_str[MAX_GROUP_STRING_LEN];
+  char label_group_str[MAX_GROUP_STRING_LEN];
 
-  sprintf(values_group_str, "/Data/Recording_0/Peak_Train/%s/values", label);
-  sprintf(samples_group_str, "/Data/Recording_0/Peak_Train/%s/samples", label);
+  if (!peak_train_path(phase, label, "/values", values_group_str) ||
+      !peak_train_path(phase, label, "/samples", samples_group_str) ||
+      !peak_train_path(phase, label, "/", label_group_str)) {
+    return PEAK_TRAIN_PATH_TOO_LONG;
+  }
 
   // Delete old dataspaces if present (maybe close the identifiers)
   // Check if the group exists
-  char label_group_str[MAX_GROUP_STRING_LEN];
-  sprintf(label_group_str, "/Data/Recording_0/Peak_Train/%s/", label);
   herr_t res = H5Lexists(phase->fid, label_group_str, H5P_DEFAULT);
 
   if (res < 0) {
     return SET_PEAK_TRAIN_CHECK_LABEL_GROUP_FAIL;
   } else if (res > 0) {
-    // the group exists. Delete the old datasets
+    // the group exists. Delete the old datasets and the metadata of the
+    // detection, that doesn't describe the new train
+    if (delete_peak_train_metadata(phase->fid, label_group_str) < 0) {
+      return SET_PEAK_TRAIN_DELETE_METADATA_FAIL;
+    }
 
     //   Check if those links exist
     res = H5Lexists(phase->fid, values_group_str, H5P_DEFAULT);
@@ -1029,3 +1427,790 @@ phaseh5_error set_peak_train(PhaseH5* phase, const char* label, const PeakTrain*
 
   return OK;
 }
+
+phaseh5_error n_peak_trains(PhaseH5* phase, size_t* n) {
+  *n = 0;
+  if (phase->peaks_group == 0) {
+    return OK;
+  }
+
+  H5G_info_t info;
+  herr_t res = H5Gget_info_by_name(phase->fid, phase->peaks_path, &info, H5P_DEFAULT);
+  if (res < 0) {
+    return PEAK_TRAIN_LABELS_GET_GROUP_INFO_FAIL;
+  }
+  *n = info.nlinks;
+
+  return OK;
+}
+
+/*
+  Copy in `label` the name of the `index`-th peak train, in alphabetical
+  order. `label` must be at least CHANNEL_LABEL_STRING_LEN long.
+ */
+phaseh5_error peak_train_label(PhaseH5* phase, size_t index, char* label) {
+  if (phase->peaks_group == 0) {
+    return PEAK_TRAIN_NO_PEAK_GROUP;
+  }
+
+  ssize_t len = H5Lget_name_by_idx(phase->fid,
+                                   phase->peaks_path,
+                                   H5_INDEX_NAME,
+                                   H5_ITER_INC,
+                                   index,
+                                   label,
+                                   CHANNEL_LABEL_STRING_LEN,
+                                   H5P_DEFAULT);
+  if (len < 0) {
+    return PEAK_TRAIN_LABEL_GET_NAME_FAIL;
+  } else if (len >= CHANNEL_LABEL_STRING_LEN) {
+    return PEAK_TRAIN_LABEL_TOO_LONG;
+  }
+
+  return OK;
+}
+
+phaseh5_error delete_peak_train(PhaseH5* phase, const char* label) {
+  if (phase->read_only) {
+    return PHASE_IS_READ_ONLY;
+  }
+  if (phase->peaks_group == 0) {
+    return PEAK_TRAIN_NO_PEAK_GROUP;
+  }
+
+  char label_group_str[MAX_GROUP_STRING_LEN];
+  if (!peak_train_path(phase, label, "", label_group_str)) {
+    return PEAK_TRAIN_PATH_TOO_LONG;
+  }
+
+  herr_t res = H5Lexists(phase->fid, label_group_str, H5P_DEFAULT);
+  if (res < 0) {
+    return DELETE_PEAK_TRAIN_CHECK_LABEL_GROUP_FAIL;
+  } else if (res == 0) {
+    return DELETE_PEAK_TRAIN_NO_LABEL_GROUP;
+  }
+
+  res = H5Ldelete(phase->fid, label_group_str, H5P_DEFAULT);
+  if (res < 0) {
+    return DELETE_PEAK_TRAIN_GROUP_FAIL;
+  }
+
+  return OK;
+}
+
+phaseh5_error rename_peak_train(PhaseH5* phase, const char* label, const char* new_label) {
+  if (phase->read_only) {
+    return PHASE_IS_READ_ONLY;
+  }
+  if (phase->peaks_group == 0) {
+    return PEAK_TRAIN_NO_PEAK_GROUP;
+  }
+
+  char label_group_str[MAX_GROUP_STRING_LEN];
+  char new_label_group_str[MAX_GROUP_STRING_LEN];
+  if (!peak_train_path(phase, label, "", label_group_str) ||
+      !peak_train_path(phase, new_label, "", new_label_group_str)) {
+    return PEAK_TRAIN_PATH_TOO_LONG;
+  }
+
+  herr_t res = H5Lexists(phase->fid, label_group_str, H5P_DEFAULT);
+  if (res < 0) {
+    return RENAME_PEAK_TRAIN_CHECK_LABEL_GROUP_FAIL;
+  } else if (res == 0) {
+    return RENAME_PEAK_TRAIN_NO_LABEL_GROUP;
+  }
+
+  res = H5Lexists(phase->fid, new_label_group_str, H5P_DEFAULT);
+  if (res < 0) {
+    return RENAME_PEAK_TRAIN_CHECK_LABEL_GROUP_FAIL;
+  } else if (res > 0) {
+    return RENAME_PEAK_TRAIN_LABEL_ALREADY_EXISTS;
+  }
+
+  res = H5Lmove(phase->fid, label_group_str, phase->fid, new_label_group_str, H5P_DEFAULT, H5P_DEFAULT);
+  if (res < 0) {
+    return RENAME_PEAK_TRAIN_MOVE_GROUP_FAIL;
+  }
+
+  return OK;
+}
+
+/// read a scalar attribute of `object` with the native `type`
+herr_t read_scalar_attribute(hid_t loc, const char* object, const char* name, hid_t type, void* buf) {
+  hid_t attribute = H5Aopen_by_name(loc, object, name, H5P_DEFAULT, H5P_DEFAULT);
+  if (attribute <= 0) {
+    return -1;
+  }
+  herr_t res = H5Aread(attribute, type, buf);
+  H5Aclose(attribute);
+  return res;
+}
+
+phaseh5_error peak_train_metadata(PhaseH5* phase, const char* label, PeakTrainMetadata* metadata) {
+  if (phase->peaks_group == 0) {
+    return PEAK_TRAIN_NO_PEAK_GROUP;
+  }
+
+  char label_group_str[MAX_GROUP_STRING_LEN];
+  if (!peak_train_path(phase, label, "", label_group_str)) {
+    return PEAK_TRAIN_PATH_TOO_LONG;
+  }
+
+  herr_t res = H5Lexists(phase->fid, label_group_str, H5P_DEFAULT);
+  if (res < 0) {
+    return PEAK_TRAIN_METADATA_CHECK_LABEL_GROUP_FAIL;
+  } else if (res == 0) {
+    return PEAK_TRAIN_METADATA_NO_LABEL_GROUP;
+  }
+
+  // trains set without a detection have no metadata
+  res = H5Aexists_by_name(phase->fid, label_group_str, "Threshold", H5P_DEFAULT);
+  if (res < 0) {
+    return PEAK_TRAIN_METADATA_READ_ATTRIBUTE_FAIL;
+  } else if (res == 0) {
+    return PEAK_TRAIN_NO_METADATA;
+  }
+
+  if (read_scalar_attribute(phase->fid, label_group_str, "Threshold", H5T_NATIVE_FLOAT, &metadata->threshold) < 0 ||
+      read_scalar_attribute(phase->fid, label_group_str, "Multiplier", H5T_NATIVE_FLOAT, &metadata->multiplier) < 0 ||
+      read_scalar_attribute(phase->fid, label_group_str, "PeakDuration", H5T_NATIVE_FLOAT, &metadata->peak_duration) < 0 ||
+      read_scalar_attribute(phase->fid, label_group_str, "RefractoryTime", H5T_NATIVE_FLOAT, &metadata->refractory_time) < 0 ||
+      read_scalar_attribute(phase->fid, label_group_str, "Start", H5T_NATIVE_LLONG, &metadata->start) < 0 ||
+      read_scalar_attribute(phase->fid, label_group_str, "End", H5T_NATIVE_LLONG, &metadata->end) < 0 ||
+      read_scalar_attribute(phase->fid, label_group_str, "Created", H5T_NATIVE_LLONG, &metadata->created) < 0) {
+    return PEAK_TRAIN_METADATA_READ_ATTRIBUTE_FAIL;
+  }
+
+  hid_t version_attribute = H5Aopen_by_name(phase->fid, label_group_str, "LibraryVersion", H5P_DEFAULT, H5P_DEFAULT);
+  if (version_attribute <= 0) {
+    return PEAK_TRAIN_METADATA_READ_ATTRIBUTE_FAIL;
+  }
+  res = read_string_attribute(version_attribute, metadata->library_version, VERSION_STRING_LEN);
+  H5Aclose(version_attribute);
+  if (res < 0) {
+    return PEAK_TRAIN_METADATA_READ_ATTRIBUTE_FAIL;
+  }
+
+  return OK;
+}
+
+phaseh5_error set_peak_train_metadata(PhaseH5* phase, const char* label, const PeakTrainMetadata* metadata) {
+  if (phase->read_only) {
+    return PHASE_IS_READ_ONLY;
+  }
+  if (phase->peaks_group == 0) {
+    return PEAK_TRAIN_NO_PEAK_GROUP;
+  }
+
+  char label_group_str[MAX_GROUP_STRING_LEN];
+  if (!peak_train_path(phase, label, "", label_group_str)) {
+    return PEAK_TRAIN_PATH_TOO_LONG;
+  }
+
+  herr_t res = H5Lexists(phase->fid, label_group_str, H5P_DEFAULT);
+  if (res < 0) {
+    return PEAK_TRAIN_METADATA_CHECK_LABEL_GROUP_FAIL;
+  } else if (res == 0) {
+    return PEAK_TRAIN_METADATA_NO_LABEL_GROUP;
+  }
+
+  phaseh5_error err;
+  if ((err = h5_write_float_attribute(phase->fid, label_group_str, "Threshold", metadata->threshold)) != OK ||
+      (err = h5_write_float_attribute(phase->fid, label_group_str, "Multiplier", metadata->multiplier)) != OK ||
+      (err = h5_write_float_attribute(phase->fid, label_group_str, "PeakDuration", metadata->peak_duration)) != OK ||
+      (err = h5_write_float_attribute(phase->fid, label_group_str, "RefractoryTime", metadata->refractory_time)) != OK ||
+      (err = h5_write_llong_attribute(phase->fid, label_group_str, "Start", metadata->start)) != OK ||
+      (err = h5_write_llong_attribute(phase->fid, label_group_str, "End", metadata->end)) != OK ||
+      (err = h5_write_llong_attribute(phase->fid, label_group_str, "Created", metadata->created)) != OK ||
+      (err = h5_write_string_attribute(phase->fid, label_group_str, "LibraryVersion", metadata->library_version)) != OK) {
+    return err;
+  }
+
+  return OK;
+}
+
+//==============================================================================
+//                      PEAK TRAIN RUNS RELATED FUNCTIONS
+//==============================================================================
+
+/// write in `path` the path of the group of a detection run. Returns false
+/// if it doesn't fit MAX_GROUP_STRING_LEN
+bool peak_run_path(const char* run, char* path) {
+  int len;
+  if (strcmp(run, DEFAULT_PEAK_RUN) == 0) {
+    len = snprintf(path, MAX_GROUP_STRING_LEN, "/Data/Recording_0/Peak_Train");
+  } else {
+    len = snprintf(path, MAX_GROUP_STRING_LEN, "/Data/Recording_0/Peaks/%s", run);
+  }
+  return len >= 0 && len < MAX_GROUP_STRING_LEN;
+}
+
+/// check if `run` can name the group of a detection run: it can't be empty
+/// nor contain a '/'
+bool peak_run_name_valid(const char* run) {
+  return run[0] != 0 && strchr(run, '/') == NULL;
+}
+
+/// check if the group of a detection run exists
+htri_t peak_run_exists(PhaseH5* phase, const char* run) {
+  if (strcmp(run, DEFAULT_PEAK_RUN) != 0) {
+    htri_t res = H5Lexists(phase->fid, "/Data/Recording_0/Peaks", H5P_DEFAULT);
+    if (res <= 0) {
+      return res;
+    }
+  }
+  // a name invalid or too long can't be set, so such a run doesn't exist
+  char path[MAX_GROUP_STRING_LEN];
+  if (!peak_run_name_valid(run) || strlen(run) >= PEAK_RUN_STRING_LEN ||
+      !peak_run_path(run, path)) {
+    return 0;
+  }
+  return H5Lexists(phase->fid, path, H5P_DEFAULT);
+}
+
+phaseh5_error set_peak_run(PhaseH5* phase, const char* run) {
+  if (!peak_run_name_valid(run)) {
+    return PEAK_RUN_INVALID_NAME;
+  }
+  if (strlen(run) >= PEAK_RUN_STRING_LEN) {
+    return PEAK_RUN_NAME_TOO_LONG;
+  }
+
+  char path[MAX_GROUP_STRING_LEN];
+  if (!peak_run_path(run, path)) {
+    return PEAK_RUN_NAME_TOO_LONG;
+  }
+
+  htri_t res = peak_run_exists(phase, run);
+  if (res < 0) {
+    return PEAK_RUN_CHECK_GROUP_FAIL;
+  } else if (res == 0 && phase->read_only) {
+    return PHASE_IS_READ_ONLY;
+  } else if (res == 0) {
+    phaseh5_error err = h5_create_group(phase->fid, path);
+    if (err != OK) {
+      return err;
+    }
+  }
+
+  strcpy(phase->peak_run, run);
+  strcpy(phase->peaks_path, path);
+  phase->peaks_group = true;
+
+  return OK;
+}
+
+phaseh5_error n_peak_runs(PhaseH5* phase, size_t* n) {
+  *n = 0;
+
+  htri_t res = H5Lexists(phase->fid, "/Data/Recording_0/Peak_Train", H5P_DEFAULT);
+  if (res < 0) {
+    return PEAK_RUN_CHECK_GROUP_FAIL;
+  } else if (res > 0) {
+    *n += 1;
+  }
+
+  res = H5Lexists(phase->fid, "/Data/Recording_0/Peaks", H5P_DEFAULT);
+  if (res < 0) {
+    return PEAK_RUN_CHECK_GROUP_FAIL;
+  } else if (res > 0) {
+    H5G_info_t info;
+    if (H5Gget_info_by_name(phase->fid, "/Data/Recording_0/Peaks", &info, H5P_DEFAULT) < 0) {
+      return PEAK_RUNS_GET_GROUP_INFO_FAIL;
+    }
+    *n += info.nlinks;
+  }
+
+  return OK;
+}
+
+/*
+  Copy in `run` the name of the `index`-th detection run. The DEFAULT_PEAK_RUN
+  comes first if present, then the others in alphabetical order. `run` must
+  be at least PEAK_RUN_STRING_LEN long.
+ */
+phaseh5_error peak_run_name(PhaseH5* phase, size_t index, char* run) {
+  htri_t res = H5Lexists(phase->fid, "/Data/Recording_0/Peak_Train", H5P_DEFAULT);
+  if (res < 0) {
+    return PEAK_RUN_CHECK_GROUP_FAIL;
+  } else if (res > 0) {
+    if (index == 0) {
+      strcpy(run, DEFAULT_PEAK_RUN);
+      return OK;
+    }
+    index -= 1;
+  }
+
+  ssize_t len = H5Lget_name_by_idx(phase->fid,
+                                   "/Data/Recording_0/Peaks",
+                                   H5_INDEX_NAME,
+                                   H5_ITER_INC,
+                                   index,
+                                   run,
+                                   PEAK_RUN_STRING_LEN,
+                                   H5P_DEFAULT);
+  if (len < 0) {
+    return PEAK_RUN_GET_NAME_FAIL;
+  } else if (len >= PEAK_RUN_STRING_LEN) {
+    return PEAK_RUN_NAME_TOO_LONG;
+  }
+
+  return OK;
+}
+
+/*
+  Copy all the peak trains of `run` in `new_run`, replacing its content if it
+  already exists
+ */
+phaseh5_error copy_peak_run(PhaseH5* phase, const char* run, const char* new_run) {
+  if (phase->read_only) {
+    return PHASE_IS_READ_ONLY;
+  }
+  if (!peak_run_name_valid(new_run)) {
+    return PEAK_RUN_INVALID_NAME;
+  }
+  if (strlen(new_run) >= PEAK_RUN_STRING_LEN) {
+    return PEAK_RUN_NAME_TOO_LONG;
+  }
+
+  htri_t res = peak_run_exists(phase, run);
+  if (res < 0) {
+    return PEAK_RUN_CHECK_GROUP_FAIL;
+  } else if (res == 0) {
+    return PEAK_RUN_NOT_FOUND;
+  }
+  if (strcmp(run, new_run) == 0) {
+    return OK;
+  }
+
+  char path[MAX_GROUP_STRING_LEN];
+  char new_path[MAX_GROUP_STRING_LEN];
+  if (!peak_run_path(run, path) || !peak_run_path(new_run, new_path)) {
+    return PEAK_RUN_NAME_TOO_LONG;
+  }
+
+  phaseh5_error err = h5_delete_link(phase->fid, new_path);
+  if (err != OK) {
+    return err;
+  }
+
+  hid_t lcpl = H5Pcreate(H5P_LINK_CREATE);
+  H5Pset_create_intermediate_group(lcpl, 1);
+  herr_t copy_res = H5Ocopy(phase->fid, path, phase->fid, new_path, H5P_DEFAULT, lcpl);
+  H5Pclose(lcpl);
+  if (copy_res < 0) {
+    return COPY_PEAK_RUN_FAIL;
+  }
+
+  return OK;
+}
+
+//==============================================================================
+//                      GENERIC HDF5 WRITING FUNCTIONS
+//==============================================================================
+
+hid_t native_type(h5_type type) {
+  switch (type) {
+  case H5_TYPE_INT:
+    return H5T_NATIVE_INT;
+  case H5_TYPE_LLONG:
+    return H5T_NATIVE_LLONG;
+  case H5_TYPE_ULLONG:
+    return H5T_NATIVE_ULLONG;
+  case H5_TYPE_FLOAT:
+    return H5T_NATIVE_FLOAT;
+  case H5_TYPE_DOUBLE:
+    return H5T_NATIVE_DOUBLE;
+  }
+  return H5T_NATIVE_INT;
+}
+
+hid_t h5_create_file(const char* filename) {
+  return H5Fcreate(filename, H5F_ACC_TRUNC, H5P_DEFAULT, H5P_DEFAULT);
+}
+
+phaseh5_error h5_close_file(hid_t fid) {
+  if (H5Fclose(fid) < 0) {
+    return H5_CLOSE_FILE_FAIL;
+  }
+  return OK;
+}
+
+phaseh5_error h5_create_group(hid_t loc, const char* path) {
+  htri_t exists = H5Lexists(loc, path, H5P_DEFAULT);
+  if (exists > 0) {
+    return OK;
+  }
+
+  hid_t lcpl = H5Pcreate(H5P_LINK_CREATE);
+  H5Pset_create_intermediate_group(lcpl, 1);
+  hid_t group = H5Gcreate2(loc, path, lcpl, H5P_DEFAULT, H5P_DEFAULT);
+  H5Pclose(lcpl);
+  if (group <= 0) {
+    return H5_CREATE_GROUP_FAIL;
+  }
+  H5Gclose(group);
+  return OK;
+}
+
+phaseh5_error h5_delete_link(hid_t loc, const char* path) {
+  htri_t exists = H5Lexists(loc, path, H5P_DEFAULT);
+  if (exists > 0 && H5Ldelete(loc, path, H5P_DEFAULT) < 0) {
+    return H5_DELETE_LINK_FAIL;
+  }
+  return OK;
+}
+
+/// create a scalar attribute, deleting the previous one if present
+hid_t create_scalar_attribute(hid_t loc, const char* object, const char* name, hid_t type) {
+  if (H5Aexists_by_name(loc, object, name, H5P_DEFAULT) > 0) {
+    H5Adelete_by_name(loc, object, name, H5P_DEFAULT);
+  }
+  hid_t dataspace = H5Screate(H5S_SCALAR);
+  hid_t attribute = H5Acreate_by_name(loc, object, name, type, dataspace, H5P_DEFAULT,
+                                      H5P_DEFAULT, H5P_DEFAULT);
+  H5Sclose(dataspace);
+  return attribute;
+}
+
+phaseh5_error h5_write_string_attribute(hid_t loc, const char* object, const char* name, const char* value) {
+  hid_t attribute = create_scalar_attribute(loc, object, name, HDF5StringType);
+  if (attribute <= 0) {
+    return H5_CREATE_ATTRIBUTE_FAIL;
+  }
+  herr_t res = H5Awrite(attribute, HDF5StringType, (const void*)&value);
+  H5Aclose(attribute);
+  if (res < 0) {
+    return H5_WRITE_ATTRIBUTE_FAIL;
+  }
+  return OK;
+}
+
+phaseh5_error h5_write_llong_attribute(hid_t loc, const char* object, const char* name, LLONG_TYPE value) {
+  hid_t attribute = create_scalar_attribute(loc, object, name, H5T_NATIVE_LLONG);
+  if (attribute <= 0) {
+    return H5_CREATE_ATTRIBUTE_FAIL;
+  }
+  herr_t res = H5Awrite(attribute, H5T_NATIVE_LLONG, (const void*)&value);
+  H5Aclose(attribute);
+  if (res < 0) {
+    return H5_WRITE_ATTRIBUTE_FAIL;
+  }
+  return OK;
+}
+
+phaseh5_error h5_write_float_attribute(hid_t loc, const char* object, const char* name, float value) {
+  hid_t attribute = create_scalar_attribute(loc, object, name, H5T_NATIVE_FLOAT);
+  if (attribute <= 0) {
+    return H5_CREATE_ATTRIBUTE_FAIL;
+  }
+  herr_t res = H5Awrite(attribute, H5T_NATIVE_FLOAT, (const void*)&value);
+  H5Aclose(attribute);
+  if (res < 0) {
+    return H5_WRITE_ATTRIBUTE_FAIL;
+  }
+  return OK;
+}
+
+/// create a dataset and its intermediate groups, replacing the previous one
+/// if present, and write `buf` to it, if not NULL
+phaseh5_error write_new_dataset(hid_t loc, const char* path, hid_t type, int rank,
+                                const hsize_t* dims, const void* buf) {
+  phaseh5_error err = h5_delete_link(loc, path);
+  if (err != OK) {
+    return err;
+  }
+
+  hid_t dataspace = rank == 0 ? H5Screate(H5S_SCALAR) : H5Screate_simple(rank, dims, NULL);
+  hid_t lcpl = H5Pcreate(H5P_LINK_CREATE);
+  H5Pset_create_intermediate_group(lcpl, 1);
+  hid_t dataset = H5Dcreate2(loc, path, type, dataspace, lcpl, H5P_DEFAULT, H5P_DEFAULT);
+  H5Pclose(lcpl);
+  H5Sclose(dataspace);
+  if (dataset <= 0) {
+    return H5_CREATE_DATASET_FAIL;
+  }
+  if (buf == NULL) {
+    H5Dclose(dataset);
+    return OK;
+  }
+
+  herr_t res = H5Dwrite(dataset, type, H5S_ALL, H5S_ALL, H5P_DEFAULT, buf);
+  H5Dclose(dataset);
+  if (res < 0) {
+    return H5_WRITE_DATASET_FAIL;
+  }
+  return OK;
+}
+
+phaseh5_error h5_write_dataset(hid_t loc, const char* path, h5_type type, int rank, const hsize_t* dims, const void* buf) {
+  return write_new_dataset(loc, path, native_type(type), rank, dims, buf);
+}
+
+phaseh5_error h5_write_info_channels(hid_t loc, const char* path, const InfoChannel* info_channels, size_t n_channels) {
+  hsize_t dims[] = {n_channels};
+  return write_new_dataset(loc, path, InfoChannelMemoryType, 1, dims, info_channels);
+}
+
+phaseh5_error h5_write_info_events(hid_t loc, const char* path, const InfoEvent* info_events, size_t n_events) {
+  hsize_t dims[] = {n_events};
+  return write_new_dataset(loc, path, InfoEventMemoryType, 1, dims, info_events);
+}
+
+phaseh5_error h5_create_soft_link(hid_t loc, const char* path, const char* target) {
+  phaseh5_error err = h5_delete_link(loc, path);
+  if (err != OK) {
+    return err;
+  }
+
+  hid_t lcpl = H5Pcreate(H5P_LINK_CREATE);
+  H5Pset_create_intermediate_group(lcpl, 1);
+  herr_t res = H5Lcreate_soft(target, loc, path, lcpl, H5P_DEFAULT);
+  H5Pclose(lcpl);
+  if (res < 0) {
+    return H5_CREATE_LINK_FAIL;
+  }
+  return OK;
+}
+
+phaseh5_error h5_write_string_array_attribute(hid_t loc, const char* object, const char* name, const char* const* values, size_t n) {
+  if (H5Aexists_by_name(loc, object, name, H5P_DEFAULT) > 0) {
+    H5Adelete_by_name(loc, object, name, H5P_DEFAULT);
+  }
+  hsize_t dims[] = {n};
+  hid_t dataspace = H5Screate_simple(1, dims, NULL);
+  hid_t attribute = H5Acreate_by_name(loc, object, name, HDF5StringType, dataspace, H5P_DEFAULT,
+                                      H5P_DEFAULT, H5P_DEFAULT);
+  H5Sclose(dataspace);
+  if (attribute <= 0) {
+    return H5_CREATE_ATTRIBUTE_FAIL;
+  }
+  herr_t res = H5Awrite(attribute, HDF5StringType, (const void*)values);
+  H5Aclose(attribute);
+  if (res < 0) {
+    return H5_WRITE_ATTRIBUTE_FAIL;
+  }
+  return OK;
+}
+
+phaseh5_error h5_write_reference_attribute(hid_t loc, const char* object, const char* name, const char* target) {
+  hobj_ref_t reference;
+  if (H5Rcreate(&reference, loc, target, H5R_OBJECT, -1) < 0) {
+    return H5_CREATE_REFERENCE_FAIL;
+  }
+  hid_t attribute = create_scalar_attribute(loc, object, name, H5T_STD_REF_OBJ);
+  if (attribute <= 0) {
+    return H5_CREATE_ATTRIBUTE_FAIL;
+  }
+  herr_t res = H5Awrite(attribute, H5T_STD_REF_OBJ, (const void*)&reference);
+  H5Aclose(attribute);
+  if (res < 0) {
+    return H5_WRITE_ATTRIBUTE_FAIL;
+  }
+  return OK;
+}
+
+phaseh5_error h5_write_string_dataset(hid_t loc, const char* path, int rank, const hsize_t* dims, const char* const* values) {
+  return write_new_dataset(loc, path, HDF5StringType, rank, dims, values);
+}
+
+phaseh5_error h5_write_reference_dataset(hid_t loc, const char* path, const char* const* targets, size_t n) {
+  hobj_ref_t* references = malloc(sizeof(hobj_ref_t) * (n > 0 ? n : 1));
+  if (references == NULL) {
+    return H5_CREATE_REFERENCE_FAIL;
+  }
+  for (size_t i = 0; i < n; ++i) {
+    if (H5Rcreate(&references[i], loc, targets[i], H5R_OBJECT, -1) < 0) {
+      free(references);
+      return H5_CREATE_REFERENCE_FAIL;
+    }
+  }
+  hsize_t dims[] = {n};
+  phaseh5_error err = write_new_dataset(loc, path, H5T_STD_REF_OBJ, 1, dims, references);
+  free(references);
+  return err;
+}
+
+phaseh5_error h5_create_dataset(hid_t loc, const char* path, h5_type type, int rank, const hsize_t* dims) {
+  return write_new_dataset(loc, path, native_type(type), rank, dims, NULL);
+}
+
+phaseh5_error h5_write_dataset_slice(hid_t loc, const char* path, h5_type type, const hsize_t* start, const hsize_t* count, const void* buf) {
+  hid_t dataset = H5Dopen2(loc, path, H5P_DEFAULT);
+  if (dataset <= 0) {
+    return H5_OPEN_DATASET_FAIL;
+  }
+  hid_t file_dataspace = H5Dget_space(dataset);
+  int rank = H5Sget_simple_extent_ndims(file_dataspace);
+  if (H5Sselect_hyperslab(file_dataspace, H5S_SELECT_SET, start, NULL, count, NULL) < 0) {
+    H5Sclose(file_dataspace);
+    H5Dclose(dataset);
+    return H5_SELECT_HYPERSLAB_FAIL;
+  }
+  hid_t memory_dataspace = H5Screate_simple(rank, count, NULL);
+  herr_t res = H5Dwrite(dataset, native_type(type), memory_dataspace, file_dataspace, H5P_DEFAULT, buf);
+  H5Sclose(memory_dataspace);
+  H5Sclose(file_dataspace);
+  H5Dclose(dataset);
+  if (res < 0) {
+    return H5_WRITE_DATASET_FAIL;
+  }
+  return OK;
+}
+
+hid_t h5_open_file(const char* filename) {
+  return H5Fopen(filename, H5F_ACC_RDONLY, H5P_DEFAULT);
+}
+
+phaseh5_error h5_dataset_dims(hid_t loc, const char* path, int max_rank, int* rank, hsize_t* dims) {
+  hid_t dataset = H5Dopen2(loc, path, H5P_DEFAULT);
+  if (dataset <= 0) {
+    return H5_OPEN_DATASET_FAIL;
+  }
+  hid_t dataspace = H5Dget_space(dataset);
+  *rank = H5Sget_simple_extent_ndims(dataspace);
+  phaseh5_error err = OK;
+  if (*rank > max_rank) {
+    err = H5_DATASET_RANK_TOO_LARGE;
+  } else {
+    H5Sget_simple_extent_dims(dataspace, dims, NULL);
+  }
+  H5Sclose(dataspace);
+  H5Dclose(dataset);
+  return err;
+}
+
+phaseh5_error h5_read_dataset(hid_t loc, const char* path, h5_type type, void* buf) {
+  hid_t dataset = H5Dopen2(loc, path, H5P_DEFAULT);
+  if (dataset <= 0) {
+    return H5_OPEN_DATASET_FAIL;
+  }
+  herr_t res = H5Dread(dataset, native_type(type), H5S_ALL, H5S_ALL, H5P_DEFAULT, buf);
+  H5Dclose(dataset);
+  if (res < 0) {
+    return H5_READ_DATASET_FAIL;
+  }
+  return OK;
+}
+
+phaseh5_error h5_read_string_attribute(hid_t loc, const char* object, const char* name, char* buf, size_t len) {
+  hid_t attribute = H5Aopen_by_name(loc, object, name, H5P_DEFAULT, H5P_DEFAULT);
+  if (attribute <= 0) {
+    return H5_OPEN_ATTRIBUTE_FAIL;
+  }
+  hid_t type = H5Aget_type(attribute);
+  herr_t res;
+  if (H5Tis_variable_str(type) > 0) {
+    char* value = NULL;
+    res = H5Aread(attribute, HDF5StringType, (void*)&value);
+    if (res >= 0 && value != NULL) {
+      strncpy(buf, value, len - 1);
+      buf[len - 1] = '\0';
+      H5free_memory(value);
+    } else {
+      res = -1;
+    }
+  } else {
+    size_t size = H5Tget_size(type);
+    char* value = calloc(size + 1, 1);
+    res = H5Aread(attribute, type, value);
+    if (res >= 0) {
+      strncpy(buf, value, len - 1);
+      buf[len - 1] = '\0';
+    }
+    free(value);
+  }
+  H5Tclose(type);
+  H5Aclose(attribute);
+  if (res < 0) {
+    return H5_READ_ATTRIBUTE_FAIL;
+  }
+  return OK;
+}
+
+phaseh5_error h5_string_attribute_len(hid_t loc, const char* object, const char* name, size_t* len) {
+  hid_t attribute = H5Aopen_by_name(loc, object, name, H5P_DEFAULT, H5P_DEFAULT);
+  if (attribute <= 0) {
+    return H5_OPEN_ATTRIBUTE_FAIL;
+  }
+  hid_t type = H5Aget_type(attribute);
+  phaseh5_error err = OK;
+  if (H5Tis_variable_str(type) > 0) {
+    char* value = NULL;
+    if (H5Aread(attribute, HDF5StringType, (void*)&value) < 0 || value == NULL) {
+      err = H5_READ_ATTRIBUTE_FAIL;
+    } else {
+      *len = strlen(value) + 1;
+      H5free_memory(value);
+    }
+  } else {
+    *len = H5Tget_size(type) + 1;
+  }
+  H5Tclose(type);
+  H5Aclose(attribute);
+  return err;
+}
+
+int h5_object_exists(hid_t loc, const char* path) {
+  char partial[MAX_GROUP_STRING_LEN];
+  size_t len = strlen(path);
+  if (len >= MAX_GROUP_STRING_LEN) {
+    return 0;
+  }
+  // every link of the path must exist before checking the next one
+  for (size_t i = 1; i <= len; ++i) {
+    if (i == len || path[i] == '/') {
+      strncpy(partial, path, i);
+      partial[i] = '\0';
+      if (H5Lexists(loc, partial, H5P_DEFAULT) <= 0) {
+        return 0;
+      }
+    }
+  }
+  return 1;
+}
+
+phaseh5_error h5_read_dataset_slice(hid_t loc, const char* path, h5_type type, const hsize_t* start, const hsize_t* count, void* buf) {
+  return h5_read_dataset_strided(loc, path, type, start, NULL, count, buf);
+}
+
+phaseh5_error h5_read_dataset_strided(hid_t loc, const char* path, h5_type type, const hsize_t* start, const hsize_t* stride, const hsize_t* count, void* buf) {
+  hid_t dataset = H5Dopen2(loc, path, H5P_DEFAULT);
+  if (dataset <= 0) {
+    return H5_OPEN_DATASET_FAIL;
+  }
+  hid_t file_dataspace = H5Dget_space(dataset);
+  int rank = H5Sget_simple_extent_ndims(file_dataspace);
+  if (H5Sselect_hyperslab(file_dataspace, H5S_SELECT_SET, start, stride, count, NULL) < 0) {
+    H5Sclose(file_dataspace);
+    H5Dclose(dataset);
+    return H5_SELECT_HYPERSLAB_FAIL;
+  }
+  hid_t memory_dataspace = H5Screate_simple(rank, count, NULL);
+  herr_t res = H5Dread(dataset, native_type(type), memory_dataspace, file_dataspace, H5P_DEFAULT, buf);
+  H5Sclose(memory_dataspace);
+  H5Sclose(file_dataspace);
+  H5Dclose(dataset);
+  if (res < 0) {
+    return H5_READ_DATASET_FAIL;
+  }
+  return OK;
+}
+
+phaseh5_error h5_read_compound_member(hid_t loc, const char* path, const char* member, h5_type type, void* buf) {
+  hid_t dataset = H5Dopen2(loc, path, H5P_DEFAULT);
+  if (dataset <= 0) {
+    return H5_OPEN_DATASET_FAIL;
+  }
+  // the members of the compound types are matched by name
+  hid_t member_type = native_type(type);
+  hid_t memory_type = H5Tcreate(H5T_COMPOUND, H5Tget_size(member_type));
+  H5Tinsert(memory_type, member, 0, member_type);
+  herr_t res = H5Dread(dataset, memory_type, H5S_ALL, H5S_ALL, H5P_DEFAULT, buf);
+  H5Tclose(memory_type);
+  H5Dclose(dataset);
+  if (res < 0) {
+    return H5_READ_DATASET_FAIL;
+  }
+  return OK;
+}
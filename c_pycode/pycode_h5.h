@@ -6,10 +6,10 @@
 #define DATE_STRING_LEN 32
 #define ANALOG_LABEL_STRING_LEN 64
 #define CHANNEL_LABEL_STRING_LEN 32
-#define MAX_EVENT_STREAMS 16
-#define MAX_CHANNELS 60
-#define MAX_ANALOG_STREAMS 4 
 #define MAX_GROUP_STRING_LEN 256
+#define VERSION_STRING_LEN 32
+#define PEAK_RUN_STRING_LEN 64
+#define DEFAULT_PEAK_RUN "default"
 
 #ifdef _WIN32
 #define LLONG_TYPE long long int
@@ -28,12 +28,18 @@ typedef enum phaseh5_error {
   INIT_CREATE_STRING_TYPE_FAIL,
 
   OPEN_FAIL,
+  OPEN_FILE_ACCESS_PROPERTIES_FAIL,
+  PHASE_IS_READ_ONLY,
   CLOSE_FILE_FAIL,
   OPEN_DATA_GROUP_FAIL,
   OPEN_DATE_ATTRIBUTE_FAIL,
   READ_DATE_ATTRIBUTE_FAIL,
   OPEN_DATE_DATATYPE_FAIL,
+  OPEN_DATE_IN_TICKS_ATTRIBUTE_FAIL,
+  READ_DATE_IN_TICKS_ATTRIBUTE_FAIL,
   OPEN_ALLOCATE_ANALOGS_FAIL,
+  OPEN_ALLOCATE_INFO_CHANNELS_FAIL,
+  OPEN_ALLOCATE_EVENTS_FAIL,
   OPEN_ANALOG_GROUP_FAIL,
   OPEN_INFO_CHANNEL_DATASET_FAIL,
   OPEN_INFO_CHANNEL_DATASPACE_FAIL,
@@ -42,12 +48,11 @@ typedef enum phaseh5_error {
   OPEN_LABEL_ATTRIBUTE_FAIL,
   READ_LABEL_ATTRIBUTE_FAIL,
   OPEN_LABEL_DATATYPE_FAIL,
+  OPEN_DATA_SUBTYPE_ATTRIBUTE_FAIL,
+  READ_DATA_SUBTYPE_ATTRIBUTE_FAIL,
   READ_INFO_CHANNELS_FAIL,
   PARSE_ANALOG_STREAM_DIFFERENT_TICK,
-  MULTIPLE_DIGITAL_STREAMS,
-  MULTIPLE_RAW_DATA_STREAMS,
   MULTIPLE_SAMPLING_FREQUENCIES,
-  MULTIPLE_DATALENS,
   OPEN_CHANNEL_DATA_FAIL,
   OPEN_CHANNEL_DATA_DATASPACE_FAIL,
   GET_CHANNEL_DATA_DIMS_FAIL,
@@ -55,17 +60,22 @@ typedef enum phaseh5_error {
   OPEN_EVENT_STREAM_GROUP_LINK_FAIL,
   OPEN_EVENT_STREAM_GROUP_FAIL,
   OPEN_EVENT_STREAM_STREAM_0_GROUP_LINK_FAIL,
-  MAX_EVENT_STREAMS_EXCEEDED,
   OPEN_ENTITY_DATASET_FAIL,
+  OPEN_INFO_EVENT_DATASET_FAIL,
   EVENT_ENTITY_DATASET_CLOSE_FAIL,
   OPEN_PEAK_TRAIN_GROUP_FAIL,
   CREATE_PEAK_GROUP_FAIL,
+  SET_RAW_DATA_STREAM_INDEX_OUT_OF_BOUNDS,
+  SET_RAW_DATA_STREAM_IS_DIGITAL,
+  ANALOG_DATA_STREAM_INDEX_OUT_OF_BOUNDS,
+  ANALOG_DATA_CHANNEL_INDEX_OUT_OF_BOUNDS,
   RAW_DATA_END_BEFORE_START,
   RAW_DATA_END_OUT_OF_BOUNDS,
   RAW_DATA_GET_DATASPACE_FAIL,
   RAW_DATA_SELECT_HYPERSLAB_FAIL,
   RAW_DATA_CREATE_MEMORY_DATASPACE_FAIL,
   RAW_DATA_READ_DATA_FAIL,
+  RAW_DATA_BLOCK_CHANNEL_INDEX_OUT_OF_BOUNDS,
   SET_RAW_DATA_END_BEFORE_START,
   SET_RAW_DATA_END_OUT_OF_BOUNDS,
   SET_RAW_DATA_GET_DATASPACE_FAIL,
@@ -73,6 +83,7 @@ typedef enum phaseh5_error {
   SET_RAW_DATA_CREATE_MEMORY_DATASPACE_FAIL,
   SET_RAW_DATA_WRITE_DATASET_FAIL,
   DIGITAL_NO_DIGITAL,
+  DIGITAL_INDEX_OUT_OF_BOUNDS,
   DIGITAL_END_BEFORE_START,
   DIGITAL_END_OUT_OF_BOUNDS,
   DIGITAL_GET_DATASPACE_FAIL,
@@ -80,6 +91,7 @@ typedef enum phaseh5_error {
   DIGITAL_CREATE_MEMORY_DATASPACE_FAIL,
   DIGITAL_READ_DATA_FAIL,
   SET_DIGITAL_NO_DIGITAL,
+  SET_DIGITAL_INDEX_OUT_OF_BOUNDS,
   SET_DIGITAL_END_BEFORE_START,
   SET_DIGITAL_END_OUT_OF_BOUNDS,
   SET_DIGITAL_GET_DATASPACE_FAIL,
@@ -141,8 +153,60 @@ typedef enum phaseh5_error {
   SET_PEAK_TRAIN_CLOSE_VALUES_MEMORY_DATASPACE_FAIL,
   SET_PEAK_TRAIN_CLOSE_SAMPLES_DATASET_FAIL,
   SET_PEAK_TRAIN_CLOSE_VALUES_DATASET_FAIL,
+  SET_PEAK_TRAIN_DELETE_METADATA_FAIL,
+  PEAK_TRAIN_LABELS_GET_GROUP_INFO_FAIL,
+  PEAK_TRAIN_LABEL_GET_NAME_FAIL,
+  PEAK_TRAIN_LABEL_TOO_LONG,
+  PEAK_TRAIN_PATH_TOO_LONG,
+  DELETE_PEAK_TRAIN_CHECK_LABEL_GROUP_FAIL,
+  DELETE_PEAK_TRAIN_NO_LABEL_GROUP,
+  DELETE_PEAK_TRAIN_GROUP_FAIL,
+  RENAME_PEAK_TRAIN_CHECK_LABEL_GROUP_FAIL,
+  RENAME_PEAK_TRAIN_NO_LABEL_GROUP,
+  RENAME_PEAK_TRAIN_LABEL_ALREADY_EXISTS,
+  RENAME_PEAK_TRAIN_MOVE_GROUP_FAIL,
+  PEAK_TRAIN_METADATA_CHECK_LABEL_GROUP_FAIL,
+  PEAK_TRAIN_METADATA_NO_LABEL_GROUP,
+  PEAK_TRAIN_NO_METADATA,
+  PEAK_TRAIN_METADATA_READ_ATTRIBUTE_FAIL,
+  PEAK_RUN_CHECK_GROUP_FAIL,
+  PEAK_RUN_NOT_FOUND,
+  PEAK_RUN_NAME_TOO_LONG,
+  PEAK_RUN_INVALID_NAME,
+  PEAK_RUNS_GET_GROUP_INFO_FAIL,
+  PEAK_RUN_GET_NAME_FAIL,
+  COPY_PEAK_RUN_FAIL,
+
+  // generic HDF5 writing functions
+  H5_CREATE_FILE_FAIL,
+  H5_CLOSE_FILE_FAIL,
+  H5_CREATE_GROUP_FAIL,
+  H5_DELETE_LINK_FAIL,
+  H5_CREATE_ATTRIBUTE_FAIL,
+  H5_WRITE_ATTRIBUTE_FAIL,
+  H5_CREATE_DATASET_FAIL,
+  H5_WRITE_DATASET_FAIL,
+  H5_CREATE_LINK_FAIL,
+  H5_CREATE_REFERENCE_FAIL,
+  H5_OPEN_DATASET_FAIL,
+  H5_SELECT_HYPERSLAB_FAIL,
+  H5_DATASET_RANK_TOO_LARGE,
+  H5_READ_DATASET_FAIL,
+  H5_OPEN_ATTRIBUTE_FAIL,
+  H5_READ_ATTRIBUTE_FAIL,
 } phaseh5_error;
 
+/*
+  Native datatypes accepted by the generic HDF5 writing functions
+ */
+typedef enum h5_type {
+  H5_TYPE_INT,
+  H5_TYPE_LLONG,
+  H5_TYPE_ULLONG,
+  H5_TYPE_FLOAT,
+  H5_TYPE_DOUBLE,
+} h5_type;
+
 typedef struct InfoChannel {
   int channel_id;
   int row_index;
@@ -164,14 +228,24 @@ typedef struct InfoChannel {
   int low_pass_filter_order;
 } InfoChannel;
 
+typedef struct InfoEvent {
+  int event_id;
+  int group_id;
+  const char *label;
+  const char *source_channel_ids;
+  const char *source_channel_labels;
+} InfoEvent;
+
 typedef struct AnalogStream {
   const char label[ANALOG_LABEL_STRING_LEN];
+  const char data_subtype[ANALOG_LABEL_STRING_LEN];
   hsize_t n_channels;
   // ChannelData dataset
   hid_t channel_data_dataset;
   size_t datalen;
-  // InfoChannel data
-  InfoChannel info_channels[MAX_CHANNELS];
+  float sampling_frequency;
+  // InfoChannel data, n_channels elements
+  InfoChannel *info_channels;
 } AnalogStream;
 
 typedef struct PeakTrain {
@@ -180,17 +254,47 @@ typedef struct PeakTrain {
   long int* samples;
 } PeakTrain;
 
+/*
+  Parameters of the spike detection that produced a peak train, stored as
+  attributes of its group. `start` and `end` are the detected sample range and
+  `created` is the time of the detection in microseconds from the unix epoch.
+ */
+typedef struct PeakTrainMetadata {
+  float threshold;
+  float multiplier;
+  float peak_duration;
+  float refractory_time;
+  LLONG_TYPE start;
+  LLONG_TYPE end;
+  LLONG_TYPE created;
+  char library_version[VERSION_STRING_LEN];
+} PeakTrainMetadata;
+
 typedef struct PhaseH5 {
   hid_t fid;
+  bool read_only;
   char date[DATE_STRING_LEN];
+  LLONG_TYPE date_in_ticks;
   size_t datalen;
   float sampling_frequency;
-  AnalogStream raw_data;
-  bool has_digital;
-  AnalogStream digital;
+  // all the arrays are allocated in phase_open and freed in phase_close
+  size_t n_analogs;
+  AnalogStream *analogs;
+  size_t raw_data_index;
+  size_t n_digitals;
+  size_t *digital_indices;
   int n_events;
-  hid_t event_entities[MAX_EVENT_STREAMS];
+  hid_t *event_entities;
+  // the id of each event entity, from the EventEntity_<id> dataset name
+  int *event_ids;
+  // the InfoEvent table of the event stream, if present
+  size_t n_info_events;
+  InfoEvent *info_events;
   hid_t peaks_group;
+  // the active detection run and the path of its group. The DEFAULT_PEAK_RUN
+  // is the Peak_Train group, the other runs are in the Peaks group
+  char peak_run[PEAK_RUN_STRING_LEN];
+  char peaks_path[MAX_GROUP_STRING_LEN];
 } PhaseH5;
 
 /*
@@ -210,20 +314,88 @@ void pycodeh5_close();
  */
 void init_phase(PhaseH5* phase);
 /*
-  Open a Phase from a .h5 file and parse its content
+  Open a Phase from a .h5 file and parse its content. If `read_only` is set
+  the file is opened without write access and without locking it, so that
+  more processes can read it at the same time
  */
-phaseh5_error phase_open(PhaseH5* phase, const char *filename);
+phaseh5_error phase_open(PhaseH5* phase, const char *filename, bool read_only);
 /*
   Close a PhaseH5 clearing the allocated resources
  */
 phaseh5_error phase_close(PhaseH5* phase);
 
+/*
+  Select the analog stream used as raw data
+ */
+phaseh5_error set_raw_data_stream(PhaseH5* phase, size_t stream);
+
+phaseh5_error analog_data(PhaseH5* phase, size_t stream, size_t index, size_t start, size_t end, int* buf);
 phaseh5_error raw_data(PhaseH5* phase, size_t index, size_t start, size_t end, int* buf);
+/*
+  Read the raw data of `n_indices` channels with a single hyperslab. The
+  `indices` must be sorted and unique, `buf` is filled row by row.
+ */
+phaseh5_error raw_data_block(PhaseH5* phase, const size_t* indices, size_t n_indices, size_t start, size_t end, int* buf);
 phaseh5_error set_raw_data(PhaseH5* phase, size_t index, size_t start, size_t end, const int* buf);
-phaseh5_error digital(PhaseH5* phase, size_t start, size_t end, int* buf);
-phaseh5_error set_digital(PhaseH5* phase, size_t start, size_t end, const int* buf);
+phaseh5_error digital(PhaseH5* phase, size_t index, size_t start, size_t end, int* buf);
+phaseh5_error set_digital(PhaseH5* phase, size_t index, size_t start, size_t end, const int* buf);
 phaseh5_error events_len(PhaseH5* phase, size_t index, hsize_t *len);
 phaseh5_error events(PhaseH5* phase, size_t index, LLONG_TYPE *buf);
+phaseh5_error event_durations(PhaseH5* phase, size_t index, LLONG_TYPE *buf);
 phaseh5_error peak_train_len(PhaseH5*, const char* label, size_t *len);
 phaseh5_error peak_train(PhaseH5* phase, const char* label, PeakTrain* peak_train);
 phaseh5_error set_peak_train(PhaseH5* phase, const char* label, const PeakTrain* peak_train);
+phaseh5_error n_peak_trains(PhaseH5* phase, size_t* n);
+phaseh5_error peak_train_label(PhaseH5* phase, size_t index, char* label);
+phaseh5_error delete_peak_train(PhaseH5* phase, const char* label);
+phaseh5_error rename_peak_train(PhaseH5* phase, const char* label, const char* new_label);
+phaseh5_error peak_train_metadata(PhaseH5* phase, const char* label, PeakTrainMetadata* metadata);
+phaseh5_error set_peak_train_metadata(PhaseH5* phase, const char* label, const PeakTrainMetadata* metadata);
+phaseh5_error set_peak_run(PhaseH5* phase, const char* run);
+phaseh5_error n_peak_runs(PhaseH5* phase, size_t* n);
+phaseh5_error peak_run_name(PhaseH5* phase, size_t index, char* run);
+phaseh5_error copy_peak_run(PhaseH5* phase, const char* run, const char* new_run);
+
+/*
+  Generic HDF5 writing functions, used to create files from scratch or to add
+  data to an opened phase through its `fid`. Paths are relative to `loc` and
+  the missing intermediate groups are created. Datasets and attributes already
+  present are replaced. A `rank` of 0 creates a scalar dataset.
+  References are object references to the `target` path of the same file.
+ */
+hid_t h5_create_file(const char* filename);
+phaseh5_error h5_close_file(hid_t fid);
+phaseh5_error h5_create_group(hid_t loc, const char* path);
+phaseh5_error h5_delete_link(hid_t loc, const char* path);
+phaseh5_error h5_write_string_attribute(hid_t loc, const char* object, const char* name, const char* value);
+phaseh5_error h5_write_llong_attribute(hid_t loc, const char* object, const char* name, LLONG_TYPE value);
+phaseh5_error h5_write_float_attribute(hid_t loc, const char* object, const char* name, float value);
+phaseh5_error h5_write_dataset(hid_t loc, const char* path, h5_type type, int rank, const hsize_t* dims, const void* buf);
+phaseh5_error h5_write_info_channels(hid_t loc, const char* path, const InfoChannel* info_channels, size_t n_channels);
+phaseh5_error h5_write_info_events(hid_t loc, const char* path, const InfoEvent* info_events, size_t n_events);
+phaseh5_error h5_create_soft_link(hid_t loc, const char* path, const char* target);
+phaseh5_error h5_write_string_array_attribute(hid_t loc, const char* object, const char* name, const char* const* values, size_t n);
+phaseh5_error h5_write_reference_attribute(hid_t loc, const char* object, const char* name, const char* target);
+phaseh5_error h5_write_string_dataset(hid_t loc, const char* path, int rank, const hsize_t* dims, const char* const* values);
+phaseh5_error h5_write_reference_dataset(hid_t loc, const char* path, const char* const* targets, size_t n);
+phaseh5_error h5_create_dataset(hid_t loc, const char* path, h5_type type, int rank, const hsize_t* dims);
+phaseh5_error h5_write_dataset_slice(hid_t loc, const char* path, h5_type type, const hsize_t* start, const hsize_t* count, const void* buf);
+
+/*
+  Generic HDF5 reading functions, to check files written with the functions
+  above and to read the files of other acquisition systems. `dims` must have
+  room for `max_rank` values and `buf` for all the read elements. String
+  attributes can be variable or fixed length, `h5_string_attribute_len`
+  returns the room needed by a value, terminator included.
+  `h5_read_dataset_strided` reads `count` elements every `stride` from
+  `start`, e.g. a single channel of interleaved frames.
+ */
+hid_t h5_open_file(const char* filename);
+phaseh5_error h5_dataset_dims(hid_t loc, const char* path, int max_rank, int* rank, hsize_t* dims);
+phaseh5_error h5_read_dataset(hid_t loc, const char* path, h5_type type, void* buf);
+phaseh5_error h5_read_string_attribute(hid_t loc, const char* object, const char* name, char* buf, size_t len);
+phaseh5_error h5_string_attribute_len(hid_t loc, const char* object, const char* name, size_t* len);
+int h5_object_exists(hid_t loc, const char* path);
+phaseh5_error h5_read_dataset_slice(hid_t loc, const char* path, h5_type type, const hsize_t* start, const hsize_t* count, void* buf);
+phaseh5_error h5_read_dataset_strided(hid_t loc, const char* path, h5_type type, const hsize_t* start, const hsize_t* stride, const hsize_t* count, void* buf);
+phaseh5_error h5_read_compound_member(hid_t loc, const char* path, const char* member, h5_type type, void* buf);
@@ -1,20 +1,127 @@
 def init() -> None: ...
 def close() -> None: ...
 
+class ChannelInfo:
+   label: str
+   channel_id: int
+   row_index: int
+   group_id: int
+   electrode_group: int
+   raw_data_type: str
+   unit: str
+   exponent: int
+   ad_zero: int
+   tick: int
+   conversion_factor: int
+   adc_bits: int
+   high_pass_filter_type: str
+   high_pass_filter_cutoff: str
+   high_pass_filter_order: int
+   low_pass_filter_type: str
+   low_pass_filter_cutoff: str
+   low_pass_filter_order: int
+
+class EventEntity:
+   label: str
+   event_id: int
+   timestamps_us: List[int]
+   durations_us: List[int]
+
+class PeakTrainMetadata:
+   threshold: float
+   multiplier: float
+   peak_duration: float
+   refractory_time: float
+   start: int
+   end: int
+   library_version: str
+   created: str
+
+class RawBlock:
+   def labels(self) -> List[str]: ...
+   def start(self) -> int: ...
+   def n_channels(self) -> int: ...
+   def n_samples(self) -> int: ...
+   def data(self) -> List[float]: ...
+   def rows(self) -> List[List[float]]: ...
+   def channel(self, label: str) -> Optional[List[float]]: ...
+   def mean_row(self) -> List[float]: ...
+
+class RawDataChunks:
+   def __iter__(self) -> Iterator[Tuple[int, List[float]]]: ...
+   def __next__(self) -> Tuple[int, List[float]]: ...
+
+class Span:
+   @staticmethod
+   def samples(start: Optional[int] = None, end: Optional[int] = None) -> Span: ...
+   @staticmethod
+   def seconds(start: Optional[float] = None, end: Optional[float] = None) -> Span: ...
+   @staticmethod
+   def millis(start: Optional[float] = None, end: Optional[float] = None) -> Span: ...
+   @staticmethod
+   def all() -> Span: ...
+   def to_samples(self, sampling_frequency: float) -> Tuple[Optional[int], Optional[int]]: ...
+
 class PyPhase:
+   def __init__(self, filename: str, mode: str = "r+") -> None: ...
+   def is_read_only(self) -> bool: ...
    def datalen(self) -> int: ... 
    def sampling_frequency(self) -> float: ...
    def labels(self) -> List[str]: ...
+   def channel_info(self, channel: str) -> ChannelInfo: ...
+
+   def date(self) -> str: ...
+   def recording_start(self) -> Optional[str]: ...
+   def sample_to_seconds(self, sample: int) -> float: ...
+   def seconds_to_sample(self, seconds: float) -> int: ...
+   def micros_to_sample(self, micros: int) -> int: ...
+   def sample_to_datetime(self, sample: int) -> Optional[str]: ...
 
    def raw_data(self, channel: str, start: Optional[int] = None, end: Optional[int] = None) -> List[float]: ...
+   def raw_data_in(self, channel: str, span: Span) -> List[float]: ...
+   def raw_block(self, channels: List[str], start: Optional[int] = None, end: Optional[int] = None) -> RawBlock: ...
+   def raw_block_in(self, channels: List[str], span: Span) -> RawBlock: ...
+   def raw_data_chunks(self, channel: str, chunk_len: int, overlap: int = 0) -> RawDataChunks: ...
    def set_raw_data(self, channel: str, data: List[float], start: Optional[int] = None) -> bool: ...
 
+   def analog_streams(self) -> List[str]: ...
+   def raw_data_stream(self) -> str: ...
+   def set_raw_data_stream(self, stream: str) -> bool: ...
+   def analog_labels(self, stream: str) -> List[str]: ...
+   def analog_data(self, stream: str, channel: str, start: Optional[int] = None, end: Optional[int] = None) -> List[float]: ...
+
    def n_digitals(self) -> int: ...
+   def digital_labels(self) -> List[str]: ...
    def digital(self, index: int, start: Optional[int], end: Optional[int]) -> List[float]: ...
+   def digital_in(self, index: int, span: Span) -> List[float]: ...
    def set_digital(self, index: int, data: List[float], start: Optional[int]) -> bool: ...
 
    def n_events(self) -> int: ...
-   def events(self, index: int) -> List[int]: ...
+   def events(self, index: int) -> EventEntity: ...
+   def event_samples(self, index: int) -> List[int]: ...
+   def event_intervals(self, index: int) -> List[Tuple[int, int]]: ...
 
    def peak_train(self, channel: str, start: Optional[int], end: Optional[int]) -> Tuple[List[int], List[float]]: ...
+   def peak_train_in(self, channel: str, span: Span) -> Tuple[List[int], List[float]]: ...
    def set_peak_train(self, channel: str, data: Tuple[List[int], List[float]], start: Optional[int], end: Optional[int]) -> bool: ...
+   def replace_peak_train_range(self, channel: str, start: int, end: int, data: Tuple[List[int], List[float]]) -> bool: ...
+   def insert_peaks(self, channel: str, data: Tuple[List[int], List[float]]) -> bool: ...
+   def remove_peaks_in(self, channel: str, start: int, end: int) -> Optional[int]: ...
+   def remove_peaks_where(self, channel: str, predicate: Callable[[int, float], bool]) -> Optional[int]: ...
+   def peak_train_labels(self) -> List[str]: ...
+   def delete_peak_train(self, channel: str) -> bool: ...
+   def rename_peak_train(self, channel: str, new_channel: str) -> bool: ...
+   def peak_train_metadata(self, channel: str) -> Optional[PeakTrainMetadata]: ...
+   def clear_peak_trains(self) -> bool: ...
+   def peak_run(self) -> str: ...
+   def peak_runs(self) -> List[str]: ...
+   def set_peak_run(self, run: str) -> bool: ...
+   def copy_peak_run(self, run: str, new_run: str) -> bool: ...
+
+   def export_nwb(self, filename: str, session_description: str = "", utc_offset: str = "+00:00") -> bool: ...
+   def export_mcs(self, filename: str) -> bool: ...
+   def crop(self, filename: str, start: int, end: int) -> bool: ...
+   def split_by_digital(self, digital: int, filename_prefix: str) -> Optional[List[str]]: ...
+   def export_kilosort(self, folder: str, channels: Optional[List[str]] = None, start: Optional[int] = None, end: Optional[int] = None) -> Optional[List[str]]: ...
+   def export_spycode_peak_trains(self, folder: str, experiment: str) -> Optional[List[str]]: ...
+   def import_spycode_peak_trains(self, folder: str, experiment: str) -> Optional[List[str]]: ...
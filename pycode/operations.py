@@ -39,7 +39,12 @@ def subsample_range(
     return py_subsample_range(peaks, starting_sample, bin_size, n_bins)
 
 
-def psth(phase: PyPhase, bin_time_duration: float, psth_duration: float) -> np.ndarray:
+def psth(
+    phase: PyPhase,
+    bin_time_duration: float,
+    psth_duration: float,
+    digital_index: int = 0,
+) -> np.ndarray:
     """
     Compute the PSTH ociaoooooooo :):):)
     and returns a list with the count of the spikes in each bin.
@@ -48,30 +53,35 @@ def psth(phase: PyPhase, bin_time_duration: float, psth_duration: float) -> np.n
     - phase: the Phase of interest
     - bin_time_duration: the duration of the bin IN SECONDS
     - psth_duration: the duration of the whole psth IN SECONDS
+    - digital_index: the index of the digital channel with the stimulation
     """
 
     # OPEN THE PYCODE_RS HANDLER FOR THE DATA
-    sampling_frequency = phase.sampling_frequency()
-    bin_size = int(
-        sampling_frequency * bin_time_duration
-    )  # this round the size of a bin to the lower integer
+    bin_size = phase.seconds_to_sample(
+        bin_time_duration
+    )  # this round the size of a bin to the nearest sample, like the Rust side
+    if not bin_size:
+        exit(
+            f"ERROR: the bin duration of {bin_time_duration} s is shorter than half a sample"
+        )
 
-    n_bins = int(psth_duration / bin_time_duration)  # number of bin after the stimulus
+    n_bins = (
+        phase.seconds_to_sample(psth_duration) // bin_size
+    )  # number of bin after the stimulus
 
     channels = phase.labels()  # list of all the available channels
 
-    # get the number of digital channels. if it's different from 1 an error has occurred
-    # during the recording phase
+    # check that the requested digital channel is present in the recording
     n_digital = phase.n_digitals()
-    if n_digital != 1:
+    if digital_index >= n_digital:
         exit(
-            f"ERROR: the stimulation phase has {n_digital} digital channels (grazie MultiChannel)"
+            f"ERROR: the stimulation phase has {n_digital} digital channels, can't use {digital_index}"
         )
 
     res = np.zeros(n_bins)  # variable to accumulate the psth
 
     # read the digital channel
-    digital = phase.digital(0)
+    digital = phase.digital(digital_index)
     # get the interval timestamps where the stimulation is active
     digital_intervals = get_digital_intervals(digital)
 
@@ -1,5 +1,5 @@
 use crate::{
-    analysis::subsampling::subsample_range, error::SpikeError, operations::math,
+    analysis::subsampling::subsample_range, error::SpikeError, operations::math, time::Time,
     types::PhaseHandler,
 };
 use std::collections::HashMap;
@@ -37,13 +37,18 @@ pub fn get_peaks_bins(range: &[f32], n_bins: usize) -> Option<(Vec<usize>, f32,
     }
 }
 
-/// Count the number of spikes grouped for the duration of `bin_size`
-/// before, during and after the stimulus interval
+/// Count the number of spikes grouped for the duration of `bin_size`, in
+/// samples or seconds, before, during and after the stimulus interval. A
+/// `bin_size` shorter than half a sample is an error.
 pub fn get_subsampled_pre_stim_post_from_intervals(
     phase: &mut impl PhaseHandler,
     intervals: &[(usize, usize)],
-    bin_size: usize,
+    bin_size: impl Into<Time>,
 ) -> Result<HashMap<String, Vec<(Vec<usize>, Vec<usize>, Vec<usize>)>>, SpikeError> {
+    let bin_size = bin_size.into().to_samples(phase.sampling_frequency());
+    if bin_size == 0 {
+        return Err(SpikeError::BinSizeIsZero);
+    }
     let n_intervals = intervals.len();
     let raw_data_len = phase.datalen();
     assert!(n_intervals != 0, "No intervals provided!!!");
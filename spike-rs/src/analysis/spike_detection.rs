@@ -1,4 +1,9 @@
-use crate::{error::SpikeError, operations::math, types::PhaseHandler};
+use crate::{
+    error::SpikeError,
+    operations::math,
+    time::{DateTime, Span},
+    types::{PeakTrainMetadata, PhaseHandler},
+};
 
 pub fn compute_threshold(
     range: &[f32],
@@ -195,21 +200,52 @@ pub fn spike_detection(
     Ok((ret_times, ret_values))
 }
 
+/// Like `compute_peak_train`, on the `span` of the recording
+pub fn compute_peak_train_in(
+    phase: &mut impl PhaseHandler,
+    label: &str,
+    span: &Span,
+) -> Result<(), SpikeError> {
+    let (start, end) = phase.span_to_samples(span);
+    compute_peak_train(phase, label, start, end)
+}
+
+/// Detect the spikes of the raw data channel `label` in the `[start, end)`
+/// range and store them as its peak train, together with the parameters of
+/// the detection
 pub fn compute_peak_train(
     phase: &mut impl PhaseHandler,
     label: &str,
     start: Option<usize>,
     end: Option<usize>,
 ) -> Result<(), SpikeError> {
-    let signal = phase.raw_data(label, start, end)?;
-    let threshold = compute_threshold(&signal[..], phase.sampling_frequency(), 8 as _)?;
+    const MULTIPLIER: f32 = 8f32;
+    const PEAK_DURATION: f32 = 2e-3; // s
+    const REFRACTORY_TIME: f32 = 2e-3; // s
+
+    let start = start.unwrap_or(0);
+    let end = end.unwrap_or(phase.datalen());
+    let signal = phase.raw_data(label, Some(start), Some(end))?;
+    let threshold = compute_threshold(&signal[..], phase.sampling_frequency(), MULTIPLIER)?;
     let peaks_train = spike_detection(
         &signal[..],
         phase.sampling_frequency(),
         threshold,
-        2e-3,
-        2e-3,
+        PEAK_DURATION,
+        REFRACTORY_TIME,
     )?;
-    phase.set_peak_train(label, start, end, peaks_train)?;
+    phase.set_peak_train(label, Some(start), Some(end), peaks_train)?;
+
+    let metadata = PeakTrainMetadata {
+        threshold,
+        multiplier: MULTIPLIER,
+        peak_duration: PEAK_DURATION,
+        refractory_time: REFRACTORY_TIME,
+        start,
+        end,
+        library_version: env!("CARGO_PKG_VERSION").to_string(),
+        created: DateTime::now(),
+    };
+    phase.set_peak_train_metadata(label, &metadata)?;
     Ok(())
 }
@@ -3,6 +3,7 @@ use crate::{
         digital::get_digital_intervals, spike_analysis::get_subsampled_pre_stim_post_from_intervals,
     },
     error::SpikeError,
+    time::Time,
     types::PhaseHandler,
 };
 use std::collections::HashMap;
@@ -57,7 +58,7 @@ pub fn subsample_range(
 
 pub fn subsample_peak_trains(
     phase: &mut impl PhaseHandler,
-    bin_size: usize,
+    bin_size: impl Into<Time>,
     digital_index: usize,
 ) -> Result<HashMap<String, Vec<(Vec<usize>, Vec<usize>, Vec<usize>)>>, SpikeError> {
     if digital_index >= phase.n_digitals() {
@@ -69,10 +70,14 @@ pub fn subsample_peak_trains(
 
 pub fn subsampled_post_stimulus_times(
     phase: &mut impl PhaseHandler,
-    bin_size: usize,
+    bin_size: impl Into<Time>,
     n_bins_post_stim: usize,
     digital_index: usize,
 ) -> Result<Vec<Vec<usize>>, SpikeError> {
+    let bin_size = bin_size.into().to_samples(phase.sampling_frequency());
+    if bin_size == 0 {
+        return Err(SpikeError::BinSizeIsZero);
+    }
     if digital_index >= phase.n_digitals() {
         return Err(SpikeError::IndexOutOfRange);
     }
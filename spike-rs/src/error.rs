@@ -1,21 +1,56 @@
 #[derive(Debug)]
 pub enum SpikeError {
     Implementation(String),
+    PhaseIsReadOnly,
     RawDataStartIsAfterEnd,
     RawDataLabelNotFound,
     RawDataOutOfBounds,
+    RawDataChunksOverlapTooLarge,
+    RawBlockWrongDataLen(usize, usize),
     SetRawDataOutOfBounds,
     SetRawDataLabelNotFound,
+    AnalogStreamNotFound,
     DigitalNoDigitalPresent,
     DigitalStartIsAfterEnd,
+    DigitalOutOfBounds,
+    SetDigitalOutOfBounds,
     IndexOutOfRange,
     ReplaceRangeError,
+    SetPeakTrainSamplesValuesDifferentLen,
+    PeakTrainStartIsAfterEnd,
+    PeakTrainOutOfBounds,
+    PeakTrainNotSorted,
+    PeakTrainDuplicateSample(usize),
+    PeakTrainNotFound,
+    PeakTrainAlreadyExists,
+    PeakRunNotFound,
+    PeakRunInvalidName,
+    MemoryPhaseWrongDataLen(usize, usize),
+    ConcatNoPhases,
+    ConcatSamplingFrequencyMismatch(usize),
+    ConcatLabelsMismatch(usize),
+    ConcatDigitalsMismatch(usize),
+    ConcatEventsMismatch(usize),
+    ConcatEventEntityMismatch(usize),
+
+    Io(std::io::Error),
+    MatFileInvalid(String),
+    MatFileVariableNotFound(String),
+    McDataToolInvalidHeader(String),
+    NpyWrongDataLen(usize, usize),
+    NpyInvalid(String),
+    JsonInvalid(String),
+    OpenEphysInvalid(String),
+    KilosortNoAdcConversion(String),
+    KilosortSampleOutOfRange(String),
+    NwbInvalidUtcOffset(String),
 
     ComputeThresholdTooFewSamples(usize, usize),
 
     SpikeDetectionTooFewSamples,
     NoSpikeTrainsAvailable,
     OperationFailed,
+    BinSizeIsZero,
 
     LogISITooFewSamples,
     LogISICalcThresholdNoIntraIndex,
@@ -32,3 +67,9 @@ impl std::fmt::Display for SpikeError {
 }
 
 impl std::error::Error for SpikeError {}
+
+impl From<std::io::Error> for SpikeError {
+    fn from(err: std::io::Error) -> Self {
+        SpikeError::Io(err)
+    }
+}
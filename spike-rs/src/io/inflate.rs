@@ -0,0 +1,344 @@
+use crate::error::SpikeError;
+
+const MAX_BITS: usize = 15;
+
+/// Base lengths and extra bits of the length symbols 257..285
+const LENGTH_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Base distances and extra bits of the distance symbols 0..29
+const DISTANCE_BASE: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Order of the code lengths of the code lengths alphabet of dynamic blocks
+const CODE_LENGTHS_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn invalid() -> SpikeError {
+    SpikeError::MatFileInvalid("invalid compressed data".to_string())
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    n_bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, n: u32) -> Result<u32, SpikeError> {
+        while self.n_bits < n {
+            let byte = *self.data.get(self.pos).ok_or_else(invalid)?;
+            self.buffer |= (byte as u32) << self.n_bits;
+            self.pos += 1;
+            self.n_bits += 8;
+        }
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.n_bits -= n;
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.buffer = 0;
+        self.n_bits = 0;
+    }
+}
+
+/// Canonical Huffman code, as the number of codes of each length and the
+/// symbols ordered by code
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for length in lengths.iter() {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<usize, SpikeError> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for len in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return self
+                    .symbols
+                    .get((index + code - first) as usize)
+                    .map(|x| *x as usize)
+                    .ok_or_else(invalid);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid())
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5u8; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), SpikeError> {
+    let n_lengths = reader.bits(5)? as usize + 257;
+    let n_distances = reader.bits(5)? as usize + 1;
+    let n_codes = reader.bits(4)? as usize + 4;
+
+    let mut lengths = [0u8; 19];
+    for index in CODE_LENGTHS_ORDER.iter().take(n_codes) {
+        lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&lengths);
+
+    let mut lengths = vec![0u8; n_lengths + n_distances];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_lengths.decode(reader)?;
+        if symbol < 16 {
+            lengths[i] = symbol as u8;
+            i += 1;
+            continue;
+        }
+        let (value, repeat) = match symbol {
+            16 => {
+                let previous = *lengths.get(i.wrapping_sub(1)).ok_or_else(invalid)?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(invalid());
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+
+    Ok((
+        Huffman::new(&lengths[..n_lengths]),
+        Huffman::new(&lengths[n_lengths..]),
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    lengths: &Huffman,
+    distances: &Huffman,
+) -> Result<(), SpikeError> {
+    loop {
+        let symbol = lengths.decode(reader)?;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err(invalid());
+                }
+                let len = LENGTH_BASE[index] + reader.bits(LENGTH_EXTRA[index])? as usize;
+                let index = distances.decode(reader)?;
+                if index >= DISTANCE_BASE.len() {
+                    return Err(invalid());
+                }
+                let distance = DISTANCE_BASE[index] + reader.bits(DISTANCE_EXTRA[index])? as usize;
+                if distance > out.len() {
+                    return Err(invalid());
+                }
+                let start = out.len() - distance;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+/// Adler-32 checksum of `data`, the trailer of a zlib stream
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the largest number of bytes that can't overflow `b`
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// Decompress a zlib stream (RFC 1950), the format of the compressed
+/// variables of MAT-files, version 7. The Adler-32 trailer is checked.
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, SpikeError> {
+    if data.len() < 2 || data[0] & 0x0f != 8 || data[1] & 0x20 != 0 {
+        return Err(invalid());
+    }
+
+    let mut reader = BitReader {
+        data: &data[2..],
+        pos: 0,
+        buffer: 0,
+        n_bits: 0,
+    };
+    let mut out = vec![];
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let header = reader
+                    .data
+                    .get(reader.pos..reader.pos + 4)
+                    .ok_or_else(invalid)?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let nlen = u16::from_le_bytes([header[2], header[3]]) as usize;
+                if len != !nlen & 0xffff {
+                    return Err(invalid());
+                }
+                reader.pos += 4;
+                let stored = reader
+                    .data
+                    .get(reader.pos..reader.pos + len)
+                    .ok_or_else(invalid)?;
+                out.extend_from_slice(stored);
+                reader.pos += len;
+            }
+            1 => {
+                let (lengths, distances) = fixed_codes();
+                inflate_block(&mut reader, &mut out, &lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut out, &lengths, &distances)?;
+            }
+            _ => return Err(invalid()),
+        }
+        if last {
+            reader.align_to_byte();
+            let trailer = reader
+                .data
+                .get(reader.pos..reader.pos + 4)
+                .ok_or_else(invalid)?;
+            if u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) != adler32(&out)
+            {
+                return Err(invalid());
+            }
+            return Ok(out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// The data of the dynamic Huffman block, skewed so that the compressor
+    /// prefers a code of its own to the fixed one
+    fn dynamic_data() -> Vec<u8> {
+        (0..400)
+            .map(|i| b'a' + ((i * 7919) % 251 % 11) as u8)
+            .collect()
+    }
+
+    // streams written by zlib, with the type of their first block checked by
+    // hand
+
+    #[test]
+    fn stored_block() {
+        let data = hex("7801011300ecff68656c6c6f2c2073746f72656420626c6f636b46ce071d");
+        assert_eq!(zlib_decompress(&data).unwrap(), b"hello, stored block");
+    }
+
+    #[test]
+    fn fixed_huffman_block() {
+        let data = hex("78da4b4c4a4e4442002df505bf");
+        assert_eq!(zlib_decompress(&data).unwrap(), b"abcabcabcabcabc");
+    }
+
+    #[test]
+    fn dynamic_huffman_block() {
+        let data = hex(concat!(
+            "78dac5cf290e00310cc0c0b7a6499bebffbc0aaec1b295cc0659dcca57ed1553",
+            "87f6d19c244d7c7ab8810b388103d8810ff006366005fefaf6fbfa05c2a19f4d",
+        ));
+        assert_eq!(zlib_decompress(&data).unwrap(), dynamic_data());
+    }
+
+    #[test]
+    fn multiple_blocks() {
+        // a fixed block, the empty stored block of a full flush and a
+        // dynamic block
+        let data = hex(concat!(
+            "78da4a4c4a4e444200000000ffffc5cf290e00310cc0c0b7a6499bebffbc0aae",
+            "c1b295cc0659dcca57ed155387f6d19c244d7c7ab8810b388103d8810ff00636",
+            "6005fefaf6fbfa05e9fda50b",
+        ));
+        let mut expected = b"abcabcabcabcabc".to_vec();
+        expected.extend(dynamic_data());
+        assert_eq!(zlib_decompress(&data).unwrap(), expected);
+    }
+
+    #[test]
+    fn checks_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+        assert_eq!(adler32(&[]), 1);
+
+        let mut data = hex("78da4b4c4a4e4442002df505bf");
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(zlib_decompress(&data).is_err());
+        assert!(zlib_decompress(&data[..last]).is_err());
+        // a corrupted stored byte is detected only by the checksum
+        let mut data = hex("7801011300ecff68656c6c6f2c2073746f72656420626c6f636b46ce071d");
+        data[8] = b'j';
+        assert!(zlib_decompress(&data).is_err());
+    }
+}
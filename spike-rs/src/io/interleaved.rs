@@ -0,0 +1,146 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+};
+
+use crate::error::SpikeError;
+
+/// Number of frames decoded at once
+const CHUNK_LEN: usize = 1 << 12;
+
+/// Split frames of `n_channels` interleaved samples, returning the samples
+/// of the channels at `columns`, in their order
+pub fn deinterleave<T: Copy>(frames: &[T], n_channels: usize, columns: &[usize]) -> Vec<Vec<T>> {
+    columns
+        .iter()
+        .map(|column| {
+            frames
+                .chunks_exact(n_channels)
+                .map(|frame| frame[*column])
+                .collect()
+        })
+        .collect()
+}
+
+/// Int16File
+///
+/// A binary file with the samples of `n_channels` channels interleaved frame
+/// by frame as 16 bit little endian integers, from `data_offset` to its end,
+/// like the exports of MC_DataTool and the `continuous.dat` files of Open
+/// Ephys. The file stays open and only the bytes from the first to the last
+/// requested channel of each frame are read.
+///
+/// The samples are read through a buffered reader instead of memory-mapping
+/// the file: the crate has no dependencies and the standard library can't
+/// map files, while seeking past the unrequested channels already keeps the
+/// memory bounded by the requested data.
+#[derive(Debug)]
+pub struct Int16File {
+    file: File,
+    data_offset: u64,
+    n_channels: usize,
+    signed: bool,
+}
+
+impl Int16File {
+    /// Wrap an open file, with signed or unsigned samples
+    pub fn new(file: File, data_offset: u64, n_channels: usize, signed: bool) -> Self {
+        Self {
+            file,
+            data_offset,
+            n_channels,
+            signed,
+        }
+    }
+
+    /// Returns the number of complete frames of the file
+    pub fn n_frames(&self) -> Result<usize, SpikeError> {
+        let data_len = self.file.metadata()?.len().saturating_sub(self.data_offset);
+        Ok((data_len / (2 * self.n_channels as u64)) as usize)
+    }
+
+    /// Read the samples in `start..end` of the channels at `columns`,
+    /// returning the values of each one
+    pub fn read_columns(
+        &self,
+        columns: &[usize],
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<Vec<i32>>, SpikeError> {
+        let mut data = vec![Vec::with_capacity(end - start); columns.len()];
+        let (Some(first), Some(last)) = (columns.iter().min(), columns.iter().max()) else {
+            return Ok(data);
+        };
+        if *last >= self.n_channels {
+            return Err(SpikeError::IndexOutOfRange);
+        }
+        let span_channels = last + 1 - first;
+        let span_columns: Vec<usize> = columns.iter().map(|column| column - first).collect();
+        let frame_len = 2 * self.n_channels;
+        let gap = (2 * (self.n_channels - span_channels)) as i64;
+
+        let mut reader = BufReader::new(&self.file);
+        reader.seek(SeekFrom::Start(
+            self.data_offset + (start * frame_len + 2 * first) as u64,
+        ))?;
+        let mut span = vec![0u8; 2 * span_channels];
+        let mut chunk_start = start;
+        while chunk_start < end {
+            let chunk_end = usize::min(chunk_start + CHUNK_LEN, end);
+            let mut frames = Vec::with_capacity((chunk_end - chunk_start) * span_channels);
+            for sample in chunk_start..chunk_end {
+                reader.read_exact(&mut span)?;
+                frames.extend(span.chunks_exact(2).map(|bytes| {
+                    let bytes = [bytes[0], bytes[1]];
+                    if self.signed {
+                        i16::from_le_bytes(bytes) as i32
+                    } else {
+                        u16::from_le_bytes(bytes) as i32
+                    }
+                }));
+                if sample + 1 < end {
+                    reader.seek_relative(gap)?;
+                }
+            }
+            for (values, chunk) in
+                data.iter_mut()
+                    .zip(deinterleave(&frames, span_channels, &span_columns))
+            {
+                values.extend(chunk);
+            }
+            chunk_start = chunk_end;
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn read_some_columns() {
+        let path = std::env::temp_dir().join("spike_rs_test_interleaved.dat");
+        let mut bytes = b"header".to_vec();
+        for sample in 0..10i16 {
+            for channel in 0..4i16 {
+                bytes.extend((channel * 100 - sample).to_le_bytes());
+            }
+        }
+        fs::write(&path, bytes).unwrap();
+
+        let file = Int16File::new(File::open(&path).unwrap(), 6, 4, true);
+        assert_eq!(file.n_frames().unwrap(), 10);
+        assert_eq!(
+            file.read_columns(&[2, 1], 7, 10).unwrap(),
+            vec![vec![193, 192, 191], vec![93, 92, 91]]
+        );
+        assert_eq!(file.read_columns(&[0], 0, 2).unwrap(), vec![vec![0, -1]]);
+        assert!(matches!(
+            file.read_columns(&[4], 0, 1),
+            Err(SpikeError::IndexOutOfRange)
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+use crate::error::SpikeError;
+
+fn invalid(reason: &str) -> SpikeError {
+    SpikeError::JsonInvalid(reason.to_string())
+}
+
+/// JsonValue
+///
+/// A parsed JSON value. The members of the objects keep the order of the
+/// document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Returns the member `key` of an object, if present
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .text
+            .get(self.pos)
+            .is_some_and(|x| x.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.text.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), SpikeError> {
+        if self.peek() != Some(byte) {
+            return Err(invalid(&format!("expected '{}'", byte as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, SpikeError> {
+        if !self.text[self.pos..].starts_with(literal.as_bytes()) {
+            return Err(invalid("invalid literal"));
+        }
+        self.pos += literal.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<JsonValue, SpikeError> {
+        match self.peek().ok_or_else(|| invalid("unexpected end"))? {
+            b'{' => {
+                self.pos += 1;
+                let mut members = vec![];
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                loop {
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    members.push((key, self.value()?));
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b'}')?;
+                Ok(JsonValue::Object(members))
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut values = vec![];
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b']')?;
+                Ok(JsonValue::Array(values))
+            }
+            b'"' => Ok(JsonValue::String(self.string()?)),
+            b't' => self.literal("true", JsonValue::Bool(true)),
+            b'f' => self.literal("false", JsonValue::Bool(false)),
+            b'n' => self.literal("null", JsonValue::Null),
+            _ => self.number(),
+        }
+    }
+
+    fn number(&mut self) -> Result<JsonValue, SpikeError> {
+        let start = self.pos;
+        while self
+            .text
+            .get(self.pos)
+            .is_some_and(|x| x.is_ascii_digit() || b"+-.eE".contains(x))
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.text[start..self.pos])
+            .ok()
+            .and_then(|x| x.parse::<f64>().ok())
+            .map(JsonValue::Number)
+            .ok_or_else(|| invalid("invalid number"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, SpikeError> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .and_then(|x| std::str::from_utf8(x).ok())
+            .and_then(|x| u32::from_str_radix(x, 16).ok())
+            .ok_or_else(|| invalid("invalid escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+
+    fn string(&mut self) -> Result<String, SpikeError> {
+        self.expect(b'"')?;
+        let mut bytes = vec![];
+        loop {
+            let byte = *self
+                .text
+                .get(self.pos)
+                .ok_or_else(|| invalid("unterminated string"))?;
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = *self
+                        .text
+                        .get(self.pos)
+                        .ok_or_else(|| invalid("unterminated string"))?;
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // surrogate pair
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.text[self.pos..].starts_with(b"\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code =
+                                    0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00));
+                            }
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(invalid("invalid escape")),
+                    };
+                    let mut buffer = [0u8; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                }
+                _ => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| invalid("invalid UTF-8 string"))
+    }
+}
+
+/// Parse a JSON document
+pub fn parse_json(text: &str) -> Result<JsonValue, SpikeError> {
+    let mut parser = Parser {
+        text: text.as_bytes(),
+        pos: 0,
+    };
+    let value = parser.value()?;
+    if parser.peek().is_some() {
+        return Err(invalid("trailing characters"));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_escapes() {
+        assert_eq!(
+            parse_json(r#""a\"b\\c\/d\n\tµ😀""#).unwrap(),
+            JsonValue::String("a\"b\\c/d\n\tµ😀".to_string())
+        );
+        assert_eq!(
+            parse_json(r#""\u00b5V \ud83d\ude00""#).unwrap().as_str(),
+            Some("µV 😀")
+        );
+        assert!(matches!(
+            parse_json(r#""\x""#),
+            Err(SpikeError::JsonInvalid(_))
+        ));
+        assert!(matches!(
+            parse_json(r#""\u12""#),
+            Err(SpikeError::JsonInvalid(_))
+        ));
+    }
+
+    #[test]
+    fn parse_numbers() {
+        for (text, value) in [
+            ("0", 0f64),
+            ("-12", -12f64),
+            ("3.5e-3", 3.5e-3),
+            ("1E2", 100f64),
+        ] {
+            assert_eq!(parse_json(text).unwrap().as_f64(), Some(value));
+        }
+        assert!(matches!(parse_json("1-2"), Err(SpikeError::JsonInvalid(_))));
+    }
+
+    #[test]
+    fn parse_nested() {
+        let value =
+            parse_json(r#" { "b": [1, {"c": null}, []], "a": {"d": true, "e": "x"}, "f": {} } "#)
+                .unwrap();
+        let JsonValue::Object(members) = &value else {
+            panic!("not an object");
+        };
+        let keys: Vec<&str> = members.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, vec!["b", "a", "f"]);
+
+        let array = value.get("b").and_then(|x| x.as_array()).unwrap();
+        assert_eq!(array[0].as_f64(), Some(1f64));
+        assert_eq!(array[1].get("c"), Some(&JsonValue::Null));
+        assert_eq!(array[2], JsonValue::Array(vec![]));
+        assert_eq!(
+            value
+                .get("a")
+                .and_then(|x| x.get("e"))
+                .and_then(|x| x.as_str()),
+            Some("x")
+        );
+        assert_eq!(value.get("f"), Some(&JsonValue::Object(vec![])));
+    }
+
+    #[test]
+    fn reject_malformed() {
+        for text in [
+            "",
+            "{",
+            "[1, 2",
+            "[1,]",
+            r#"{"a" 1}"#,
+            r#"{"a": }"#,
+            r#"{a: 1}"#,
+            r#""unterminated"#,
+            "tru",
+            "nul",
+            "{} x",
+        ] {
+            assert!(
+                matches!(parse_json(text), Err(SpikeError::JsonInvalid(_))),
+                "{text}"
+            );
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use super::npy::write_npy;
+use crate::{
+    error::SpikeError,
+    layout::MeaLayout,
+    types::{check_range, PhaseHandler},
+};
+
+/// Name of the binary file with the samples
+pub const DAT_FILENAME: &str = "recording.dat";
+
+/// Distance in micrometers between the channels when the recording doesn't
+/// match a built-in layout, placing them on a vertical line
+const FALLBACK_PITCH: f64 = 200f64;
+
+/// Number of samples read from the phase at once
+const CHUNK_LEN: usize = 1 << 16;
+
+/// Export the raw data of the phase to the binary layout used by Kilosort
+/// and phy, writing in `folder`, created if missing:
+/// - `recording.dat`: the samples as int16, interleaved by channel;
+/// - `params.py`: the parameters of the recording read by phy;
+/// - `channel_map.npy`: the index of each channel in the `.dat` file;
+/// - `channel_positions.npy`: the (x, y) position in micrometers of each
+///   channel on the detected MEA layout.
+///
+/// Only the `channels` labels are exported, in their order, or all the
+/// labels of the phase if `None`, in the `start..end` samples range.
+/// The samples are converted back to ADC values, relative to the ADC zero,
+/// with the `channel_info` of each channel, so the channels must provide a
+/// valid conversion factor and their samples must fit in an int16, else
+/// `KilosortSampleOutOfRange` names the first channel that doesn't.
+/// Returns the labels of the exported channels.
+pub fn export_kilosort(
+    phase: &impl PhaseHandler,
+    folder: impl AsRef<Path>,
+    channels: Option<&[String]>,
+    start: Option<usize>,
+    end: Option<usize>,
+) -> Result<Vec<String>, SpikeError> {
+    let labels = match channels {
+        Some(channels) => channels.to_vec(),
+        None => phase.labels(),
+    };
+    if labels.is_empty() {
+        return Err(SpikeError::RawDataLabelNotFound);
+    }
+    let (actual_start, actual_end) = check_range(start, end, phase.datalen())?;
+
+    let mut infos = vec![];
+    for label in labels.iter() {
+        let info = phase.channel_info(label)?;
+        let step = info.adc_step();
+        if step == 0f32 || !step.is_finite() {
+            return Err(SpikeError::KilosortNoAdcConversion(label.clone()));
+        }
+        infos.push(info);
+    }
+
+    let folder = folder.as_ref();
+    fs::create_dir_all(folder)?;
+
+    let mut dat = BufWriter::new(File::create(folder.join(DAT_FILENAME))?);
+    let mut start = actual_start;
+    while start < actual_end {
+        let end = usize::min(start + CHUNK_LEN, actual_end);
+        let block = phase.raw_block(&labels, Some(start), Some(end))?;
+        let rows = block
+            .rows()
+            .zip(labels.iter().zip(infos.iter()))
+            .map(|(row, (label, info))| {
+                info.physical_to_adc(row)
+                    .iter()
+                    .map(|x| {
+                        i16::try_from(*x - info.ad_zero)
+                            .map_err(|_| SpikeError::KilosortSampleOutOfRange(label.clone()))
+                    })
+                    .collect::<Result<Vec<i16>, SpikeError>>()
+            })
+            .collect::<Result<Vec<Vec<i16>>, SpikeError>>()?;
+        let mut bytes = Vec::with_capacity(rows.len() * (end - start) * 2);
+        for sample in 0..end - start {
+            for row in rows.iter() {
+                bytes.extend_from_slice(&row[sample].to_le_bytes());
+            }
+        }
+        dat.write_all(&bytes)?;
+        start = end;
+    }
+    dat.flush()?;
+
+    let params = format!(
+        "dat_path = '{DAT_FILENAME}'\n\
+         n_channels_dat = {}\n\
+         dtype = 'int16'\n\
+         offset = 0\n\
+         sample_rate = {:?}\n\
+         hp_filtered = False\n",
+        labels.len(),
+        phase.sampling_frequency() as f64,
+    );
+    fs::write(folder.join("params.py"), params)?;
+
+    let channel_map: Vec<i32> = (0..labels.len() as i32).collect();
+    write_npy(
+        folder.join("channel_map.npy"),
+        &[labels.len()],
+        &channel_map,
+    )?;
+
+    let layout = MeaLayout::detect(&labels);
+    let positions: Vec<f64> = labels
+        .iter()
+        .enumerate()
+        .flat_map(
+            |(i, label)| match layout.as_ref().and_then(|layout| layout.coordinates(label)) {
+                Some((x, y)) => [x as f64, y as f64],
+                None => [0f64, i as f64 * FALLBACK_PITCH],
+            },
+        )
+        .collect();
+    write_npy(
+        folder.join("channel_positions.npy"),
+        &[labels.len(), 2],
+        &positions,
+    )?;
+
+    Ok(labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ChannelInfo, MemoryPhase};
+
+    #[test]
+    fn export_interleaved_samples() {
+        let mut phase = MemoryPhase::new(10000f32, 5);
+        for (label, offset) in [("12", 0f32), ("13", 100f32)] {
+            let data = (0..5).map(|x| (x as f32 + offset) * 1e-7).collect();
+            phase.add_raw_data(label, data).unwrap();
+            phase.set_channel_info(ChannelInfo {
+                label: label.to_string(),
+                ad_zero: 32768,
+                conversion_factor: 1,
+                exponent: -7,
+                ..Default::default()
+            });
+        }
+
+        let folder = std::env::temp_dir().join("spike_rs_test_kilosort");
+        let labels = ["13".to_string(), "12".to_string()];
+        assert_eq!(
+            export_kilosort(&phase, &folder, Some(&labels), Some(1), Some(4)).unwrap(),
+            labels.to_vec()
+        );
+
+        let samples: Vec<i16> = fs::read(folder.join(DAT_FILENAME))
+            .unwrap()
+            .chunks_exact(2)
+            .map(|x| i16::from_le_bytes([x[0], x[1]]))
+            .collect();
+        assert_eq!(samples, vec![101, 1, 102, 2, 103, 3]);
+
+        let params = fs::read_to_string(folder.join("params.py")).unwrap();
+        assert!(params.contains("n_channels_dat = 2\n"));
+        assert!(params.contains("sample_rate = 10000.0\n"));
+
+        let positions = fs::read(folder.join("channel_positions.npy")).unwrap();
+        assert_eq!(&positions[..6], b"\x93NUMPY");
+        let header_len = u16::from_le_bytes([positions[8], positions[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        assert_eq!(positions.len(), 10 + header_len + 4 * 8);
+
+        assert!(matches!(
+            export_kilosort(&MemoryPhase::new(10000f32, 5), &folder, None, None, None),
+            Err(SpikeError::RawDataLabelNotFound)
+        ));
+
+        phase.set_raw_data("13", Some(2), &[1e-2]).unwrap();
+        assert!(matches!(
+            export_kilosort(&phase, &folder, None, None, None),
+            Err(SpikeError::KilosortSampleOutOfRange(label)) if label == "13"
+        ));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,416 @@
+use std::{fs, path::Path};
+
+use super::inflate::zlib_decompress;
+use crate::{error::SpikeError, time::DateTime};
+
+const HEADER_LEN: usize = 128;
+const HEADER_TEXT_LEN: usize = 116;
+
+const MI_INT8: u32 = 1;
+const MI_UINT8: u32 = 2;
+const MI_INT16: u32 = 3;
+const MI_UINT16: u32 = 4;
+const MI_INT32: u32 = 5;
+const MI_UINT32: u32 = 6;
+const MI_SINGLE: u32 = 7;
+const MI_DOUBLE: u32 = 9;
+const MI_INT64: u32 = 12;
+const MI_UINT64: u32 = 13;
+const MI_MATRIX: u32 = 14;
+const MI_COMPRESSED: u32 = 15;
+
+const MX_SPARSE_CLASS: u32 = 5;
+const MX_DOUBLE_CLASS: u32 = 6;
+const MX_UINT64_CLASS: u32 = 15;
+const LOGICAL_FLAG: u32 = 0x0200;
+
+fn invalid(reason: &str) -> SpikeError {
+    SpikeError::MatFileInvalid(reason.to_string())
+}
+
+/// MatArray
+///
+/// The content of a numeric variable of a MAT-file, with the values converted
+/// to f64 whatever their class.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatArray {
+    /// A dense array, with the values in column major order
+    Dense { dims: Vec<usize>, values: Vec<f64> },
+    /// A sparse matrix in compressed column format: the values of the column
+    /// `j` are the ones in `column_starts[j]..column_starts[j + 1]`, at the
+    /// rows with the same index in `row_indices`
+    Sparse {
+        rows: usize,
+        columns: usize,
+        row_indices: Vec<usize>,
+        column_starts: Vec<usize>,
+        values: Vec<f64>,
+    },
+}
+
+/// MatVariable
+///
+/// A named variable of a MAT-file
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatVariable {
+    pub name: String,
+    pub array: MatArray,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn u32(&mut self) -> Result<u32, SpikeError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| invalid("truncated data element"))?;
+        self.pos += 4;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Read the next data element, returning its type and its data, or None
+    /// at the end of the data
+    fn element(&mut self) -> Result<Option<(u32, &'a [u8])>, SpikeError> {
+        if self.pos >= self.data.len() {
+            return Ok(None);
+        }
+        let tag = self.u32()?;
+
+        // small data element format, with type, size and data in 8 bytes
+        if tag >> 16 != 0 {
+            let size = (tag >> 16) as usize;
+            let data = self
+                .data
+                .get(self.pos..self.pos + size.min(4))
+                .ok_or_else(|| invalid("truncated data element"))?;
+            self.pos += 4;
+            return Ok(Some((tag & 0xffff, data)));
+        }
+
+        let size = self.u32()? as usize;
+        let data = self
+            .data
+            .get(self.pos..self.pos + size)
+            .ok_or_else(|| invalid("truncated data element"))?;
+        self.pos += size;
+        // compressed elements are not padded to 8 bytes
+        if tag != MI_COMPRESSED {
+            self.pos = self.pos.div_ceil(8) * 8;
+        }
+        Ok(Some((tag, data)))
+    }
+
+    fn required_element(&mut self) -> Result<(u32, &'a [u8]), SpikeError> {
+        self.element()?
+            .ok_or_else(|| invalid("missing matrix subelement"))
+    }
+
+    fn numbers(&self, element: (u32, &[u8])) -> Result<Vec<f64>, SpikeError> {
+        let (data_type, data) = element;
+        macro_rules! convert {
+            ($type:ty) => {
+                data.chunks_exact(std::mem::size_of::<$type>())
+                    .map(|x| {
+                        let bytes = x.try_into().unwrap();
+                        if self.big_endian {
+                            <$type>::from_be_bytes(bytes) as f64
+                        } else {
+                            <$type>::from_le_bytes(bytes) as f64
+                        }
+                    })
+                    .collect()
+            };
+        }
+        Ok(match data_type {
+            MI_INT8 => convert!(i8),
+            MI_UINT8 => convert!(u8),
+            MI_INT16 => convert!(i16),
+            MI_UINT16 => convert!(u16),
+            MI_INT32 => convert!(i32),
+            MI_UINT32 => convert!(u32),
+            MI_SINGLE => convert!(f32),
+            MI_DOUBLE => convert!(f64),
+            MI_INT64 => convert!(i64),
+            MI_UINT64 => convert!(u64),
+            _ => return Err(invalid("unsupported numeric data type")),
+        })
+    }
+
+    fn indices(&self, element: (u32, &[u8])) -> Result<Vec<usize>, SpikeError> {
+        Ok(self.numbers(element)?.iter().map(|x| *x as usize).collect())
+    }
+}
+
+/// Parse the body of a miMATRIX element, returning None for the classes
+/// that are not numeric, like cells, structs and chars
+fn parse_matrix(data: &[u8], big_endian: bool) -> Result<Option<MatVariable>, SpikeError> {
+    let mut reader = Reader {
+        data,
+        pos: 0,
+        big_endian,
+    };
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    let flags = reader.required_element()?;
+    let flags = reader.indices(flags)?;
+    let flags = *flags
+        .first()
+        .ok_or_else(|| invalid("missing array flags"))? as u32;
+    let class = flags & 0xff;
+    let dims = reader.required_element()?;
+    let dims = reader.indices(dims)?;
+    let (_, name) = reader.required_element()?;
+    let name = String::from_utf8_lossy(name).to_string();
+
+    let array = match class {
+        MX_SPARSE_CLASS => {
+            if dims.len() != 2 {
+                return Err(invalid("sparse matrix with more than 2 dimensions"));
+            }
+            let element = reader.required_element()?;
+            let mut row_indices = reader.indices(element)?;
+            let element = reader.required_element()?;
+            let column_starts = reader.indices(element)?;
+            let nnz = *column_starts.last().unwrap_or(&0);
+            let mut values = if flags & LOGICAL_FLAG != 0 {
+                vec![1f64; nnz]
+            } else {
+                let element = reader.required_element()?;
+                reader.numbers(element)?
+            };
+            if row_indices.len() < nnz || values.len() < nnz || column_starts.len() != dims[1] + 1 {
+                return Err(invalid("inconsistent sparse matrix"));
+            }
+            row_indices.truncate(nnz);
+            values.truncate(nnz);
+            MatArray::Sparse {
+                rows: dims[0],
+                columns: dims[1],
+                row_indices,
+                column_starts,
+                values,
+            }
+        }
+        MX_DOUBLE_CLASS..=MX_UINT64_CLASS => {
+            let element = reader.required_element()?;
+            let values = reader.numbers(element)?;
+            if values.len() != dims.iter().product::<usize>() {
+                return Err(invalid("inconsistent array dimensions"));
+            }
+            MatArray::Dense { dims, values }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(MatVariable { name, array }))
+}
+
+fn parse_elements(
+    data: &[u8],
+    big_endian: bool,
+    variables: &mut Vec<MatVariable>,
+) -> Result<(), SpikeError> {
+    let mut reader = Reader {
+        data,
+        pos: 0,
+        big_endian,
+    };
+    while let Some((data_type, data)) = reader.element()? {
+        match data_type {
+            MI_MATRIX => {
+                if let Some(variable) = parse_matrix(data, big_endian)? {
+                    variables.push(variable);
+                }
+            }
+            MI_COMPRESSED => parse_elements(&zlib_decompress(data)?, big_endian, variables)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Parse the content of a MAT-file, version 5 to 7.
+/// Only the numeric variables, dense or sparse, are returned, the others are
+/// skipped.
+pub fn parse_mat(bytes: &[u8]) -> Result<Vec<MatVariable>, SpikeError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(invalid("file shorter than the header"));
+    }
+    let big_endian = match &bytes[126..128] {
+        b"IM" => false,
+        b"MI" => true,
+        _ => return Err(invalid("not a MAT-file, version 5 to 7")),
+    };
+    let mut variables = vec![];
+    parse_elements(&bytes[HEADER_LEN..], big_endian, &mut variables)?;
+    Ok(variables)
+}
+
+/// Read the numeric variables of a MAT-file, version 5 to 7
+pub fn read_mat(path: impl AsRef<Path>) -> Result<Vec<MatVariable>, SpikeError> {
+    parse_mat(&fs::read(path)?)
+}
+
+fn push_element(out: &mut Vec<u8>, data_type: u32, data: &[u8]) {
+    out.extend(data_type.to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend(data);
+    out.resize(out.len().div_ceil(8) * 8, 0);
+}
+
+fn i32_bytes(values: &[usize]) -> Result<Vec<u8>, SpikeError> {
+    let mut bytes = Vec::with_capacity(values.len() * 4);
+    for value in values.iter() {
+        let value = i32::try_from(*value).map_err(|_| invalid("index too large"))?;
+        bytes.extend(value.to_le_bytes());
+    }
+    Ok(bytes)
+}
+
+fn f64_bytes(values: &[f64]) -> Vec<u8> {
+    values.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn matrix_element(out: &mut Vec<u8>, variable: &MatVariable) -> Result<(), SpikeError> {
+    let mut body = vec![];
+    match &variable.array {
+        MatArray::Dense { dims, values } => {
+            if values.len() != dims.iter().product::<usize>() {
+                return Err(invalid("inconsistent array dimensions"));
+            }
+            let flags: Vec<u8> = [MX_DOUBLE_CLASS, 0]
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect();
+            push_element(&mut body, MI_UINT32, &flags);
+            push_element(&mut body, MI_INT32, &i32_bytes(dims)?);
+            push_element(&mut body, MI_INT8, variable.name.as_bytes());
+            push_element(&mut body, MI_DOUBLE, &f64_bytes(values));
+        }
+        MatArray::Sparse {
+            rows,
+            columns,
+            row_indices,
+            column_starts,
+            values,
+        } => {
+            if column_starts.len() != columns + 1
+                || row_indices.len() != values.len()
+                || column_starts.last() != Some(&values.len())
+            {
+                return Err(invalid("inconsistent sparse matrix"));
+            }
+            let flags: Vec<u8> = [MX_SPARSE_CLASS, values.len() as u32]
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect();
+            push_element(&mut body, MI_UINT32, &flags);
+            push_element(&mut body, MI_INT32, &i32_bytes(&[*rows, *columns])?);
+            push_element(&mut body, MI_INT8, variable.name.as_bytes());
+            push_element(&mut body, MI_INT32, &i32_bytes(row_indices)?);
+            push_element(&mut body, MI_INT32, &i32_bytes(column_starts)?);
+            push_element(&mut body, MI_DOUBLE, &f64_bytes(values));
+        }
+    }
+    push_element(out, MI_MATRIX, &body);
+    Ok(())
+}
+
+/// Write the variables to a MAT-file, version 5, uncompressed and little
+/// endian, readable by MATLAB and by `read_mat`
+pub fn write_mat(path: impl AsRef<Path>, variables: &[MatVariable]) -> Result<(), SpikeError> {
+    let mut out = format!(
+        "MATLAB 5.0 MAT-file, Platform: spike-rs, Created on: {}",
+        DateTime::now()
+    )
+    .into_bytes();
+    out.resize(HEADER_TEXT_LEN, b' ');
+    // subsystem data offset, version and endian indicator
+    out.extend([0u8; 8]);
+    out.extend(0x0100u16.to_le_bytes());
+    out.extend(b"IM");
+
+    for variable in variables.iter() {
+        matrix_element(&mut out, variable)?;
+    }
+    fs::write(path, out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compressed data element of the test, written with zlib
+    const COMPRESSED_PEAK_TRAIN: [u8; 73] = [
+        0x0f, 0x00, 0x00, 0x00, 0x41, 0x00, 0x00, 0x00, 0x78, 0xda, 0xe3, 0x63, 0x60, 0x60, 0x28,
+        0x00, 0x62, 0x36, 0x20, 0xe6, 0x00, 0x62, 0x56, 0x20, 0x66, 0x82, 0xd2, 0x20, 0x3e, 0x17,
+        0x10, 0x33, 0x42, 0x31, 0x88, 0x5d, 0x90, 0x9a, 0x98, 0x1d, 0x5f, 0x52, 0x94, 0x98, 0x99,
+        0xc7, 0x00, 0x06, 0x30, 0x75, 0x30, 0x3d, 0x30, 0x3e, 0x03, 0x54, 0x8c, 0x13, 0x88, 0x05,
+        0x18, 0x60, 0xe0, 0x87, 0x3d, 0x94, 0x71, 0x00, 0x00, 0x79, 0x15, 0x07, 0x08,
+    ];
+
+    #[test]
+    fn write_and_read_back() {
+        let variables = vec![
+            MatVariable {
+                name: "peak_train".to_string(),
+                array: MatArray::Sparse {
+                    rows: 100,
+                    columns: 1,
+                    row_indices: vec![3, 50, 99],
+                    column_starts: vec![0, 3],
+                    values: vec![1.5, -2., 0.25],
+                },
+            },
+            MatVariable {
+                name: "artifact".to_string(),
+                array: MatArray::Dense {
+                    dims: vec![1, 2],
+                    values: vec![10., 20.],
+                },
+            },
+        ];
+        let path = std::env::temp_dir().join("spike_rs_test_mat.mat");
+        write_mat(&path, &variables).unwrap();
+        assert_eq!(read_mat(&path).unwrap(), variables);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_compressed() {
+        // sparse 10x1 `peak_train` with 1.5 at row 2 and -2 at row 5, saved
+        // in a miCOMPRESSED element as MATLAB does by default
+        let mut bytes = vec![b' '; HEADER_TEXT_LEN];
+        bytes.extend([0u8; 8]);
+        bytes.extend([0x00, 0x01]);
+        bytes.extend(b"IM");
+        bytes.extend(COMPRESSED_PEAK_TRAIN);
+
+        let variables = parse_mat(&bytes).unwrap();
+        assert_eq!(
+            variables,
+            vec![MatVariable {
+                name: "peak_train".to_string(),
+                array: MatArray::Sparse {
+                    rows: 10,
+                    columns: 1,
+                    row_indices: vec![2, 5],
+                    column_starts: vec![0, 2],
+                    values: vec![1.5, -2.],
+                },
+            }]
+        );
+    }
+}
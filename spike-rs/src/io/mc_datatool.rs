@@ -0,0 +1,560 @@
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::Path,
+};
+
+use super::{interleaved::Int16File, parse_volts};
+use crate::{
+    error::SpikeError,
+    time::DateTime,
+    types::{
+        check_digital_range, check_range, ChannelInfo, EventEntity, MemoryPhase, PhaseHandler,
+        RawBlock,
+    },
+};
+
+/// Group of the streams of the electrodes, e.g. `El_12`
+pub const ELECTRODE_GROUP: &str = "El";
+
+/// Last line of the header of the binary exports
+const END_OF_HEADER: &str = "EOH";
+
+fn invalid(reason: &str) -> SpikeError {
+    SpikeError::McDataToolInvalidHeader(reason.to_string())
+}
+
+/// Returns the name of the analog stream of a group of streams, the
+/// electrodes one being the raw data stream of the phase
+fn stream_name(group: &str) -> String {
+    if group == ELECTRODE_GROUP {
+        MemoryPhase::DEFAULT_STREAM.to_string()
+    } else {
+        group.to_string()
+    }
+}
+
+fn is_digital(group: &str) -> bool {
+    group.starts_with("Dig")
+}
+
+/// Decode a header line as Latin-1, the encoding of the `µ` of the units
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| *x as char).collect()
+}
+
+/// Stream of an MC_DataTool export, a column of the samples
+#[derive(Debug, Clone)]
+struct Stream {
+    group: String,
+    label: String,
+    /// volts for each step of the ADC, 1 for the digital streams
+    scale: f32,
+}
+
+impl Stream {
+    fn new(name: &str) -> Self {
+        let (group, label) = match name.split_once('_') {
+            Some((group, label)) => (group.to_string(), label.to_string()),
+            None => (name.to_string(), name.to_string()),
+        };
+        Self {
+            group,
+            label,
+            scale: 1f32,
+        }
+    }
+}
+
+/// McDataToolPhase
+///
+/// A phase read from a binary export of MC_DataTool, the `.raw` files: a
+/// Latin-1 text header with the sample rate, the ADC zero, the scaling of
+/// each group of streams and the list of streams, terminated by an `EOH`
+/// line, followed by the samples of all the streams, interleaved, as 16 bit
+/// integers. The samples are unsigned if the ADC zero is not 0.
+///
+/// The streams are grouped by the prefix of their name: `El_12` is the
+/// channel `12` of the raw data stream, `An_1` the channel `1` of the `An`
+/// analog stream and the ones starting with `Dig` are digital channels.
+/// Raw and analog data are converted to volts.
+///
+/// Only the header is parsed when opening. The file stays open and each
+/// request reads just the streams it asks for, with an `Int16File`; the
+/// samples are not memory-mapped, see its documentation. Peak trains live in
+/// memory, since the export is never written.
+pub struct McDataToolPhase {
+    samples: Int16File,
+    sampling_frequency: f32,
+    datalen: usize,
+    adc_zero: i32,
+    streams: Vec<Stream>,
+    raw_data_stream: String,
+    peaks: MemoryPhase,
+}
+
+impl McDataToolPhase {
+    /// Open an MC_DataTool binary export, parsing its header
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SpikeError> {
+        let mut file = File::open(path)?;
+
+        // the header is small, read it up to its end without the samples
+        let mut header = vec![];
+        let mut data_offset = None;
+        let mut buffer = [0u8; 4096];
+        while data_offset.is_none() {
+            let n = file.read(&mut buffer)?;
+            if n == 0 {
+                return Err(invalid("missing end of header"));
+            }
+            header.extend_from_slice(&buffer[..n]);
+            data_offset = find_end_of_header(&header);
+        }
+        let data_offset = data_offset.unwrap_or_default();
+
+        let mut sampling_frequency = None;
+        let mut adc_zero = None;
+        let mut scales = vec![];
+        let mut streams = vec![];
+        for line in latin1(&header[..data_offset]).lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "Sample rate" => sampling_frequency = value.parse::<f32>().ok(),
+                "ADC zero" => adc_zero = value.parse::<i32>().ok(),
+                "Streams" => {
+                    streams = value
+                        .split(';')
+                        .map(|x| x.trim())
+                        .filter(|x| !x.is_empty())
+                        .map(Stream::new)
+                        .collect()
+                }
+                _ => {
+                    if let Some(scale) = parse_volts(value) {
+                        scales.push((key.to_string(), scale));
+                    }
+                }
+            }
+        }
+
+        let sampling_frequency =
+            sampling_frequency.ok_or_else(|| invalid("missing sample rate"))?;
+        let adc_zero = adc_zero.ok_or_else(|| invalid("missing ADC zero"))?;
+        if streams.is_empty() {
+            return Err(invalid("missing streams"));
+        }
+        for stream in streams.iter_mut().filter(|x| !is_digital(&x.group)) {
+            stream.scale = scales
+                .iter()
+                .find(|(group, _)| *group == stream.group)
+                .map(|(_, scale)| *scale)
+                .ok_or_else(|| invalid(&format!("missing scaling of {}", stream.group)))?;
+        }
+
+        let samples = Int16File::new(file, data_offset as u64, streams.len(), adc_zero == 0);
+        let datalen = samples.n_frames()?;
+        let raw_data_stream = if streams.iter().any(|x| x.group == ELECTRODE_GROUP) {
+            stream_name(ELECTRODE_GROUP)
+        } else {
+            streams
+                .iter()
+                .find(|x| !is_digital(&x.group))
+                .map(|x| stream_name(&x.group))
+                .unwrap_or_default()
+        };
+
+        Ok(Self {
+            samples,
+            sampling_frequency,
+            datalen,
+            adc_zero,
+            streams,
+            raw_data_stream,
+            peaks: MemoryPhase::new(sampling_frequency, datalen),
+        })
+    }
+
+    /// Returns the ADC zero of the samples
+    pub fn adc_zero(&self) -> i32 {
+        self.adc_zero
+    }
+
+    fn stream_index(&self, stream: &str, channel: &str) -> Option<usize> {
+        self.streams.iter().position(|x| {
+            !is_digital(&x.group) && stream_name(&x.group) == stream && x.label == channel
+        })
+    }
+
+    fn digital_indices(&self) -> Vec<usize> {
+        (0..self.streams.len())
+            .filter(|i| is_digital(&self.streams[*i].group))
+            .collect()
+    }
+
+    /// Read the samples in `start..end` of the streams at `columns`,
+    /// returning the values of each one
+    fn read_columns(
+        &self,
+        columns: &[usize],
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<Vec<f32>>, SpikeError> {
+        Ok(self
+            .samples
+            .read_columns(columns, start, end)?
+            .into_iter()
+            .zip(columns.iter())
+            .map(|(values, column)| {
+                let stream = &self.streams[*column];
+                if is_digital(&stream.group) {
+                    values.iter().map(|x| *x as f32).collect()
+                } else {
+                    values
+                        .iter()
+                        .map(|x| (*x - self.adc_zero) as f32 * stream.scale)
+                        .collect()
+                }
+            })
+            .collect())
+    }
+}
+
+/// Returns the offset of the samples, after the `EOH` line, if present
+fn find_end_of_header(header: &[u8]) -> Option<usize> {
+    let mut line_start = 0;
+    for (i, byte) in header.iter().enumerate() {
+        if *byte == b'\n' {
+            let line = &header[line_start..i];
+            if line.strip_suffix(b"\r").unwrap_or(line) == END_OF_HEADER.as_bytes() {
+                return Some(i + 1);
+            }
+            line_start = i + 1;
+        }
+    }
+    None
+}
+
+impl PhaseHandler for McDataToolPhase {
+    fn sampling_frequency(&self) -> f32 {
+        self.sampling_frequency
+    }
+
+    fn datalen(&self) -> usize {
+        self.datalen
+    }
+
+    fn labels(&self) -> Vec<String> {
+        self.analog_labels(&self.raw_data_stream)
+            .unwrap_or_default()
+    }
+
+    fn channel_info(&self, channel: &str) -> Result<ChannelInfo, SpikeError> {
+        match self.stream_index(&self.raw_data_stream, channel) {
+            Some(index) => Ok(ChannelInfo {
+                label: channel.to_string(),
+                row_index: index as i32,
+                raw_data_type: "Int".to_string(),
+                unit: "V".to_string(),
+                ad_zero: self.adc_zero,
+                tick: (1e6 / self.sampling_frequency as f64).round() as i64,
+                adc_bits: 16,
+                ..Default::default()
+            }),
+            None => Err(SpikeError::RawDataLabelNotFound),
+        }
+    }
+
+    fn recording_start(&self) -> Option<DateTime> {
+        None
+    }
+
+    fn raw_data(
+        &self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        self.analog_data(&self.raw_data_stream, channel, start, end)
+    }
+
+    fn set_raw_data(
+        &mut self,
+        _channel: &str,
+        _start: Option<usize>,
+        _data: &[f32],
+    ) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn raw_block(
+        &self,
+        channels: &[String],
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<RawBlock, SpikeError> {
+        let (actual_start, actual_end) = check_range(start, end, self.datalen)?;
+        let columns = channels
+            .iter()
+            .map(|channel| {
+                self.stream_index(&self.raw_data_stream, channel)
+                    .ok_or(SpikeError::RawDataLabelNotFound)
+            })
+            .collect::<Result<Vec<usize>, SpikeError>>()?;
+        let data = self
+            .read_columns(&columns, actual_start, actual_end)?
+            .concat();
+        RawBlock::new(
+            channels.to_vec(),
+            actual_start,
+            actual_end - actual_start,
+            data,
+        )
+    }
+
+    fn analog_streams(&self) -> Vec<String> {
+        let mut streams: Vec<String> = vec![];
+        for stream in self.streams.iter().filter(|x| !is_digital(&x.group)) {
+            let name = stream_name(&stream.group);
+            if !streams.contains(&name) {
+                streams.push(name);
+            }
+        }
+        streams
+    }
+
+    fn raw_data_stream(&self) -> String {
+        self.raw_data_stream.clone()
+    }
+
+    fn set_raw_data_stream(&mut self, stream: &str) -> Result<(), SpikeError> {
+        if !self.analog_streams().iter().any(|x| x == stream) {
+            return Err(SpikeError::AnalogStreamNotFound);
+        }
+        self.raw_data_stream = stream.to_string();
+        Ok(())
+    }
+
+    fn analog_labels(&self, stream: &str) -> Result<Vec<String>, SpikeError> {
+        let labels: Vec<String> = self
+            .streams
+            .iter()
+            .filter(|x| !is_digital(&x.group) && stream_name(&x.group) == stream)
+            .map(|x| x.label.clone())
+            .collect();
+        if labels.is_empty() {
+            return Err(SpikeError::AnalogStreamNotFound);
+        }
+        Ok(labels)
+    }
+
+    fn analog_data(
+        &self,
+        stream: &str,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        if !self.analog_streams().iter().any(|x| x == stream) {
+            return Err(SpikeError::AnalogStreamNotFound);
+        }
+        let (actual_start, actual_end) = check_range(start, end, self.datalen)?;
+        let column = self
+            .stream_index(stream, channel)
+            .ok_or(SpikeError::RawDataLabelNotFound)?;
+        Ok(self
+            .read_columns(&[column], actual_start, actual_end)?
+            .remove(0))
+    }
+
+    fn n_digitals(&self) -> usize {
+        self.digital_indices().len()
+    }
+
+    fn digital_labels(&self) -> Vec<String> {
+        self.digital_indices()
+            .iter()
+            .map(|i| self.streams[*i].label.clone())
+            .collect()
+    }
+
+    fn digital(
+        &self,
+        index: usize,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        let digitals = self.digital_indices();
+        if digitals.is_empty() {
+            return Err(SpikeError::DigitalNoDigitalPresent);
+        }
+
+        if index >= digitals.len() {
+            return Err(SpikeError::IndexOutOfRange);
+        }
+
+        let (actual_start, actual_end) = check_digital_range(start, end, self.datalen)?;
+        Ok(self
+            .read_columns(&[digitals[index]], actual_start, actual_end)?
+            .remove(0))
+    }
+
+    fn set_digital(
+        &mut self,
+        _index: usize,
+        _start: Option<usize>,
+        _data: &[f32],
+    ) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn n_events(&self) -> usize {
+        0
+    }
+
+    fn events(&self, _index: usize) -> Result<EventEntity, SpikeError> {
+        Err(SpikeError::IndexOutOfRange)
+    }
+
+    crate::delegate_peak_trains!(peaks);
+}
+
+/// Read an ASCII export of MC_DataTool in memory: a text header ending with
+/// the line of the column names, the time in milliseconds followed by the
+/// streams with their unit, like `t[ms]`, `El_12[µV]`, separated by tabs,
+/// then a line of tab separated values for each sample.
+/// The streams are grouped as in `McDataToolPhase`, raw and analog data are
+/// converted to volts.
+pub fn read_ascii(path: impl AsRef<Path>) -> Result<MemoryPhase, SpikeError> {
+    let text = latin1(&fs::read(path)?);
+    let mut lines = text.lines();
+    let columns = loop {
+        match lines.next() {
+            Some(line) if line.starts_with("t[") => break line,
+            Some(_) => continue,
+            None => return Err(invalid("missing column names")),
+        }
+    };
+
+    // stream and scale of each column after the time
+    let mut streams = vec![];
+    for column in columns.split('\t').skip(1) {
+        let (name, unit) = match column.split_once('[') {
+            Some((name, unit)) => (name.trim(), unit.trim_end_matches(']')),
+            None => (column.trim(), ""),
+        };
+        let mut stream = Stream::new(name);
+        if !is_digital(&stream.group) {
+            stream.scale =
+                parse_volts(unit).ok_or_else(|| invalid(&format!("unknown unit of {name}")))?;
+        }
+        streams.push(stream);
+    }
+
+    let mut times = vec![];
+    let mut data = vec![vec![]; streams.len()];
+    for line in lines.filter(|x| !x.trim().is_empty()) {
+        let mut values = line.split('\t').map(|x| x.trim().parse::<f64>());
+        let time = values
+            .next()
+            .and_then(|x| x.ok())
+            .ok_or_else(|| invalid("invalid time"))?;
+        times.push(time);
+        for (i, (stream, values)) in streams.iter().zip(values).enumerate() {
+            let value = values.map_err(|_| invalid("invalid value"))?;
+            data[i].push(value as f32 * stream.scale);
+        }
+    }
+    if times.len() < 2 {
+        return Err(invalid("too few samples"));
+    }
+    if data.iter().any(|x| x.len() != times.len()) {
+        return Err(invalid("missing values"));
+    }
+
+    let sampling_frequency = (1e3 / (times[1] - times[0])) as f32;
+    let mut phase = MemoryPhase::new(sampling_frequency, times.len());
+    for (stream, data) in streams.iter().zip(data) {
+        if is_digital(&stream.group) {
+            phase.add_digital(&stream.label, data)?;
+        } else {
+            phase.add_analog_data(&stream_name(&stream.group), &stream.label, data)?;
+        }
+    }
+    Ok(phase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_binary_export() {
+        let path = std::env::temp_dir().join("spike_rs_test_mc_datatool.raw");
+        let mut bytes = b"MC_DataTool binary conversion\r\nVersion 2.6.15\r\n".to_vec();
+        bytes.extend(b"MC_REC file = \"C:\\data\\test.mcd\"\r\nSample rate = 10000\r\n");
+        bytes.extend(b"ADC zero = 32768\r\nEl = 0.5\xb5V/AD\r\n");
+        bytes.extend(b"Streams = El_12;El_13;Dig_D1\r\nEOH\r\n");
+        for sample in 0..100u16 {
+            for value in [32768 + sample, 32768 - sample, sample % 2] {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        fs::write(&path, bytes).unwrap();
+
+        let phase = McDataToolPhase::open(&path).unwrap();
+        assert_eq!(phase.sampling_frequency(), 10000f32);
+        assert_eq!(phase.datalen(), 100);
+        assert_eq!(phase.labels(), vec!["12".to_string(), "13".to_string()]);
+        assert_eq!(
+            phase.raw_data("12", Some(10), Some(12)).unwrap(),
+            vec![5e-6, 5.5e-6]
+        );
+        assert_eq!(phase.raw_data("13", Some(2), Some(3)).unwrap(), vec![-1e-6]);
+        assert_eq!(phase.digital_labels(), vec!["D1".to_string()]);
+        assert_eq!(
+            phase.digital(0, Some(0), Some(4)).unwrap(),
+            vec![0., 1., 0., 1.]
+        );
+        assert_eq!(phase.digital(0, Some(99), None).unwrap().len(), 1);
+        assert!(matches!(
+            phase.digital(0, Some(4), Some(4)),
+            Err(SpikeError::DigitalStartIsAfterEnd)
+        ));
+        assert!(matches!(
+            phase.digital(0, None, Some(101)),
+            Err(SpikeError::DigitalOutOfBounds)
+        ));
+
+        let block = phase
+            .raw_block(&["13".to_string(), "12".to_string()], Some(98), None)
+            .unwrap();
+        assert_eq!(block.channel("12").unwrap(), &[49e-6, 49.5e-6]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_ascii_export() {
+        let path = std::env::temp_dir().join("spike_rs_test_mc_datatool.txt");
+        let mut bytes = b"MC_DataTool ASCII conversion\r\n\r\n".to_vec();
+        bytes.extend(b"t[ms]\tEl_12[\xb5V]\tAn_1[mV]\tDig_D1\r\n");
+        bytes.extend(b"0.0\t1.5\t2\t0\r\n0.1\t-3\t4\t1\r\n0.2\t0\t0\t1\r\n");
+        fs::write(&path, bytes).unwrap();
+
+        let phase = read_ascii(&path).unwrap();
+        assert_eq!(phase.sampling_frequency().round(), 10000f32);
+        assert_eq!(phase.datalen(), 3);
+        assert_eq!(
+            phase.raw_data("12", None, None).unwrap(),
+            vec![1.5e-6, -3e-6, 0.]
+        );
+        assert_eq!(
+            phase.analog_data("An", "1", None, None).unwrap(),
+            vec![2e-3, 4e-3, 0.]
+        );
+        assert_eq!(phase.digital(0, Some(0), Some(2)).unwrap(), vec![0., 1.]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,34 @@
+mod inflate;
+pub mod interleaved;
+pub mod json;
+pub mod kilosort;
+pub mod mat;
+pub mod mc_datatool;
+pub mod npy;
+pub mod open_ephys;
+pub mod spycode;
+
+/// Parse a value with a voltage unit, like `0.1984µV/AD` or `µV`, returning
+/// it in volts
+pub(crate) fn parse_volts(text: &str) -> Option<f32> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+' || c == 'e'))
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(split);
+    let value = if value.is_empty() {
+        1f32
+    } else {
+        value.parse::<f32>().ok()?
+    };
+    let unit = unit.trim().trim_end_matches("/AD");
+    let factor = match unit {
+        "V" => 1f32,
+        "mV" => 1e-3,
+        "µV" | "uV" => 1e-6,
+        "nV" => 1e-9,
+        "pV" => 1e-12,
+        _ => return None,
+    };
+    Some(value * factor)
+}
@@ -0,0 +1,241 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::error::SpikeError;
+
+/// Element of an array that can be written to a NPY file
+pub trait NpyElement: Copy {
+    /// NumPy type description, e.g. `<i4`
+    const DESCR: &'static str;
+
+    fn extend_le_bytes(&self, out: &mut Vec<u8>);
+}
+
+macro_rules! npy_element {
+    ($type:ty, $descr:expr) => {
+        impl NpyElement for $type {
+            const DESCR: &'static str = $descr;
+
+            fn extend_le_bytes(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+        }
+    };
+}
+
+npy_element!(i16, "<i2");
+npy_element!(i32, "<i4");
+npy_element!(i64, "<i8");
+npy_element!(f32, "<f4");
+npy_element!(f64, "<f8");
+
+/// Write an array to a NPY file, version 1.0, with the provided `shape` and
+/// the elements in `data` in row major order
+pub fn write_npy<T: NpyElement>(
+    path: impl AsRef<Path>,
+    shape: &[usize],
+    data: &[T],
+) -> Result<(), SpikeError> {
+    if shape.iter().product::<usize>() != data.len() {
+        return Err(SpikeError::NpyWrongDataLen(
+            data.len(),
+            shape.iter().product(),
+        ));
+    }
+
+    let shape = match shape {
+        [len] => format!("({len},)"),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {shape}, }}",
+        T::DESCR
+    );
+    // the data must be aligned to 64 bytes, after the magic string, the
+    // version, the header length and the header terminated by a newline
+    let padding = 63 - (10 + header.len()) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    let mut bytes = Vec::with_capacity(10 + header.len() + data.len() * 8);
+    bytes.extend_from_slice(b"\x93NUMPY\x01\x00");
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for value in data.iter() {
+        value.extend_le_bytes(&mut bytes);
+    }
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+fn invalid(reason: &str) -> SpikeError {
+    SpikeError::NpyInvalid(reason.to_string())
+}
+
+/// Header of a NPY file
+struct NpyHeader {
+    big_endian: bool,
+    kind: u8,
+    size: usize,
+    shape: Vec<usize>,
+    data_offset: u64,
+}
+
+impl NpyHeader {
+    fn read(file: &mut File) -> Result<Self, SpikeError> {
+        let mut preamble = [0u8; 8];
+        file.read_exact(&mut preamble)?;
+        if &preamble[..6] != b"\x93NUMPY" {
+            return Err(invalid("not a NPY file"));
+        }
+        let header_len = if preamble[6] == 1 {
+            let mut len = [0u8; 2];
+            file.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        } else {
+            let mut len = [0u8; 4];
+            file.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        };
+        let data_offset = file.stream_position()? + header_len as u64;
+        let mut header = vec![0u8; header_len];
+        file.read_exact(&mut header)?;
+        let header = String::from_utf8_lossy(&header).to_string();
+
+        let value_of = |key: &str| -> Option<String> {
+            let start = header.find(&format!("'{key}'"))? + key.len() + 2;
+            let rest = header[start..].trim_start().strip_prefix(':')?.trim_start();
+            let end = match rest.chars().next()? {
+                '\'' => rest[1..].find('\'')? + 2,
+                '(' => rest.find(')')? + 1,
+                _ => rest.find(',')?,
+            };
+            Some(rest[..end].to_string())
+        };
+
+        if value_of("fortran_order").as_deref() == Some("True") {
+            return Err(invalid("fortran order not supported"));
+        }
+        let descr = value_of("descr").ok_or_else(|| invalid("missing descr"))?;
+        let descr = descr.trim_matches('\'').as_bytes();
+        if descr.len() != 3 {
+            return Err(invalid("unsupported descr"));
+        }
+        let size = (descr[2] as char)
+            .to_digit(10)
+            .filter(|x| [1, 2, 4, 8].contains(x))
+            .ok_or_else(|| invalid("unsupported descr"))? as usize;
+        let kind = descr[1];
+        if !matches!((kind, size), (b'i' | b'u' | b'b', _) | (b'f', 4 | 8)) {
+            return Err(invalid("unsupported descr"));
+        }
+
+        let shape = value_of("shape").ok_or_else(|| invalid("missing shape"))?;
+        let shape = shape
+            .trim_matches(|c| c == '(' || c == ')')
+            .split(',')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .map(|x| x.parse::<usize>().map_err(|_| invalid("invalid shape")))
+            .collect::<Result<Vec<usize>, SpikeError>>()?;
+
+        Ok(Self {
+            big_endian: descr[0] == b'>',
+            kind,
+            size,
+            shape,
+            data_offset,
+        })
+    }
+
+    fn value(&self, bytes: &[u8]) -> f64 {
+        let mut buffer = [0u8; 8];
+        if self.big_endian {
+            buffer[..self.size].copy_from_slice(bytes);
+            buffer[..self.size].reverse();
+        } else {
+            buffer[..self.size].copy_from_slice(bytes);
+        }
+        let unsigned = u64::from_le_bytes(buffer);
+        match (self.kind, self.size) {
+            (b'f', 4) => f32::from_bits(unsigned as u32) as f64,
+            (b'f', _) => f64::from_bits(unsigned),
+            (b'i', size) => {
+                // sign extension of the value
+                let shift = 64 - 8 * size as u32;
+                ((unsigned << shift) as i64 >> shift) as f64
+            }
+            _ => unsigned as f64,
+        }
+    }
+}
+
+/// Read `len` elements of a NPY file starting from the element `start`, in
+/// row major order, converted to f64 whatever their type.
+/// Returns the shape of the whole array and the elements.
+pub fn read_npy_slice(
+    path: impl AsRef<Path>,
+    start: usize,
+    len: Option<usize>,
+) -> Result<(Vec<usize>, Vec<f64>), SpikeError> {
+    let mut file = File::open(path)?;
+    let header = NpyHeader::read(&mut file)?;
+    let n_elements: usize = header.shape.iter().product();
+    let end = match len {
+        Some(len) => usize::min(start + len, n_elements),
+        None => n_elements,
+    };
+    if start > end {
+        return Err(SpikeError::IndexOutOfRange);
+    }
+
+    file.seek(SeekFrom::Start(
+        header.data_offset + (start * header.size) as u64,
+    ))?;
+    let mut bytes = vec![0u8; (end - start) * header.size];
+    file.read_exact(&mut bytes)?;
+    let values = bytes
+        .chunks_exact(header.size)
+        .map(|x| header.value(x))
+        .collect();
+    Ok((header.shape, values))
+}
+
+/// Read a NPY file, returning the shape of the array and its elements in row
+/// major order, converted to f64 whatever their type
+pub fn read_npy(path: impl AsRef<Path>) -> Result<(Vec<usize>, Vec<f64>), SpikeError> {
+    read_npy_slice(path, 0, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_read_back() {
+        let path = std::env::temp_dir().join("spike_rs_test_npy.npy");
+        write_npy(&path, &[2, 3], &[1i16, -2, 3, -4, 5, -32768]).unwrap();
+        assert_eq!(
+            read_npy(&path).unwrap(),
+            (vec![2, 3], vec![1., -2., 3., -4., 5., -32768.])
+        );
+        assert_eq!(
+            read_npy_slice(&path, 4, Some(10)).unwrap().1,
+            vec![5., -32768.]
+        );
+
+        write_npy(&path, &[2], &[0.5f32, -1.25]).unwrap();
+        assert_eq!(read_npy(&path).unwrap(), (vec![2], vec![0.5, -1.25]));
+        fs::remove_file(&path).unwrap();
+    }
+}
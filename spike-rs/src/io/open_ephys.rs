@@ -0,0 +1,556 @@
+use std::{
+    fs::{self, File},
+    path::Path,
+};
+
+use super::{
+    interleaved::Int16File,
+    json::{parse_json, JsonValue},
+    npy::{read_npy, read_npy_slice},
+    parse_volts,
+};
+use crate::{
+    error::SpikeError,
+    time::DateTime,
+    types::{
+        check_digital_range, check_range, ChannelInfo, EventEntity, MemoryPhase, PhaseHandler,
+        RawBlock,
+    },
+};
+
+/// Name of the file describing the content of a recording
+pub const STRUCTURE_FILENAME: &str = "structure.oebin";
+
+fn invalid(reason: &str) -> SpikeError {
+    SpikeError::OpenEphysInvalid(reason.to_string())
+}
+
+/// Continuous stream of a recording, with its channels interleaved in a
+/// `continuous.dat` file
+#[derive(Debug)]
+struct ContinuousStream {
+    name: String,
+    samples: Int16File,
+    sampling_frequency: f32,
+    datalen: usize,
+    first_sample: i64,
+    labels: Vec<String>,
+    /// volts for each step of the ADC of each channel
+    scales: Vec<f32>,
+}
+
+impl ContinuousStream {
+    fn new(folder: &Path, entry: &JsonValue) -> Result<Self, SpikeError> {
+        let folder_name = entry
+            .get("folder_name")
+            .and_then(|x| x.as_str())
+            .ok_or_else(|| invalid("missing continuous folder name"))?;
+        let sampling_frequency = entry
+            .get("sample_rate")
+            .and_then(|x| x.as_f64())
+            .ok_or_else(|| invalid("missing sample rate"))? as f32;
+        let channels = entry
+            .get("channels")
+            .and_then(|x| x.as_array())
+            .ok_or_else(|| invalid("missing channels"))?;
+        if channels.is_empty() {
+            return Err(invalid("stream without channels"));
+        }
+
+        let mut labels = vec![];
+        let mut scales = vec![];
+        for channel in channels.iter() {
+            let label = channel
+                .get("channel_name")
+                .and_then(|x| x.as_str())
+                .ok_or_else(|| invalid("missing channel name"))?;
+            let bit_volts = channel
+                .get("bit_volts")
+                .and_then(|x| x.as_f64())
+                .ok_or_else(|| invalid("missing bit_volts"))? as f32;
+            // old recordings don't report the units, always microvolts
+            let units = match channel.get("units").and_then(|x| x.as_str()) {
+                Some(units) if !units.is_empty() => units,
+                _ => "uV",
+            };
+            let factor =
+                parse_volts(units).ok_or_else(|| invalid(&format!("unknown units of {label}")))?;
+            labels.push(label.to_string());
+            scales.push(bit_volts * factor);
+        }
+
+        let stream_folder = folder.join("continuous").join(folder_name);
+        let samples = Int16File::new(
+            File::open(stream_folder.join("continuous.dat"))?,
+            0,
+            labels.len(),
+            true,
+        );
+        let datalen = samples.n_frames()?;
+
+        // the first sample is the one of the start of the acquisition, not of
+        // the recording. The old recordings have it in the timestamps.
+        let mut first_sample = 0;
+        for filename in ["sample_numbers.npy", "timestamps.npy"] {
+            let path = stream_folder.join(filename);
+            if path.exists() {
+                let (_, values) = read_npy_slice(path, 0, Some(1))?;
+                first_sample = values.first().copied().unwrap_or_default() as i64;
+                break;
+            }
+        }
+
+        Ok(Self {
+            name: folder_name.trim_end_matches('/').to_string(),
+            samples,
+            sampling_frequency,
+            datalen,
+            first_sample,
+            labels,
+            scales,
+        })
+    }
+
+    /// Read the samples in `start..end` of the channels at `columns`,
+    /// returning the values in volts of each one
+    fn read_columns(
+        &self,
+        columns: &[usize],
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<Vec<f32>>, SpikeError> {
+        Ok(self
+            .samples
+            .read_columns(columns, start, end)?
+            .into_iter()
+            .zip(columns.iter())
+            .map(|(values, column)| {
+                let scale = self.scales[*column];
+                values.iter().map(|x| *x as f32 * scale).collect()
+            })
+            .collect())
+    }
+
+    fn column(&self, channel: &str) -> Result<usize, SpikeError> {
+        self.labels
+            .iter()
+            .position(|x| x == channel)
+            .ok_or(SpikeError::RawDataLabelNotFound)
+    }
+}
+
+/// TTL line of a recording, as the samples where its state changes
+#[derive(Debug, Clone)]
+struct TtlLine {
+    label: String,
+    transitions: Vec<(usize, bool)>,
+}
+
+/// Read the TTL lines of an events folder, with the samples relative to
+/// `first_sample`
+fn read_ttl_lines(folder: &Path, first_sample: i64) -> Result<Vec<TtlLine>, SpikeError> {
+    // old recordings have the sample numbers in the timestamps
+    let (states, samples) = if folder.join("states.npy").exists() {
+        ("states.npy", "sample_numbers.npy")
+    } else if folder.join("channel_states.npy").exists() {
+        ("channel_states.npy", "timestamps.npy")
+    } else {
+        return Ok(vec![]);
+    };
+    let (_, states) = read_npy(folder.join(states))?;
+    let (_, samples) = read_npy(folder.join(samples))?;
+    if states.len() != samples.len() {
+        return Err(invalid("TTL states and samples of different length"));
+    }
+
+    let name = folder
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // the TTL lines with their number, to sort them by it
+    let mut lines: Vec<(i64, TtlLine)> = vec![];
+    for (state, sample) in states.iter().zip(samples.iter()) {
+        // the state is the line, starting from 1, positive when it goes high
+        let line = state.abs() as i64;
+        let sample = (*sample as i64 - first_sample).max(0) as usize;
+        let index = match lines.iter().position(|(x, _)| *x == line) {
+            Some(index) => index,
+            None => {
+                lines.push((
+                    line,
+                    TtlLine {
+                        label: format!("{name}_{line}"),
+                        transitions: vec![],
+                    },
+                ));
+                lines.len() - 1
+            }
+        };
+        lines[index].1.transitions.push((sample, *state > 0f64));
+    }
+    lines.sort_by_key(|(line, _)| *line);
+    Ok(lines
+        .into_iter()
+        .map(|(_, mut line)| {
+            line.transitions.sort_by_key(|(sample, _)| *sample);
+            line
+        })
+        .collect())
+}
+
+/// OpenEphysPhase
+///
+/// A phase read from a recording of Open Ephys in the binary format: a
+/// `structure.oebin` JSON file describing the continuous streams, each one
+/// with its channels interleaved as 16 bit integers in a `continuous.dat`
+/// file, and the events, with the TTL lines changes in `states.npy` and
+/// `sample_numbers.npy` files.
+///
+/// The continuous streams are the analog streams of the phase, the first
+/// one being the raw data stream, and are converted to volts with the
+/// `bit_volts` of each channel. The TTL lines of the events of the raw
+/// data stream are the digital channels, labeled as the events folder
+/// followed by the line, e.g. `TTL_1`, in the order of the line numbers.
+///
+/// The TTL lines are loaded when opening, while the continuous samples are
+/// read from the `continuous.dat` files at each request. Nothing is written
+/// back to the recording: peak trains are kept in memory and setting raw or
+/// digital data fails with `PhaseIsReadOnly`.
+pub struct OpenEphysPhase {
+    streams: Vec<ContinuousStream>,
+    raw_data_stream: usize,
+    digitals: Vec<TtlLine>,
+    peaks: MemoryPhase,
+}
+
+impl OpenEphysPhase {
+    /// Open a recording of Open Ephys, from its folder or its
+    /// `structure.oebin` file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SpikeError> {
+        let path = path.as_ref();
+        let (folder, structure) = if path.is_dir() {
+            (path.to_path_buf(), path.join(STRUCTURE_FILENAME))
+        } else {
+            (
+                path.parent().map(|x| x.to_path_buf()).unwrap_or_default(),
+                path.to_path_buf(),
+            )
+        };
+        let structure = parse_json(&fs::read_to_string(structure)?)?;
+
+        let streams = structure
+            .get("continuous")
+            .and_then(|x| x.as_array())
+            .unwrap_or_default()
+            .iter()
+            .map(|entry| ContinuousStream::new(&folder, entry))
+            .collect::<Result<Vec<ContinuousStream>, SpikeError>>()?;
+        let raw_stream = streams
+            .first()
+            .ok_or_else(|| invalid("missing continuous streams"))?;
+
+        // the events of the raw data stream are in folders with its name as
+        // prefix
+        let mut digitals = vec![];
+        let events = structure
+            .get("events")
+            .and_then(|x| x.as_array())
+            .unwrap_or_default();
+        for entry in events.iter() {
+            let Some(folder_name) = entry.get("folder_name").and_then(|x| x.as_str()) else {
+                continue;
+            };
+            if !folder_name.starts_with(&raw_stream.name) {
+                continue;
+            }
+            let events_folder = folder.join("events").join(folder_name);
+            digitals.extend(read_ttl_lines(&events_folder, raw_stream.first_sample)?);
+        }
+
+        Ok(Self {
+            peaks: MemoryPhase::new(raw_stream.sampling_frequency, raw_stream.datalen),
+            streams,
+            raw_data_stream: 0,
+            digitals,
+        })
+    }
+
+    /// Returns the sample number of the start of the recording from the start
+    /// of the acquisition
+    pub fn first_sample(&self) -> i64 {
+        self.raw_stream().first_sample
+    }
+
+    fn raw_stream(&self) -> &ContinuousStream {
+        &self.streams[self.raw_data_stream]
+    }
+
+    fn stream(&self, stream: &str) -> Result<&ContinuousStream, SpikeError> {
+        self.streams
+            .iter()
+            .find(|x| x.name == stream)
+            .ok_or(SpikeError::AnalogStreamNotFound)
+    }
+}
+
+impl PhaseHandler for OpenEphysPhase {
+    fn sampling_frequency(&self) -> f32 {
+        self.raw_stream().sampling_frequency
+    }
+
+    fn datalen(&self) -> usize {
+        self.raw_stream().datalen
+    }
+
+    fn labels(&self) -> Vec<String> {
+        self.raw_stream().labels.clone()
+    }
+
+    fn channel_info(&self, channel: &str) -> Result<ChannelInfo, SpikeError> {
+        let stream = self.raw_stream();
+        let column = stream.column(channel)?;
+        let mut info = ChannelInfo {
+            label: channel.to_string(),
+            row_index: column as i32,
+            raw_data_type: "Int".to_string(),
+            unit: "V".to_string(),
+            tick: (1e6 / stream.sampling_frequency as f64).round() as i64,
+            adc_bits: 16,
+            ..Default::default()
+        };
+        info.set_adc_step(stream.scales[column] as f64);
+        Ok(info)
+    }
+
+    fn recording_start(&self) -> Option<DateTime> {
+        None
+    }
+
+    fn raw_data(
+        &self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        let name = self.raw_stream().name.clone();
+        self.analog_data(&name, channel, start, end)
+    }
+
+    fn set_raw_data(
+        &mut self,
+        _channel: &str,
+        _start: Option<usize>,
+        _data: &[f32],
+    ) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn raw_block(
+        &self,
+        channels: &[String],
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<RawBlock, SpikeError> {
+        let stream = self.raw_stream();
+        let (actual_start, actual_end) = check_range(start, end, stream.datalen)?;
+        let columns = channels
+            .iter()
+            .map(|channel| stream.column(channel))
+            .collect::<Result<Vec<usize>, SpikeError>>()?;
+        let data = stream
+            .read_columns(&columns, actual_start, actual_end)?
+            .concat();
+        RawBlock::new(
+            channels.to_vec(),
+            actual_start,
+            actual_end - actual_start,
+            data,
+        )
+    }
+
+    fn analog_streams(&self) -> Vec<String> {
+        self.streams.iter().map(|x| x.name.clone()).collect()
+    }
+
+    fn raw_data_stream(&self) -> String {
+        self.raw_stream().name.clone()
+    }
+
+    /// Only the streams with the same sampling frequency and length of the
+    /// current one can be selected, since the peak trains refer to them
+    fn set_raw_data_stream(&mut self, stream: &str) -> Result<(), SpikeError> {
+        let index = self
+            .streams
+            .iter()
+            .position(|x| x.name == stream)
+            .ok_or(SpikeError::AnalogStreamNotFound)?;
+        let (current, selected) = (self.raw_stream(), &self.streams[index]);
+        if current.sampling_frequency != selected.sampling_frequency
+            || current.datalen != selected.datalen
+        {
+            return Err(invalid(
+                "stream with different sampling frequency or length",
+            ));
+        }
+        self.raw_data_stream = index;
+        Ok(())
+    }
+
+    fn analog_labels(&self, stream: &str) -> Result<Vec<String>, SpikeError> {
+        Ok(self.stream(stream)?.labels.clone())
+    }
+
+    fn analog_data(
+        &self,
+        stream: &str,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        let stream = self.stream(stream)?;
+        let (actual_start, actual_end) = check_range(start, end, stream.datalen)?;
+        let column = stream.column(channel)?;
+        Ok(stream
+            .read_columns(&[column], actual_start, actual_end)?
+            .remove(0))
+    }
+
+    fn n_digitals(&self) -> usize {
+        self.digitals.len()
+    }
+
+    fn digital_labels(&self) -> Vec<String> {
+        self.digitals.iter().map(|x| x.label.clone()).collect()
+    }
+
+    fn digital(
+        &self,
+        index: usize,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        if self.digitals.is_empty() {
+            return Err(SpikeError::DigitalNoDigitalPresent);
+        }
+
+        if index >= self.digitals.len() {
+            return Err(SpikeError::IndexOutOfRange);
+        }
+
+        let (actual_start, actual_end) = check_digital_range(start, end, self.datalen())?;
+
+        let transitions = &self.digitals[index].transitions;
+        let mut state = false;
+        let mut next = 0;
+        let mut data = Vec::with_capacity(actual_end - actual_start);
+        for sample in actual_start..actual_end {
+            while next < transitions.len() && transitions[next].0 <= sample {
+                state = transitions[next].1;
+                next += 1;
+            }
+            data.push(if state { 1f32 } else { 0f32 });
+        }
+        Ok(data)
+    }
+
+    fn set_digital(
+        &mut self,
+        _index: usize,
+        _start: Option<usize>,
+        _data: &[f32],
+    ) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn n_events(&self) -> usize {
+        0
+    }
+
+    fn events(&self, _index: usize) -> Result<EventEntity, SpikeError> {
+        Err(SpikeError::IndexOutOfRange)
+    }
+
+    crate::delegate_peak_trains!(peaks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::npy::write_npy;
+
+    #[test]
+    fn open_binary_recording() {
+        let folder = std::env::temp_dir().join("spike_rs_test_open_ephys");
+        let continuous = folder.join("continuous").join("Rhythm Data-100.0");
+        let events = folder.join("events").join("Rhythm Data-100.0").join("TTL");
+        fs::create_dir_all(&continuous).unwrap();
+        fs::create_dir_all(&events).unwrap();
+        fs::write(
+            folder.join(STRUCTURE_FILENAME),
+            r#"{
+                "GUI version": "0.6.4",
+                "continuous": [{
+                    "folder_name": "Rhythm Data-100.0/",
+                    "sample_rate": 30000.0,
+                    "num_channels": 2,
+                    "channels": [
+                        {"channel_name": "CH1", "bit_volts": 0.195, "units": "uV"},
+                        {"channel_name": "CH2", "bit_volts": 0.195, "units": "uV"}
+                    ]
+                }],
+                "events": [{
+                    "folder_name": "Rhythm Data-100.0/TTL/",
+                    "sample_rate": 30000.0,
+                    "type": "int16"
+                }],
+                "spikes": []
+            }"#,
+        )
+        .unwrap();
+
+        let mut bytes = vec![];
+        for sample in 0..10i16 {
+            for value in [sample, -sample] {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        fs::write(continuous.join("continuous.dat"), bytes).unwrap();
+        let samples: Vec<i64> = (1000..1010).collect();
+        write_npy(continuous.join("sample_numbers.npy"), &[10], &samples).unwrap();
+        write_npy(events.join("states.npy"), &[3], &[10i16, 2, -10]).unwrap();
+        write_npy(
+            events.join("sample_numbers.npy"),
+            &[3],
+            &[1002i64, 1003, 1005],
+        )
+        .unwrap();
+
+        let phase = OpenEphysPhase::open(&folder).unwrap();
+        assert_eq!(phase.sampling_frequency(), 30000f32);
+        assert_eq!(phase.datalen(), 10);
+        assert_eq!(phase.first_sample(), 1000);
+        assert_eq!(phase.labels(), vec!["CH1".to_string(), "CH2".to_string()]);
+        let step = 0.195f32 * 1e-6;
+        assert_eq!(
+            phase.raw_data("CH2", Some(3), Some(5)).unwrap(),
+            vec![-3f32 * step, -4f32 * step]
+        );
+        let info = phase.channel_info("CH1").unwrap();
+        assert_eq!((info.conversion_factor, info.exponent), (195, -9));
+
+        // the lines are sorted by number, not by label
+        assert_eq!(
+            phase.digital_labels(),
+            vec!["TTL_2".to_string(), "TTL_10".to_string()]
+        );
+        assert_eq!(
+            phase.digital(1, Some(1), Some(7)).unwrap(),
+            vec![0., 1., 1., 1., 0., 0.]
+        );
+        assert_eq!(
+            phase.digital(0, Some(4), None).unwrap(),
+            vec![1., 1., 1., 1., 1., 1.]
+        );
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+use std::{fs, path::Path};
+
+use super::mat::{read_mat, write_mat, MatArray, MatVariable};
+use crate::{error::SpikeError, types::PhaseHandler};
+
+/// Name of the MATLAB variable with the peak train in the SpyCode files
+pub const PEAK_TRAIN_VARIABLE: &str = "peak_train";
+
+/// Returns the name of the SpyCode file with the peak train of the channel
+/// `label`, e.g. `ptrain_exp1_12.mat`
+pub fn peak_train_filename(experiment: &str, label: &str) -> String {
+    format!("ptrain_{experiment}_{label}.mat")
+}
+
+/// Read the peak train of a SpyCode file: the samples and the values of the
+/// non zero elements of the `peak_train` variable, or of the first sparse
+/// variable if not present. Samples are 0 based.
+pub fn read_peak_train(path: impl AsRef<Path>) -> Result<(Vec<usize>, Vec<f32>), SpikeError> {
+    let variables = read_mat(path)?;
+    let variable = variables
+        .iter()
+        .find(|variable| variable.name == PEAK_TRAIN_VARIABLE)
+        .or_else(|| {
+            variables
+                .iter()
+                .find(|variable| matches!(variable.array, MatArray::Sparse { .. }))
+        })
+        .ok_or_else(|| SpikeError::MatFileVariableNotFound(PEAK_TRAIN_VARIABLE.to_string()))?;
+
+    // the sample is the position of the value in column major order, so that
+    // both column and row vectors are accepted
+    let elements: Vec<(usize, f64)> = match &variable.array {
+        MatArray::Dense { values, .. } => values.iter().copied().enumerate().collect(),
+        MatArray::Sparse {
+            rows,
+            row_indices,
+            column_starts,
+            values,
+            ..
+        } => {
+            // a corrupted file must not index past the stored elements
+            let nnz = row_indices.len().min(values.len());
+            if column_starts.windows(2).any(|range| range[0] > range[1])
+                || column_starts.last().is_some_and(|last| *last > nnz)
+                || row_indices.iter().any(|row| row >= rows)
+            {
+                return Err(SpikeError::MatFileInvalid(
+                    "inconsistent sparse peak train".to_string(),
+                ));
+            }
+            column_starts
+                .windows(2)
+                .enumerate()
+                .flat_map(|(column, range)| {
+                    (range[0]..range[1]).map(move |i| (column * rows + row_indices[i], values[i]))
+                })
+                .collect()
+        }
+    };
+
+    Ok(elements
+        .into_iter()
+        .filter(|(_, value)| *value != 0f64)
+        .map(|(sample, value)| (sample, value as f32))
+        .unzip())
+}
+
+/// Write a peak train to a SpyCode file, as a sparse column vector of
+/// `datalen` elements with the values of the peaks at their samples
+pub fn write_peak_train(
+    path: impl AsRef<Path>,
+    datalen: usize,
+    peak_train: (Vec<usize>, Vec<f32>),
+) -> Result<(), SpikeError> {
+    let (samples, values) = peak_train;
+    if samples.len() != values.len() {
+        return Err(SpikeError::SetPeakTrainSamplesValuesDifferentLen);
+    }
+    if samples.iter().any(|sample| *sample >= datalen) {
+        return Err(SpikeError::PeakTrainOutOfBounds);
+    }
+    let variable = MatVariable {
+        name: PEAK_TRAIN_VARIABLE.to_string(),
+        array: MatArray::Sparse {
+            rows: datalen,
+            columns: 1,
+            column_starts: vec![0, samples.len()],
+            row_indices: samples,
+            values: values.iter().map(|x| *x as f64).collect(),
+        },
+    };
+    write_mat(path, &[variable])
+}
+
+/// Export all the peak trains of the phase to `folder`, created if missing,
+/// with a SpyCode file for each channel named as in `peak_train_filename`.
+/// Peaks with a value of 0 can't be told apart from the empty samples and
+/// are lost when read back.
+/// Returns the labels of the exported peak trains.
+pub fn export_peak_trains(
+    phase: &impl PhaseHandler,
+    folder: impl AsRef<Path>,
+    experiment: &str,
+) -> Result<Vec<String>, SpikeError> {
+    let folder = folder.as_ref();
+    fs::create_dir_all(folder)?;
+
+    let labels = phase.peak_train_labels()?;
+    for label in labels.iter() {
+        let peak_train = phase.peak_train(label, None, None)?;
+        write_peak_train(
+            folder.join(peak_train_filename(experiment, label)),
+            phase.datalen(),
+            peak_train,
+        )?;
+    }
+    Ok(labels)
+}
+
+/// Import the SpyCode peak trains of `experiment` from `folder`, replacing
+/// the peak trains of the phase with the same labels.
+/// The label of each file is the part of its name after the
+/// `ptrain_<experiment>_` prefix.
+/// The peaks of each file are checked as in `replace_peak_train_range`:
+/// they must be inside the recording of the phase.
+/// Returns the labels of the imported peak trains, sorted.
+pub fn import_peak_trains(
+    phase: &mut impl PhaseHandler,
+    folder: impl AsRef<Path>,
+    experiment: &str,
+) -> Result<Vec<String>, SpikeError> {
+    let prefix = format!("ptrain_{experiment}_");
+    let mut files = vec![];
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        let filename = entry.file_name().to_string_lossy().to_string();
+        if let Some(label) = filename
+            .strip_prefix(&prefix)
+            .and_then(|x| x.strip_suffix(".mat"))
+        {
+            files.push((label.to_string(), entry.path()));
+        }
+    }
+    files.sort();
+
+    for (label, path) in files.iter() {
+        let peak_train = read_peak_train(path)?;
+        phase.replace_peak_train_range(label, 0, phase.datalen(), peak_train)?;
+    }
+    Ok(files.into_iter().map(|(label, _)| label).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MemoryPhase;
+
+    #[test]
+    fn export_and_import_round_trip() {
+        let mut phase = MemoryPhase::new(10000f32, 1000);
+        phase
+            .set_peak_train(
+                "12",
+                None,
+                None,
+                (vec![10, 500, 999], vec![1e-5, -2e-5, 3e-5]),
+            )
+            .unwrap();
+        phase
+            .set_peak_train("A4", None, None, (vec![0], vec![-4e-5]))
+            .unwrap();
+
+        let folder = std::env::temp_dir().join("spike_rs_test_spycode");
+        let labels = export_peak_trains(&phase, &folder, "exp_1").unwrap();
+        assert_eq!(labels.len(), 2);
+        assert!(folder.join("ptrain_exp_1_12.mat").exists());
+
+        let mut imported = MemoryPhase::new(10000f32, 1000);
+        assert_eq!(
+            import_peak_trains(&mut imported, &folder, "exp_1").unwrap(),
+            vec!["12".to_string(), "A4".to_string()]
+        );
+        for label in ["12", "A4"] {
+            assert_eq!(
+                imported.peak_train(label, None, None).unwrap(),
+                phase.peak_train(label, None, None).unwrap()
+            );
+        }
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn import_rejects_peaks_outside_the_phase() {
+        let folder = std::env::temp_dir().join("spike_rs_test_spycode_bounds");
+        fs::create_dir_all(&folder).unwrap();
+        write_peak_train(
+            folder.join(peak_train_filename("exp_1", "12")),
+            2000,
+            (vec![10, 1500], vec![1e-5, 2e-5]),
+        )
+        .unwrap();
+
+        let mut phase = MemoryPhase::new(10000f32, 1000);
+        assert!(matches!(
+            import_peak_trains(&mut phase, &folder, "exp_1"),
+            Err(SpikeError::PeakTrainOutOfBounds)
+        ));
+        assert!(phase.peak_train_labels().unwrap().is_empty());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn read_rejects_inconsistent_sparse_matrix() {
+        let path = std::env::temp_dir().join("spike_rs_test_spycode_sparse.mat");
+        let sparse = |column_starts: Vec<usize>, row_indices: Vec<usize>| MatVariable {
+            name: PEAK_TRAIN_VARIABLE.to_string(),
+            array: MatArray::Sparse {
+                rows: 10,
+                columns: column_starts.len() - 1,
+                values: vec![1f64; row_indices.len()],
+                column_starts,
+                row_indices,
+            },
+        };
+
+        for variable in [
+            sparse(vec![0, 3, 1, 3], vec![1, 2, 3]),
+            sparse(vec![0, 2], vec![1, 10]),
+        ] {
+            write_mat(&path, &[variable]).unwrap();
+            assert!(matches!(
+                read_peak_train(&path),
+                Err(SpikeError::MatFileInvalid(_))
+            ));
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+
+/// Role of an electrode of the array
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ElectrodeKind {
+    Recording,
+    Reference,
+    Ground,
+}
+
+/// Electrode
+///
+/// A single electrode of a layout, placed on the grid at `row` and `column`
+/// (starting from 0, top left). `well` is set only on multiwell arrays.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Electrode {
+    pub label: String,
+    pub row: usize,
+    pub column: usize,
+    pub kind: ElectrodeKind,
+    pub well: Option<usize>,
+}
+
+/// MeaLayout
+///
+/// Geometry of a microelectrode array: the electrodes on a `rows` x `columns`
+/// grid with a `pitch` inter-electrode distance in micrometers.
+/// The labels are the ones used by the acquisition software for the channels
+/// of the recording.
+#[derive(Debug, Clone)]
+pub struct MeaLayout {
+    pub name: String,
+    pub rows: usize,
+    pub columns: usize,
+    pub pitch: f32,
+    electrodes: Vec<Electrode>,
+    labels_map: HashMap<String, usize>,
+}
+
+/// Column letters of the 120MEA and 256MEA, without I and Q
+const LETTERS: [char; 16] = [
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'R',
+];
+
+impl MeaLayout {
+    /// Create a custom layout from a list of electrodes
+    pub fn new(
+        name: &str,
+        rows: usize,
+        columns: usize,
+        pitch: f32,
+        electrodes: Vec<Electrode>,
+    ) -> Self {
+        let labels_map = electrodes
+            .iter()
+            .enumerate()
+            .map(|(i, electrode)| (electrode.label.clone(), i))
+            .collect();
+        Self {
+            name: name.to_string(),
+            rows,
+            columns,
+            pitch,
+            electrodes,
+            labels_map,
+        }
+    }
+
+    /// MCS 60MEA with 8x8 grid and 200 um pitch. Labels are column and row
+    /// numbers starting from 1 ("12" is column 1, row 2) and the corners are
+    /// missing. Electrode 15 is the internal reference, labeled "Ref".
+    pub fn mea60() -> Self {
+        let mut electrodes = vec![];
+        for column in 0..8 {
+            for row in 0..8 {
+                if (row == 0 || row == 7) && (column == 0 || column == 7) {
+                    continue;
+                }
+                let (label, kind) = if column == 0 && row == 4 {
+                    ("Ref".to_string(), ElectrodeKind::Reference)
+                } else {
+                    (
+                        format!("{}{}", column + 1, row + 1),
+                        ElectrodeKind::Recording,
+                    )
+                };
+                electrodes.push(Electrode {
+                    label,
+                    row,
+                    column,
+                    kind,
+                    well: None,
+                });
+            }
+        }
+        Self::new("60MEA", 8, 8, 200f32, electrodes)
+    }
+
+    /// MCS 60-6well MEA. Six wells on two rows of three, each one with a 3x3
+    /// grid of recording electrodes, 200 um pitch, and its own reference.
+    /// Labels are the well letter (A to F) followed by the electrode number
+    /// (1 to 9, row by row) and "Ref" for the reference, e.g. "B5", "BRef".
+    /// Wells are separated by an empty row and column on the grid, so only
+    /// distances inside the same well are meaningful.
+    pub fn mea60_6well() -> Self {
+        let mut electrodes = vec![];
+        for (well, well_letter) in LETTERS.iter().take(6).enumerate() {
+            let row_offset = (well / 3) * 4;
+            let column_offset = (well % 3) * 4;
+            for i in 0..9 {
+                electrodes.push(Electrode {
+                    label: format!("{}{}", well_letter, i + 1),
+                    row: row_offset + i / 3,
+                    column: column_offset + i % 3,
+                    kind: ElectrodeKind::Recording,
+                    well: Some(well),
+                });
+            }
+            electrodes.push(Electrode {
+                label: format!("{}Ref", well_letter),
+                row: row_offset + 3,
+                column: column_offset + 1,
+                kind: ElectrodeKind::Reference,
+                well: Some(well),
+            });
+        }
+        Self::new("60-6wellMEA", 8, 11, 200f32, electrodes)
+    }
+
+    /// MCS 120MEA with 12x12 grid and 100 um pitch. Labels are the column
+    /// letter (A to M, without I) followed by the row number, and six
+    /// electrodes are missing at each corner, a triangle of three rows and
+    /// columns. The internal references are outside of the grid and not
+    /// listed.
+    pub fn mea120() -> Self {
+        let mut electrodes = vec![];
+        for (column, letter) in LETTERS.iter().take(12).enumerate() {
+            for row in 0..12 {
+                if row.min(11 - row) + column.min(11 - column) < 3 {
+                    continue;
+                }
+                electrodes.push(Electrode {
+                    label: format!("{}{}", letter, row + 1),
+                    row,
+                    column,
+                    kind: ElectrodeKind::Recording,
+                    well: None,
+                });
+            }
+        }
+        Self::new("120MEA", 12, 12, 100f32, electrodes)
+    }
+
+    /// MCS 256MEA with 16x16 grid and 200 um pitch. Labels are the column
+    /// letter (A to R, without I and Q) followed by the row number. The four
+    /// corner electrodes, A1, A16, R1 and R16, are internal references wired
+    /// to the amplifier ground, so their channels carry no signal.
+    pub fn mea256() -> Self {
+        let mut electrodes = vec![];
+        for (column, letter) in LETTERS.iter().enumerate() {
+            for row in 0..16 {
+                let kind = if (row == 0 || row == 15) && (column == 0 || column == 15) {
+                    ElectrodeKind::Ground
+                } else {
+                    ElectrodeKind::Recording
+                };
+                electrodes.push(Electrode {
+                    label: format!("{}{}", letter, row + 1),
+                    row,
+                    column,
+                    kind,
+                    well: None,
+                });
+            }
+        }
+        Self::new("256MEA", 16, 16, 200f32, electrodes)
+    }
+
+    /// Returns the built-in layouts
+    pub fn builtin() -> Vec<Self> {
+        vec![
+            Self::mea60(),
+            Self::mea60_6well(),
+            Self::mea120(),
+            Self::mea256(),
+        ]
+    }
+
+    /// Returns the built-in layout with the provided name, if exists
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::builtin()
+            .into_iter()
+            .find(|layout| layout.name == name)
+    }
+
+    /// Returns the smallest built-in layout that contains all the `labels`,
+    /// e.g. the labels of a recording, if any
+    pub fn detect(labels: &[String]) -> Option<Self> {
+        Self::builtin()
+            .into_iter()
+            .filter(|layout| labels.iter().all(|label| layout.electrode(label).is_some()))
+            .min_by_key(|layout| layout.electrodes.len())
+    }
+
+    /// Returns all the electrodes of the layout
+    pub fn electrodes(&self) -> &[Electrode] {
+        &self.electrodes
+    }
+
+    /// Returns the electrode labeled `label`, if exists
+    pub fn electrode(&self, label: &str) -> Option<&Electrode> {
+        self.labels_map
+            .get(label.trim())
+            .map(|index| &self.electrodes[*index])
+    }
+
+    /// Returns the labels of the electrodes of a kind
+    pub fn labels_of_kind(&self, kind: ElectrodeKind) -> Vec<String> {
+        self.electrodes
+            .iter()
+            .filter(|electrode| electrode.kind == kind)
+            .map(|electrode| electrode.label.clone())
+            .collect()
+    }
+
+    /// Returns the labels of the reference electrodes
+    pub fn references(&self) -> Vec<String> {
+        self.labels_of_kind(ElectrodeKind::Reference)
+    }
+
+    /// Returns the labels of the ground electrodes
+    pub fn grounds(&self) -> Vec<String> {
+        self.labels_of_kind(ElectrodeKind::Ground)
+    }
+
+    /// Returns the (row, column) position on the grid of an electrode
+    pub fn grid_position(&self, label: &str) -> Option<(usize, usize)> {
+        self.electrode(label)
+            .map(|electrode| (electrode.row, electrode.column))
+    }
+
+    /// Returns the (x, y) coordinates in micrometers of an electrode, with the
+    /// origin at the top left of the grid
+    pub fn coordinates(&self, label: &str) -> Option<(f32, f32)> {
+        self.electrode(label).map(|electrode| {
+            (
+                electrode.column as f32 * self.pitch,
+                electrode.row as f32 * self.pitch,
+            )
+        })
+    }
+
+    /// Returns the distance in micrometers between two electrodes
+    pub fn distance(&self, label_a: &str, label_b: &str) -> Option<f32> {
+        let (xa, ya) = self.coordinates(label_a)?;
+        let (xb, yb) = self.coordinates(label_b)?;
+        Some(((xa - xb).powi(2) + (ya - yb).powi(2)).sqrt())
+    }
+
+    /// Returns the labels of the recording electrodes at most `radius` grid
+    /// steps away from `label`, diagonals included, the electrode itself
+    /// excluded. On multiwell arrays only the electrodes of the same well
+    /// are returned.
+    pub fn neighbours(&self, label: &str, radius: usize) -> Vec<String> {
+        let center = match self.electrode(label) {
+            Some(electrode) => electrode,
+            None => return vec![],
+        };
+
+        self.electrodes
+            .iter()
+            .filter(|electrode| {
+                electrode.kind == ElectrodeKind::Recording
+                    && electrode.label != center.label
+                    && electrode.well == center.well
+                    && electrode.row.abs_diff(center.row) <= radius
+                    && electrode.column.abs_diff(center.column) <= radius
+            })
+            .map(|electrode| electrode.label.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|label| label.to_string()).collect()
+    }
+
+    #[test]
+    fn builtin_layouts() {
+        let counts = |layout: &MeaLayout| {
+            (
+                layout.labels_of_kind(ElectrodeKind::Recording).len(),
+                layout.references().len(),
+                layout.grounds().len(),
+            )
+        };
+
+        let mea60 = MeaLayout::mea60();
+        assert_eq!(counts(&mea60), (59, 1, 0));
+        assert_eq!(mea60.references(), labels(&["Ref"]));
+        assert!(mea60.electrode("11").is_none());
+        assert_eq!(mea60.grid_position("Ref"), Some((4, 0)));
+        assert_eq!(mea60.distance("12", "22"), Some(200f32));
+
+        let mea60_6well = MeaLayout::mea60_6well();
+        assert_eq!(counts(&mea60_6well), (54, 6, 0));
+        assert_eq!(mea60_6well.neighbours("A1", 1), labels(&["A2", "A4", "A5"]));
+        assert_eq!(mea60_6well.neighbours("A3", 3).len(), 8);
+
+        let mea120 = MeaLayout::mea120();
+        assert_eq!(counts(&mea120), (120, 0, 0));
+        for missing in ["A1", "A2", "A3", "B1", "B2", "C1", "M12", "M10", "L11"] {
+            assert!(mea120.electrode(missing).is_none(), "{missing}");
+        }
+        assert!(mea120.electrode("A4").is_some());
+        assert!(mea120.electrode("C2").is_some());
+
+        let mea256 = MeaLayout::mea256();
+        assert_eq!(counts(&mea256), (252, 0, 4));
+        assert_eq!(mea256.grounds(), labels(&["A1", "A16", "R1", "R16"]));
+        assert_eq!(mea256.coordinates("R16"), Some((3000f32, 3000f32)));
+
+        for layout in MeaLayout::builtin() {
+            assert_eq!(
+                MeaLayout::from_name(&layout.name).unwrap().name,
+                layout.name
+            );
+            for electrode in layout.electrodes() {
+                assert!(electrode.row < layout.rows && electrode.column < layout.columns);
+                assert_eq!(layout.electrode(&electrode.label), Some(electrode));
+            }
+        }
+    }
+
+    #[test]
+    fn detect_smallest_layout() {
+        let detected = |names: &[&str]| MeaLayout::detect(&labels(names)).map(|layout| layout.name);
+
+        assert_eq!(detected(&["12", "87", "Ref"]), Some("60MEA".to_string()));
+        let recording = MeaLayout::mea60().labels_of_kind(ElectrodeKind::Recording);
+        assert_eq!(MeaLayout::detect(&recording).unwrap().name, "60MEA");
+        // A5 is on the 60-6well, 120 and 256 arrays
+        assert_eq!(detected(&["A5", "BRef"]), Some("60-6wellMEA".to_string()));
+        assert_eq!(detected(&["A5", "D12"]), Some("120MEA".to_string()));
+        assert_eq!(detected(&["A5", "A12"]), Some("256MEA".to_string()));
+        assert_eq!(detected(&[" D12 "]), Some("120MEA".to_string()));
+        assert_eq!(detected(&["12", "A5"]), None);
+    }
+}
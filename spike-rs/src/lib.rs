@@ -5,6 +5,9 @@ pub mod analysis;
 
 /// import basic types and operations
 pub mod error;
+pub mod io;
+pub mod layout;
 pub mod operations;
 pub mod plot;
+pub mod time;
 pub mod types;
@@ -0,0 +1,664 @@
+use crate::{
+    error::SpikeError,
+    time::DateTime,
+    types::{check_range, ChannelInfo, EventEntity, PeakTrainMetadata, PhaseHandler},
+};
+
+/// The samples and the values of a peak train
+type PeakTrain = (Vec<usize>, Vec<f32>);
+
+/// ConcatPhase
+///
+/// An ordered list of phases, e.g. the consecutive files of a long
+/// experiment, seen as one continuous recording. The phases are placed one
+/// after the other, the gaps between the files are removed, so the samples
+/// and the event times of each phase are shifted by the length of the
+/// previous ones. All the phases must have the same sampling frequency and
+/// the same channels, the info about the channels and the recording start
+/// are the ones of the first phase.
+///
+/// The first sample of each phase is a boundary of the recording: peaks on
+/// different sides of it are not consecutive, but `peak_train` joins them
+/// in a single train. The analyses based on the distance between
+/// consecutive peaks, like the inter spike intervals and the burst
+/// detection, must be run on each train of `peak_train_segments`, as
+/// `inter_spike_intervals` does. The PSTH can use the joined train only if
+/// none of its stimulus windows crosses a boundary.
+pub struct ConcatPhase<P: PhaseHandler> {
+    phases: Vec<P>,
+    /// the first sample of each phase, plus the total datalen
+    offsets: Vec<usize>,
+}
+
+impl<P: PhaseHandler> ConcatPhase<P> {
+    pub fn new(phases: Vec<P>) -> Result<Self, SpikeError> {
+        let first = phases.first().ok_or(SpikeError::ConcatNoPhases)?;
+        let mut labels = first.labels();
+        labels.sort();
+
+        for (i, phase) in phases.iter().enumerate().skip(1) {
+            if phase.sampling_frequency() != first.sampling_frequency() {
+                return Err(SpikeError::ConcatSamplingFrequencyMismatch(i));
+            }
+            let mut phase_labels = phase.labels();
+            phase_labels.sort();
+            if phase_labels != labels {
+                return Err(SpikeError::ConcatLabelsMismatch(i));
+            }
+            if phase.n_digitals() != first.n_digitals() {
+                return Err(SpikeError::ConcatDigitalsMismatch(i));
+            }
+            if phase.n_events() != first.n_events() {
+                return Err(SpikeError::ConcatEventsMismatch(i));
+            }
+        }
+
+        let mut offsets = vec![0];
+        for phase in phases.iter() {
+            offsets.push(offsets[offsets.len() - 1] + phase.datalen());
+        }
+        Ok(Self { phases, offsets })
+    }
+
+    pub fn phases(&self) -> &[P] {
+        &self.phases
+    }
+
+    pub fn into_phases(self) -> Vec<P> {
+        self.phases
+    }
+
+    /// Returns the first sample of each phase in the concatenated recording
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets[..self.phases.len()]
+    }
+
+    /// Returns the samples where a phase ends and the next one starts
+    pub fn boundaries(&self) -> &[usize] {
+        &self.offsets[1..self.phases.len()]
+    }
+
+    /// Returns the index of the phase that contains `sample`, if any
+    pub fn segment(&self, sample: usize) -> Option<usize> {
+        if sample >= self.datalen() {
+            return None;
+        }
+        Some(self.offsets.partition_point(|x| *x <= sample) - 1)
+    }
+
+    /// Returns the peak train of `channel` split at the boundaries, one for
+    /// each phase, with the samples of the concatenated recording. A phase
+    /// without the train gives an empty one.
+    pub fn peak_train_segments(&self, channel: &str) -> Result<Vec<PeakTrain>, SpikeError> {
+        if !self.peak_train_labels()?.iter().any(|x| x == channel) {
+            return Err(SpikeError::PeakTrainNotFound);
+        }
+        self.phases
+            .iter()
+            .zip(self.offsets.iter())
+            .map(|(phase, offset)| {
+                if !Self::has_peak_train(phase, channel)? {
+                    return Ok((vec![], vec![]));
+                }
+                let (samples, values) = phase.peak_train(channel, None, None)?;
+                Ok((samples.iter().map(|x| x + offset).collect(), values))
+            })
+            .collect()
+    }
+
+    /// Returns the intervals in samples between the consecutive peaks of
+    /// `channel` that are in the same phase
+    pub fn inter_spike_intervals(&self, channel: &str) -> Result<Vec<usize>, SpikeError> {
+        Ok(self
+            .peak_train_segments(channel)?
+            .iter()
+            .flat_map(|(samples, _)| samples.windows(2).map(|x| x[1] - x[0]))
+            .collect())
+    }
+
+    /// Returns the offset of a phase in microseconds, the unit of the event
+    /// timestamps
+    fn offset_micros(&self, index: usize) -> i64 {
+        (self.sample_to_seconds(self.offsets[index]) * 1e6).round() as i64
+    }
+
+    /// Split the `[start, end)` range of the concatenated recording on the
+    /// phases, returning the index of each phase it overlaps and the range
+    /// in the samples of that phase
+    fn pieces(&self, start: usize, end: usize) -> Vec<(usize, usize, usize)> {
+        (0..self.phases.len())
+            .filter_map(|i| {
+                let piece_start = usize::max(start, self.offsets[i]);
+                let piece_end = usize::min(end, self.offsets[i + 1]);
+                if piece_start < piece_end {
+                    Some((
+                        i,
+                        piece_start - self.offsets[i],
+                        piece_end - self.offsets[i],
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn has_peak_train(phase: &P, channel: &str) -> Result<bool, SpikeError> {
+        Ok(phase.peak_train_labels()?.iter().any(|x| x == channel))
+    }
+}
+
+impl<P: PhaseHandler> PhaseHandler for ConcatPhase<P> {
+    fn sampling_frequency(&self) -> f32 {
+        self.phases[0].sampling_frequency()
+    }
+
+    fn datalen(&self) -> usize {
+        self.offsets[self.phases.len()]
+    }
+
+    fn labels(&self) -> Vec<String> {
+        self.phases[0].labels()
+    }
+
+    fn channel_info(&self, channel: &str) -> Result<ChannelInfo, SpikeError> {
+        self.phases[0].channel_info(channel)
+    }
+
+    fn recording_start(&self) -> Option<DateTime> {
+        self.phases[0].recording_start()
+    }
+
+    fn raw_data(
+        &self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        let (actual_start, actual_end) = check_range(start, end, self.datalen())?;
+        let mut data = Vec::with_capacity(actual_end - actual_start);
+        for (i, piece_start, piece_end) in self.pieces(actual_start, actual_end) {
+            data.extend(self.phases[i].raw_data(channel, Some(piece_start), Some(piece_end))?);
+        }
+        Ok(data)
+    }
+
+    fn set_raw_data(
+        &mut self,
+        channel: &str,
+        start: Option<usize>,
+        data: &[f32],
+    ) -> Result<(), SpikeError> {
+        let actual_start = start.unwrap_or(0);
+        let actual_end = actual_start + data.len();
+        if actual_end > self.datalen() {
+            return Err(SpikeError::SetRawDataOutOfBounds);
+        }
+        for (i, piece_start, piece_end) in self.pieces(actual_start, actual_end) {
+            let data_start = self.offsets[i] + piece_start - actual_start;
+            let data_end = self.offsets[i] + piece_end - actual_start;
+            self.phases[i].set_raw_data(channel, Some(piece_start), &data[data_start..data_end])?;
+        }
+        Ok(())
+    }
+
+    fn analog_streams(&self) -> Vec<String> {
+        self.phases[0].analog_streams()
+    }
+
+    fn raw_data_stream(&self) -> String {
+        self.phases[0].raw_data_stream()
+    }
+
+    fn set_raw_data_stream(&mut self, stream: &str) -> Result<(), SpikeError> {
+        for phase in self.phases.iter_mut() {
+            phase.set_raw_data_stream(stream)?;
+        }
+        Ok(())
+    }
+
+    fn analog_labels(&self, stream: &str) -> Result<Vec<String>, SpikeError> {
+        self.phases[0].analog_labels(stream)
+    }
+
+    /// Each phase contributes the same samples of the other streams as of
+    /// the raw data one, so they stay aligned across the boundaries only at
+    /// its sampling frequency
+    fn analog_data(
+        &self,
+        stream: &str,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        let (actual_start, actual_end) = check_range(start, end, self.datalen())?;
+        let mut data = Vec::with_capacity(actual_end - actual_start);
+        for (i, piece_start, piece_end) in self.pieces(actual_start, actual_end) {
+            data.extend(self.phases[i].analog_data(
+                stream,
+                channel,
+                Some(piece_start),
+                Some(piece_end),
+            )?);
+        }
+        Ok(data)
+    }
+
+    fn n_digitals(&self) -> usize {
+        self.phases[0].n_digitals()
+    }
+
+    fn digital_labels(&self) -> Vec<String> {
+        self.phases[0].digital_labels()
+    }
+
+    fn digital(
+        &self,
+        index: usize,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        if self.n_digitals() == 0 {
+            return Err(SpikeError::DigitalNoDigitalPresent);
+        }
+
+        if index >= self.n_digitals() {
+            return Err(SpikeError::IndexOutOfRange);
+        }
+
+        let actual_start = start.unwrap_or(0);
+        let actual_end = end.unwrap_or(self.datalen());
+
+        if actual_start >= actual_end {
+            return Err(SpikeError::DigitalStartIsAfterEnd);
+        }
+
+        if actual_end > self.datalen() {
+            return Err(SpikeError::DigitalOutOfBounds);
+        }
+
+        let mut data = Vec::with_capacity(actual_end - actual_start);
+        for (i, piece_start, piece_end) in self.pieces(actual_start, actual_end) {
+            data.extend(self.phases[i].digital(index, Some(piece_start), Some(piece_end))?);
+        }
+        Ok(data)
+    }
+
+    fn set_digital(
+        &mut self,
+        index: usize,
+        start: Option<usize>,
+        data: &[f32],
+    ) -> Result<(), SpikeError> {
+        let actual_start = start.unwrap_or(0);
+        let actual_end = actual_start + data.len();
+        if actual_end > self.datalen() {
+            return Err(SpikeError::SetDigitalOutOfBounds);
+        }
+        for (i, piece_start, piece_end) in self.pieces(actual_start, actual_end) {
+            let data_start = self.offsets[i] + piece_start - actual_start;
+            let data_end = self.offsets[i] + piece_end - actual_start;
+            self.phases[i].set_digital(index, Some(piece_start), &data[data_start..data_end])?;
+        }
+        Ok(())
+    }
+
+    fn n_events(&self) -> usize {
+        self.phases[0].n_events()
+    }
+
+    /// Returns the event entity `index` of every phase, joined. The entity
+    /// must have the same label and id in all the phases.
+    fn events(&self, index: usize) -> Result<EventEntity, SpikeError> {
+        let mut entity = self.phases[0].events(index)?;
+        for (i, phase) in self.phases.iter().enumerate().skip(1) {
+            let events = phase.events(index)?;
+            if events.label != entity.label || events.event_id != entity.event_id {
+                return Err(SpikeError::ConcatEventEntityMismatch(i));
+            }
+            let offset = self.offset_micros(i);
+            entity
+                .timestamps_us
+                .extend(events.timestamps_us.iter().map(|x| x + offset));
+            entity.durations_us.extend(events.durations_us);
+        }
+        Ok(entity)
+    }
+
+    fn peak_train(
+        &self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<(Vec<usize>, Vec<f32>), SpikeError> {
+        let start = start.unwrap_or(0);
+        let end = end.unwrap_or(usize::MAX);
+        if !self.peak_train_labels()?.iter().any(|x| x == channel) {
+            return Err(SpikeError::PeakTrainNotFound);
+        }
+        let mut samples = vec![];
+        let mut values = vec![];
+        for (i, piece_start, piece_end) in self.pieces(start, end) {
+            // a phase without the train counts as a phase without peaks
+            if !Self::has_peak_train(&self.phases[i], channel)? {
+                continue;
+            }
+            let offset = self.offsets[i];
+            let (phase_samples, phase_values) =
+                self.phases[i].peak_train(channel, Some(piece_start), Some(piece_end))?;
+            samples.extend(phase_samples.iter().map(|x| x + offset));
+            values.extend(phase_values);
+        }
+        Ok((samples, values))
+    }
+
+    fn set_peak_train(
+        &mut self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+        data: (Vec<usize>, Vec<f32>),
+    ) -> Result<(), SpikeError> {
+        if data.0.len() != data.1.len() {
+            return Err(SpikeError::SetPeakTrainSamplesValuesDifferentLen);
+        }
+
+        let start = start.unwrap_or(0);
+        let end = end.unwrap_or(self.datalen());
+        for (i, piece_start, piece_end) in self.pieces(start, end) {
+            let offset = self.offsets[i];
+            let i_start = data.0.partition_point(|x| *x < offset + piece_start);
+            let i_end = data
+                .0
+                .partition_point(|x| *x < offset + piece_end)
+                .max(i_start);
+            self.phases[i].set_peak_train(
+                channel,
+                Some(piece_start),
+                Some(piece_end),
+                (
+                    data.0[i_start..i_end].iter().map(|x| x - offset).collect(),
+                    data.1[i_start..i_end].to_vec(),
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn peak_train_labels(&self) -> Result<Vec<String>, SpikeError> {
+        let mut labels = vec![];
+        for phase in self.phases.iter() {
+            labels.extend(phase.peak_train_labels()?);
+        }
+        labels.sort();
+        labels.dedup();
+        Ok(labels)
+    }
+
+    fn delete_peak_train(&mut self, channel: &str) -> Result<(), SpikeError> {
+        let mut found = false;
+        for phase in self.phases.iter_mut() {
+            if Self::has_peak_train(phase, channel)? {
+                phase.delete_peak_train(channel)?;
+                found = true;
+            }
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(SpikeError::PeakTrainNotFound)
+        }
+    }
+
+    fn rename_peak_train(&mut self, channel: &str, new_channel: &str) -> Result<(), SpikeError> {
+        if self.peak_train_labels()?.iter().any(|x| x == new_channel) {
+            return Err(SpikeError::PeakTrainAlreadyExists);
+        }
+        let mut found = false;
+        for phase in self.phases.iter_mut() {
+            if Self::has_peak_train(phase, channel)? {
+                phase.rename_peak_train(channel, new_channel)?;
+                found = true;
+            }
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(SpikeError::PeakTrainNotFound)
+        }
+    }
+
+    /// Returns the metadata of the first phase with the detected range
+    /// extended to the end of the detection on the last phase
+    fn peak_train_metadata(&self, channel: &str) -> Result<Option<PeakTrainMetadata>, SpikeError> {
+        let mut found = false;
+        let mut metadata: Option<PeakTrainMetadata> = None;
+        for (i, phase) in self.phases.iter().enumerate() {
+            if !Self::has_peak_train(phase, channel)? {
+                continue;
+            }
+            found = true;
+            if let Some(phase_metadata) = phase.peak_train_metadata(channel)? {
+                let end = self.offsets[i] + phase_metadata.end;
+                metadata = match metadata {
+                    Some(metadata) => Some(PeakTrainMetadata { end, ..metadata }),
+                    None => Some(PeakTrainMetadata {
+                        start: self.offsets[i] + phase_metadata.start,
+                        end,
+                        ..phase_metadata
+                    }),
+                };
+            }
+        }
+        if found {
+            Ok(metadata)
+        } else {
+            Err(SpikeError::PeakTrainNotFound)
+        }
+    }
+
+    /// Store the metadata on each phase with the peak train of `channel`,
+    /// with the detected range clipped to the phase
+    fn set_peak_train_metadata(
+        &mut self,
+        channel: &str,
+        metadata: &PeakTrainMetadata,
+    ) -> Result<(), SpikeError> {
+        let mut found = false;
+        for (i, phase) in self.phases.iter_mut().enumerate() {
+            if !Self::has_peak_train(phase, channel)? {
+                continue;
+            }
+            found = true;
+            let offset = self.offsets[i];
+            let shift = |sample: usize| usize::min(sample.saturating_sub(offset), phase.datalen());
+            let phase_metadata = PeakTrainMetadata {
+                start: shift(metadata.start),
+                end: shift(metadata.end),
+                ..metadata.clone()
+            };
+            phase.set_peak_train_metadata(channel, &phase_metadata)?;
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(SpikeError::PeakTrainNotFound)
+        }
+    }
+
+    fn peak_run(&self) -> String {
+        self.phases[0].peak_run()
+    }
+
+    /// Returns the runs present in all the phases, in the order of the first
+    fn peak_runs(&self) -> Result<Vec<String>, SpikeError> {
+        let mut runs = self.phases[0].peak_runs()?;
+        for phase in self.phases[1..].iter() {
+            let phase_runs = phase.peak_runs()?;
+            runs.retain(|x| phase_runs.contains(x));
+        }
+        Ok(runs)
+    }
+
+    fn set_peak_run(&mut self, run: &str) -> Result<(), SpikeError> {
+        for phase in self.phases.iter_mut() {
+            phase.set_peak_run(run)?;
+        }
+        Ok(())
+    }
+
+    fn copy_peak_run(&mut self, run: &str, new_run: &str) -> Result<(), SpikeError> {
+        for phase in self.phases.iter_mut() {
+            phase.copy_peak_run(run, new_run)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        operations::tests::ramp_phase,
+        types::{MemoryPhase, DEFAULT_PEAK_RUN},
+    };
+
+    fn phase(datalen: usize, first: f32) -> MemoryPhase {
+        ramp_phase(datalen, first, vec![1_000], vec![100])
+    }
+
+    #[test]
+    fn concat_offsets_samples_and_events() {
+        let mut first = phase(10, 0.);
+        first
+            .set_peak_train("A1", None, None, (vec![2, 8], vec![1., 2.]))
+            .unwrap();
+        let mut second = phase(5, 10.);
+        second
+            .set_peak_train("A1", None, None, (vec![1], vec![3.]))
+            .unwrap();
+        let mut concat = ConcatPhase::new(vec![first, second]).unwrap();
+
+        assert_eq!(concat.datalen(), 15);
+        assert_eq!(concat.boundaries(), &[10]);
+        assert_eq!(concat.segment(9), Some(0));
+        assert_eq!(concat.segment(10), Some(1));
+        assert_eq!(concat.segment(15), None);
+        assert_eq!(
+            concat.raw_data("A1", Some(8), Some(12)).unwrap(),
+            vec![8., 9., 10., 11.]
+        );
+        // the digital runs up to the last sample of the first phase
+        assert_eq!(
+            concat.digital(0, Some(7), Some(12)).unwrap(),
+            vec![1., 0., 1., 0., 1.]
+        );
+        assert_eq!(concat.digital(0, None, None).unwrap().len(), 15);
+
+        let events = concat.events(0).unwrap();
+        assert_eq!(events.timestamps_us, vec![1_000, 11_000]);
+        assert_eq!(events.durations_us, vec![100, 100]);
+
+        assert_eq!(
+            concat.peak_train("A1", None, None).unwrap(),
+            (vec![2, 8, 11], vec![1., 2., 3.])
+        );
+        // no interval across the boundary
+        assert_eq!(
+            concat.peak_train_segments("A1").unwrap(),
+            vec![(vec![2, 8], vec![1., 2.]), (vec![11], vec![3.])]
+        );
+        assert_eq!(concat.inter_spike_intervals("A1").unwrap(), vec![6]);
+
+        concat
+            .set_peak_train("A1", Some(5), None, (vec![9, 12], vec![4., 5.]))
+            .unwrap();
+        assert_eq!(
+            concat.phases()[1].peak_train("A1", None, None).unwrap(),
+            (vec![2], vec![5.])
+        );
+        assert_eq!(
+            concat.peak_train("A1", None, None).unwrap(),
+            (vec![2, 9, 12], vec![1., 4., 5.])
+        );
+
+        concat.set_raw_data("A1", Some(9), &[0., 0.]).unwrap();
+        assert_eq!(
+            concat.raw_data("A1", Some(8), Some(12)).unwrap(),
+            vec![8., 0., 0., 11.]
+        );
+    }
+
+    #[test]
+    fn concat_missing_peak_trains_and_runs() {
+        let mut first = phase(10, 0.);
+        first
+            .set_peak_train("A1", None, None, (vec![2, 8], vec![1., 2.]))
+            .unwrap();
+        first.set_peak_run("other").unwrap();
+        first.set_peak_run("only_first").unwrap();
+        first.set_peak_run(DEFAULT_PEAK_RUN).unwrap();
+        let mut second = phase(5, 10.);
+        second.set_peak_run("other").unwrap();
+        second.set_peak_run(DEFAULT_PEAK_RUN).unwrap();
+        let concat = ConcatPhase::new(vec![first, second]).unwrap();
+
+        // the second phase has no train for A1, so no peaks after the boundary
+        assert_eq!(
+            concat.peak_train("A1", None, None).unwrap(),
+            (vec![2, 8], vec![1., 2.])
+        );
+        assert_eq!(
+            concat.peak_train_segments("A1").unwrap(),
+            vec![(vec![2, 8], vec![1., 2.]), (vec![], vec![])]
+        );
+        assert!(matches!(
+            concat.peak_train("B1", None, None),
+            Err(SpikeError::PeakTrainNotFound)
+        ));
+
+        assert_eq!(
+            concat.peak_runs().unwrap(),
+            vec![DEFAULT_PEAK_RUN.to_string(), "other".to_string()]
+        );
+    }
+
+    #[test]
+    fn concat_checks_phases() {
+        assert!(matches!(
+            ConcatPhase::<MemoryPhase>::new(vec![]),
+            Err(SpikeError::ConcatNoPhases)
+        ));
+        assert!(matches!(
+            ConcatPhase::new(vec![phase(10, 0.), MemoryPhase::new(2000f32, 10)]),
+            Err(SpikeError::ConcatSamplingFrequencyMismatch(1))
+        ));
+        assert!(matches!(
+            ConcatPhase::new(vec![phase(10, 0.), MemoryPhase::new(1000f32, 10)]),
+            Err(SpikeError::ConcatLabelsMismatch(1))
+        ));
+
+        let mut without_digital = MemoryPhase::new(1000f32, 10);
+        without_digital.add_raw_data("A1", vec![0f32; 10]).unwrap();
+        assert!(matches!(
+            ConcatPhase::new(vec![phase(10, 0.), without_digital.clone()]),
+            Err(SpikeError::ConcatDigitalsMismatch(1))
+        ));
+        without_digital.add_digital("D", vec![0f32; 10]).unwrap();
+        assert!(matches!(
+            ConcatPhase::new(vec![phase(10, 0.), without_digital.clone()]),
+            Err(SpikeError::ConcatEventsMismatch(1))
+        ));
+
+        let mut other_events = without_digital.clone();
+        other_events.add_events(EventEntity {
+            label: "other".to_string(),
+            ..Default::default()
+        });
+        let concat = ConcatPhase::new(vec![phase(10, 0.), other_events]).unwrap();
+        assert!(matches!(
+            concat.events(0),
+            Err(SpikeError::ConcatEventEntityMismatch(1))
+        ));
+    }
+}
@@ -0,0 +1,358 @@
+use crate::{
+    error::SpikeError,
+    time::DateTime,
+    types::{check_range, ChannelInfo, EventEntity, PeakTrainMetadata, PhaseHandler, RawBlock},
+};
+
+/// CroppedPhase
+///
+/// A read-only view of the `[start, end)` samples of a phase, shifted so that
+/// `start` is the new sample 0. Raw data, digital channels, events and peak
+/// trains are all moved to the new zero, while the recording start moves
+/// forward by the offset, if known. It can be exported to save the window as
+/// a new phase.
+pub struct CroppedPhase<'a, P: PhaseHandler> {
+    phase: &'a P,
+    start: usize,
+    end: usize,
+}
+
+impl<'a, P: PhaseHandler> CroppedPhase<'a, P> {
+    pub fn new(phase: &'a P, start: usize, end: usize) -> Result<Self, SpikeError> {
+        check_range(Some(start), Some(end), phase.datalen())?;
+        Ok(Self { phase, start, end })
+    }
+
+    /// Returns the sample of the original phase that is the sample 0 of the
+    /// cropped one
+    pub fn offset(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the offset in microseconds, the unit of the event timestamps
+    fn offset_micros(&self) -> i64 {
+        (self.phase.sample_to_seconds(self.start) * 1e6).round() as i64
+    }
+}
+
+impl<P: PhaseHandler> PhaseHandler for CroppedPhase<'_, P> {
+    fn sampling_frequency(&self) -> f32 {
+        self.phase.sampling_frequency()
+    }
+
+    fn datalen(&self) -> usize {
+        self.end - self.start
+    }
+
+    fn labels(&self) -> Vec<String> {
+        self.phase.labels()
+    }
+
+    fn channel_info(&self, channel: &str) -> Result<ChannelInfo, SpikeError> {
+        self.phase.channel_info(channel)
+    }
+
+    fn recording_start(&self) -> Option<DateTime> {
+        self.phase.sample_to_datetime(self.start)
+    }
+
+    fn raw_data(
+        &self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        let (actual_start, actual_end) = check_range(start, end, self.datalen())?;
+        self.phase.raw_data(
+            channel,
+            Some(self.start + actual_start),
+            Some(self.start + actual_end),
+        )
+    }
+
+    fn set_raw_data(
+        &mut self,
+        _channel: &str,
+        _start: Option<usize>,
+        _data: &[f32],
+    ) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn raw_block(
+        &self,
+        channels: &[String],
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<RawBlock, SpikeError> {
+        let (actual_start, actual_end) = check_range(start, end, self.datalen())?;
+        let (labels, data) = self
+            .phase
+            .raw_block(
+                channels,
+                Some(self.start + actual_start),
+                Some(self.start + actual_end),
+            )?
+            .into_parts();
+        RawBlock::new(labels, actual_start, actual_end - actual_start, data)
+    }
+
+    fn analog_streams(&self) -> Vec<String> {
+        self.phase.analog_streams()
+    }
+
+    fn raw_data_stream(&self) -> String {
+        self.phase.raw_data_stream()
+    }
+
+    fn set_raw_data_stream(&mut self, stream: &str) -> Result<(), SpikeError> {
+        if stream == self.phase.raw_data_stream() {
+            Ok(())
+        } else {
+            Err(SpikeError::PhaseIsReadOnly)
+        }
+    }
+
+    fn analog_labels(&self, stream: &str) -> Result<Vec<String>, SpikeError> {
+        self.phase.analog_labels(stream)
+    }
+
+    /// The window is given in samples of the raw data stream and applied
+    /// as is to the other streams: it covers the same time span only if
+    /// their sampling frequency is the same
+    fn analog_data(
+        &self,
+        stream: &str,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        let (actual_start, actual_end) = check_range(start, end, self.datalen())?;
+        self.phase.analog_data(
+            stream,
+            channel,
+            Some(self.start + actual_start),
+            Some(self.start + actual_end),
+        )
+    }
+
+    fn n_digitals(&self) -> usize {
+        self.phase.n_digitals()
+    }
+
+    fn digital_labels(&self) -> Vec<String> {
+        self.phase.digital_labels()
+    }
+
+    fn digital(
+        &self,
+        index: usize,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        if self.n_digitals() == 0 {
+            return Err(SpikeError::DigitalNoDigitalPresent);
+        }
+
+        if index >= self.n_digitals() {
+            return Err(SpikeError::IndexOutOfRange);
+        }
+
+        let actual_start = start.unwrap_or(0);
+        let actual_end = end.unwrap_or(self.datalen());
+
+        if actual_start >= actual_end {
+            return Err(SpikeError::DigitalStartIsAfterEnd);
+        }
+
+        if actual_end > self.datalen() {
+            return Err(SpikeError::DigitalOutOfBounds);
+        }
+
+        self.phase.digital(
+            index,
+            Some(self.start + actual_start),
+            Some(self.start + actual_end),
+        )
+    }
+
+    fn set_digital(
+        &mut self,
+        _index: usize,
+        _start: Option<usize>,
+        _data: &[f32],
+    ) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn n_events(&self) -> usize {
+        self.phase.n_events()
+    }
+
+    /// Returns the events of the selected entity that start in the window,
+    /// with the durations of the ones running past its end clipped to it
+    fn events(&self, index: usize) -> Result<EventEntity, SpikeError> {
+        let events = self.phase.events(index)?;
+        let offset = self.offset_micros();
+        let end = (self.phase.sample_to_seconds(self.end) * 1e6).round() as i64;
+
+        let (timestamps_us, durations_us) = events
+            .timestamps_us
+            .iter()
+            .zip(events.durations_us.iter())
+            .filter(|(timestamp, _)| (offset..end).contains(*timestamp))
+            .map(|(timestamp, duration)| (timestamp - offset, i64::min(*duration, end - timestamp)))
+            .unzip();
+        Ok(EventEntity {
+            timestamps_us,
+            durations_us,
+            ..events
+        })
+    }
+
+    fn peak_train(
+        &self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<(Vec<usize>, Vec<f32>), SpikeError> {
+        let start = usize::min(start.unwrap_or(0), self.datalen());
+        let end = usize::min(end.unwrap_or(self.datalen()), self.datalen());
+        let (samples, values) =
+            self.phase
+                .peak_train(channel, Some(self.start + start), Some(self.start + end))?;
+        Ok((samples.iter().map(|x| x - self.start).collect(), values))
+    }
+
+    fn set_peak_train(
+        &mut self,
+        _channel: &str,
+        _start: Option<usize>,
+        _end: Option<usize>,
+        _data: (Vec<usize>, Vec<f32>),
+    ) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn peak_train_labels(&self) -> Result<Vec<String>, SpikeError> {
+        self.phase.peak_train_labels()
+    }
+
+    fn delete_peak_train(&mut self, _channel: &str) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn rename_peak_train(&mut self, _channel: &str, _new_channel: &str) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    /// Returns the metadata of the peak train with the detected range
+    /// clipped to the window and shifted to the new zero
+    fn peak_train_metadata(&self, channel: &str) -> Result<Option<PeakTrainMetadata>, SpikeError> {
+        let shift = |sample: usize| usize::min(sample.saturating_sub(self.start), self.datalen());
+        Ok(self
+            .phase
+            .peak_train_metadata(channel)?
+            .map(|metadata| PeakTrainMetadata {
+                start: shift(metadata.start),
+                end: shift(metadata.end),
+                ..metadata
+            }))
+    }
+
+    fn set_peak_train_metadata(
+        &mut self,
+        _channel: &str,
+        _metadata: &PeakTrainMetadata,
+    ) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn peak_run(&self) -> String {
+        self.phase.peak_run()
+    }
+
+    fn peak_runs(&self) -> Result<Vec<String>, SpikeError> {
+        self.phase.peak_runs()
+    }
+
+    fn set_peak_run(&mut self, _run: &str) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn copy_peak_run(&mut self, _run: &str, _new_run: &str) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operations::tests::ramp_phase;
+
+    #[test]
+    fn crop_shifts_to_new_zero() {
+        let mut phase = ramp_phase(
+            100,
+            0.,
+            vec![5_000, 20_000, 55_000, 60_000],
+            vec![1, 2, 10_000, 3],
+        );
+        phase.set_recording_start(Some(DateTime::from_unix_micros(0)));
+        phase
+            .set_peak_train(
+                "A1",
+                None,
+                None,
+                (vec![5, 20, 50, 59], vec![1., 2., 3., 4.]),
+            )
+            .unwrap();
+        phase
+            .set_peak_train_metadata(
+                "A1",
+                &PeakTrainMetadata {
+                    start: 0,
+                    end: 100,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let cropped = CroppedPhase::new(&phase, 10, 60).unwrap();
+        assert_eq!(cropped.datalen(), 50);
+        assert_eq!(
+            cropped.recording_start(),
+            Some(DateTime::from_unix_micros(10_000))
+        );
+        assert_eq!(
+            cropped.raw_data("A1", Some(0), Some(3)).unwrap(),
+            vec![10., 11., 12.]
+        );
+        assert_eq!(
+            cropped
+                .raw_block(&["A1".to_string()], Some(48), None)
+                .unwrap()
+                .channel("A1"),
+            Some(&[58f32, 59.][..])
+        );
+        assert_eq!(
+            cropped.digital(0, Some(1), Some(4)).unwrap(),
+            vec![1., 0., 1.]
+        );
+        assert!(cropped.raw_data("A1", None, Some(51)).is_err());
+
+        let events = cropped.events(0).unwrap();
+        assert_eq!(events.timestamps_us, vec![10_000, 45_000]);
+        assert_eq!(events.durations_us, vec![2, 5_000]);
+
+        assert_eq!(
+            cropped.peak_train("A1", None, None).unwrap(),
+            (vec![10, 40, 49], vec![2., 3., 4.])
+        );
+        let metadata = cropped.peak_train_metadata("A1").unwrap().unwrap();
+        assert_eq!((metadata.start, metadata.end), (0, 50));
+
+        assert!(CroppedPhase::new(&phase, 60, 10).is_err());
+        assert!(CroppedPhase::new(&phase, 0, 101).is_err());
+    }
+}
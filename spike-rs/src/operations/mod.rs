@@ -1 +1,33 @@
+pub mod concat;
+pub mod crop;
 pub mod math;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::types::{EventEntity, MemoryPhase};
+
+    /// Returns a phase at 1 kHz with the ramp `first + sample` as the raw data
+    /// channel `A1`, a digital channel `D` toggling at each sample and a
+    /// `stim` event entity with the given events
+    pub(crate) fn ramp_phase(
+        datalen: usize,
+        first: f32,
+        timestamps_us: Vec<i64>,
+        durations_us: Vec<i64>,
+    ) -> MemoryPhase {
+        let mut phase = MemoryPhase::new(1000f32, datalen);
+        phase
+            .add_raw_data("A1", (0..datalen).map(|x| first + x as f32).collect())
+            .unwrap();
+        phase
+            .add_digital("D", (0..datalen).map(|x| (x % 2) as f32).collect())
+            .unwrap();
+        phase.add_events(EventEntity {
+            label: "stim".to_string(),
+            event_id: 0,
+            timestamps_us,
+            durations_us,
+        });
+        phase
+    }
+}
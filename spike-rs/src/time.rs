@@ -0,0 +1,289 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of .NET ticks (100 ns intervals since 0001-01-01T00:00:00) at the
+/// unix epoch, 1970-01-01T00:00:00
+const DOTNET_TICKS_AT_UNIX_EPOCH: i64 = 621_355_968_000_000_000;
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+/// DateTime
+///
+/// A point in time in the proleptic gregorian calendar, with microsecond
+/// resolution. No timezone is attached, the acquisition software saves the
+/// local time of the recording computer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub microsecond: u32,
+}
+
+impl DateTime {
+    /// Create a DateTime from the number of microseconds since the unix epoch
+    pub fn from_unix_micros(micros: i64) -> Self {
+        let days = micros.div_euclid(MICROS_PER_DAY);
+        let day_micros = micros.rem_euclid(MICROS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        let seconds = day_micros / MICROS_PER_SECOND;
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (seconds / 3600) as u32,
+            minute: (seconds % 3600 / 60) as u32,
+            second: (seconds % 60) as u32,
+            microsecond: (day_micros % MICROS_PER_SECOND) as u32,
+        }
+    }
+
+    /// Create a DateTime from .NET ticks, the format of the `DateInTicks`
+    /// attribute of Multi Channel Systems files
+    pub fn from_dotnet_ticks(ticks: i64) -> Self {
+        Self::from_unix_micros((ticks - DOTNET_TICKS_AT_UNIX_EPOCH).div_euclid(10))
+    }
+
+    /// Returns the .NET ticks of the DateTime, the inverse of
+    /// `from_dotnet_ticks`
+    pub fn dotnet_ticks(&self) -> i64 {
+        self.unix_micros() * 10 + DOTNET_TICKS_AT_UNIX_EPOCH
+    }
+
+    /// Returns the day of the week, 0 for sunday up to 6 for saturday
+    pub fn weekday(&self) -> u32 {
+        // 1970-01-01 was a thursday
+        (days_from_civil(self.year, self.month, self.day) + 4).rem_euclid(7) as u32
+    }
+
+    /// Returns the current UTC time of the system clock
+    pub fn now() -> Self {
+        let micros = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_micros() as i64,
+            Err(err) => -(err.duration().as_micros() as i64),
+        };
+        Self::from_unix_micros(micros)
+    }
+
+    /// Returns the number of microseconds since the unix epoch
+    pub fn unix_micros(&self) -> i64 {
+        let days = days_from_civil(self.year, self.month, self.day);
+        let seconds = self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        days * MICROS_PER_DAY + seconds * MICROS_PER_SECOND + self.microsecond as i64
+    }
+
+    /// Returns the DateTime shifted by `micros` microseconds
+    pub fn add_micros(&self, micros: i64) -> Self {
+        Self::from_unix_micros(self.unix_micros() + micros)
+    }
+
+    /// Returns the microseconds elapsed from `other` to `self`, negative if
+    /// `self` comes before `other`
+    pub fn micros_since(&self, other: &DateTime) -> i64 {
+        self.unix_micros() - other.unix_micros()
+    }
+}
+
+impl std::fmt::Display for DateTime {
+    /// ISO 8601 representation, e.g. `2024-03-05T10:22:11.000250`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.microsecond
+        )
+    }
+}
+
+/// Time
+///
+/// A time on the timeline of a recording, from its start, or a duration,
+/// either in samples or in seconds. Seconds are converted to the nearest
+/// sample, negative times to the first one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Time {
+    Samples(usize),
+    Seconds(f64),
+}
+
+impl Time {
+    pub fn millis(millis: f64) -> Self {
+        Self::Seconds(millis / 1e3)
+    }
+
+    /// Returns the number of samples at `sampling_frequency`
+    pub fn to_samples(&self, sampling_frequency: f32) -> usize {
+        match self {
+            Self::Samples(samples) => *samples,
+            Self::Seconds(seconds) => {
+                (seconds * sampling_frequency as f64).round().max(0f64) as usize
+            }
+        }
+    }
+}
+
+impl From<usize> for Time {
+    fn from(samples: usize) -> Self {
+        Self::Samples(samples)
+    }
+}
+
+/// Span
+///
+/// A `[start, end)` range of a recording. Both the bounds are optional and
+/// when omitted the start and the end of the whole data will be used, like the
+/// `start` and `end` arguments of the `PhaseHandler` accessors.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub start: Option<Time>,
+    pub end: Option<Time>,
+}
+
+impl Span {
+    pub fn new(start: Option<Time>, end: Option<Time>) -> Self {
+        Self { start, end }
+    }
+
+    /// The whole recording
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn samples(start: usize, end: usize) -> Self {
+        Self::new(Some(Time::Samples(start)), Some(Time::Samples(end)))
+    }
+
+    pub fn seconds(start: f64, end: f64) -> Self {
+        Self::new(Some(Time::Seconds(start)), Some(Time::Seconds(end)))
+    }
+
+    pub fn millis(start: f64, end: f64) -> Self {
+        Self::new(Some(Time::millis(start)), Some(Time::millis(end)))
+    }
+
+    /// Returns the `start` and `end` samples at `sampling_frequency`
+    pub fn to_samples(&self, sampling_frequency: f32) -> (Option<usize>, Option<usize>) {
+        (
+            self.start.map(|start| start.to_samples(sampling_frequency)),
+            self.end.map(|end| end.to_samples(sampling_frequency)),
+        )
+    }
+}
+
+/// Convert a number of days since 1970-01-01 to (year, month, day)
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as i32, month, day)
+}
+
+/// Convert a (year, month, day) date to the number of days since 1970-01-01
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_days_round_trip() {
+        let dates = [
+            ((1970, 1, 1), 0),
+            ((1969, 12, 31), -1),
+            ((2000, 2, 29), 11_016),
+            ((2024, 2, 29), 19_782),
+            ((1900, 3, 1), -25_508),
+            ((1600, 2, 29), -135_081),
+            ((1, 1, 1), -719_162),
+        ];
+        for ((year, month, day), days) in dates {
+            assert_eq!(days_from_civil(year, month, day), days);
+            assert_eq!(civil_from_days(days), (year, month, day));
+        }
+        // 1900 is not a leap year, 2000 is
+        assert_eq!(
+            civil_from_days(days_from_civil(1900, 2, 28) + 1),
+            (1900, 3, 1)
+        );
+        assert_eq!(
+            civil_from_days(days_from_civil(2000, 2, 28) + 1),
+            (2000, 2, 29)
+        );
+    }
+
+    #[test]
+    fn unix_micros_before_epoch() {
+        let date = DateTime::from_unix_micros(-1);
+        assert_eq!(date.to_string(), "1969-12-31T23:59:59.999999");
+        assert_eq!(date.unix_micros(), -1);
+        assert_eq!(date.weekday(), 3);
+        assert_eq!(DateTime::from_unix_micros(0).weekday(), 4);
+    }
+
+    #[test]
+    fn time_to_samples() {
+        assert_eq!(Time::Samples(7).to_samples(10000f32), 7);
+        assert_eq!(Time::from(7).to_samples(0f32), 7);
+        assert_eq!(Time::Seconds(0.5).to_samples(10000f32), 5000);
+        assert_eq!(Time::millis(2.5).to_samples(10000f32), 25);
+        // rounded to the nearest sample
+        assert_eq!(Time::Seconds(0.00014).to_samples(10000f32), 1);
+        assert_eq!(Time::Seconds(0.00016).to_samples(10000f32), 2);
+        assert_eq!(Time::Seconds(0.00004).to_samples(10000f32), 0);
+        // negative times are clamped to the first sample
+        assert_eq!(Time::Seconds(-1.).to_samples(10000f32), 0);
+        assert_eq!(Time::millis(-0.01).to_samples(10000f32), 0);
+    }
+
+    #[test]
+    fn span_to_samples() {
+        assert_eq!(Span::all().to_samples(10000f32), (None, None));
+        assert_eq!(Span::samples(3, 9).to_samples(1f32), (Some(3), Some(9)));
+        assert_eq!(
+            Span::seconds(0.1, 0.25).to_samples(20000f32),
+            (Some(2000), Some(5000))
+        );
+        assert_eq!(
+            Span::millis(-5., 1.26).to_samples(1000f32),
+            (Some(0), Some(1))
+        );
+        assert_eq!(
+            Span::new(None, Some(Time::Seconds(1.))).to_samples(100f32),
+            (None, Some(100))
+        );
+    }
+
+    #[test]
+    fn dotnet_ticks_round_trip() {
+        let date = DateTime::from_dotnet_ticks(638_452_309_310_000_000);
+        assert_eq!(date.to_string(), "2024-03-05T10:22:11.000000");
+        assert_eq!(date.dotnet_ticks(), 638_452_309_310_000_000);
+        assert_eq!(date.weekday(), 2);
+
+        let first = DateTime::from_dotnet_ticks(0);
+        assert_eq!(first.to_string(), "0001-01-01T00:00:00.000000");
+        assert_eq!(first.weekday(), 1);
+        assert_eq!(first.dotnet_ticks(), 0);
+
+        let leap = DateTime::from_unix_micros(days_from_civil(2024, 2, 29) * MICROS_PER_DAY);
+        assert_eq!(DateTime::from_dotnet_ticks(leap.dotnet_ticks()), leap);
+    }
+}
@@ -1,4 +1,251 @@
+use std::collections::HashMap;
+
 use super::error::SpikeError;
+use super::time::{DateTime, Span, Time};
+
+/// ChannelInfo
+///
+/// Metadata of a recorded channel, as stored by the acquisition hardware.
+/// `tick` is the sampling period in microseconds and a raw ADC value `x` is
+/// converted to physical units as
+/// `(x - ad_zero) * conversion_factor * 10^exponent`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChannelInfo {
+    pub label: String,
+    pub channel_id: i32,
+    pub row_index: i32,
+    pub group_id: i32,
+    pub electrode_group: i32,
+    pub raw_data_type: String,
+    pub unit: String,
+    pub exponent: i32,
+    pub ad_zero: i32,
+    pub tick: i64,
+    pub conversion_factor: i64,
+    pub adc_bits: i32,
+    pub high_pass_filter_type: String,
+    pub high_pass_filter_cutoff: String,
+    pub high_pass_filter_order: i32,
+    pub low_pass_filter_type: String,
+    pub low_pass_filter_cutoff: String,
+    pub low_pass_filter_order: i32,
+}
+
+impl ChannelInfo {
+    /// Returns the physical value of a step of the ADC,
+    /// `conversion_factor * 10^exponent`
+    pub fn adc_step(&self) -> f32 {
+        self.conversion_factor as f32 * f32::powf(10f32, self.exponent as f32)
+    }
+
+    /// Set `conversion_factor` and `exponent` so that their `adc_step` is
+    /// equal to `step`, with the largest exponent that keeps the conversion
+    /// factor an integer
+    pub fn set_adc_step(&mut self, step: f64) {
+        for exponent in (-18..=0).rev() {
+            let conversion_factor = step / 10f64.powi(exponent);
+            if conversion_factor.abs().round() >= 1f64
+                && (conversion_factor.round() - conversion_factor).abs()
+                    <= conversion_factor.abs() * 1e-6
+            {
+                self.conversion_factor = conversion_factor.round() as i64;
+                self.exponent = exponent;
+                return;
+            }
+        }
+        self.conversion_factor = (step * 1e18).round() as i64;
+        self.exponent = -18;
+    }
+
+    /// Convert ADC values to physical units
+    pub fn adc_to_physical(&self, data: &[i32]) -> Vec<f32> {
+        let step = self.adc_step();
+        data.iter()
+            .map(|x| (*x - self.ad_zero) as f32 * step)
+            .collect()
+    }
+
+    /// Convert physical values to ADC values, the inverse of
+    /// `adc_to_physical`. The values are rounded to the nearest step: the
+    /// `set_raw_data` of the HDF5 phases used to truncate them toward zero,
+    /// so data written back now differs by at most one step from before.
+    pub fn physical_to_adc(&self, data: &[f32]) -> Vec<i32> {
+        let step = self.adc_step();
+        data.iter()
+            .map(|x| (*x / step).round() as i32 + self.ad_zero)
+            .collect()
+    }
+}
+
+/// EventEntity
+///
+/// An event entity of an event stream, e.g. the triggers of a stimulator.
+/// `timestamps_us` and `durations_us` have the same length and are in
+/// microseconds, the timestamps from the start of the recording.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventEntity {
+    pub label: String,
+    pub event_id: i32,
+    pub timestamps_us: Vec<i64>,
+    pub durations_us: Vec<i64>,
+}
+
+/// Name of the detection run used when none is selected. On HDF5 files it is
+/// the legacy `Peak_Train` group.
+pub const DEFAULT_PEAK_RUN: &str = "default";
+
+/// Check that `run` can name a detection run. On HDF5 files it is the name of
+/// a group, so it can't be empty nor contain a `/`.
+fn check_peak_run_name(run: &str) -> Result<(), SpikeError> {
+    if run.is_empty() || run.contains('/') {
+        return Err(SpikeError::PeakRunInvalidName);
+    }
+    Ok(())
+}
+
+/// PeakTrainMetadata
+///
+/// Parameters of the spike detection that produced a peak train: the
+/// `threshold` (already multiplied by `multiplier`), the `peak_duration` and
+/// `refractory_time` in seconds, the detected `[start, end)` sample range, the
+/// version of the library and the time of the detection.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeakTrainMetadata {
+    pub threshold: f32,
+    pub multiplier: f32,
+    pub peak_duration: f32,
+    pub refractory_time: f32,
+    pub start: usize,
+    pub end: usize,
+    pub library_version: String,
+    pub created: DateTime,
+}
+
+/// RawBlock
+///
+/// Raw data of many channels on the same `[start, start + n_samples)` range,
+/// stored as a row-major channels x samples matrix. The rows follow the order
+/// of `labels`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RawBlock {
+    labels: Vec<String>,
+    labels_map: HashMap<String, usize>,
+    start: usize,
+    n_samples: usize,
+    data: Vec<f32>,
+}
+
+impl RawBlock {
+    /// Create a block from the rows of the channels `labels`, all of
+    /// `n_samples` samples, concatenated in `data`
+    pub fn new(
+        labels: Vec<String>,
+        start: usize,
+        n_samples: usize,
+        data: Vec<f32>,
+    ) -> Result<Self, SpikeError> {
+        if data.len() != labels.len() * n_samples {
+            return Err(SpikeError::RawBlockWrongDataLen(
+                data.len(),
+                labels.len() * n_samples,
+            ));
+        }
+        let labels_map = labels
+            .iter()
+            .enumerate()
+            .map(|(i, label)| (label.clone(), i))
+            .collect();
+        Ok(Self {
+            labels,
+            labels_map,
+            start,
+            n_samples,
+            data,
+        })
+    }
+
+    /// Returns the labels of the channels, in the order of the rows
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Returns the absolute sample of the first column
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn n_channels(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn n_samples(&self) -> usize {
+        self.n_samples
+    }
+
+    /// Returns the whole matrix, row-major
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    /// Returns the whole matrix, row-major, mutable
+    pub fn data_mut(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    /// Returns the row at `index`, if exists
+    pub fn row(&self, index: usize) -> Option<&[f32]> {
+        if index >= self.labels.len() {
+            return None;
+        }
+        Some(&self.data[index * self.n_samples..(index + 1) * self.n_samples])
+    }
+
+    /// Returns the row at `index`, mutable, if exists
+    pub fn row_mut(&mut self, index: usize) -> Option<&mut [f32]> {
+        if index >= self.labels.len() {
+            return None;
+        }
+        Some(&mut self.data[index * self.n_samples..(index + 1) * self.n_samples])
+    }
+
+    /// Returns the row of the channel `label`, if exists
+    pub fn channel(&self, label: &str) -> Option<&[f32]> {
+        self.row(*self.labels_map.get(label)?)
+    }
+
+    /// Returns the row of the channel `label`, mutable, if exists
+    pub fn channel_mut(&mut self, label: &str) -> Option<&mut [f32]> {
+        let index = *self.labels_map.get(label)?;
+        self.row_mut(index)
+    }
+
+    /// Returns an iterator over the rows
+    pub fn rows(&self) -> impl Iterator<Item = &[f32]> {
+        (0..self.labels.len()).map(|i| &self.data[i * self.n_samples..(i + 1) * self.n_samples])
+    }
+
+    /// Returns the mean of the channels for each sample, e.g. to be used as
+    /// common reference
+    pub fn mean_row(&self) -> Vec<f32> {
+        let mut mean = vec![0f32; self.n_samples];
+        if self.labels.is_empty() {
+            return mean;
+        }
+        for row in self.rows() {
+            for (m, x) in mean.iter_mut().zip(row) {
+                *m += x;
+            }
+        }
+        let n_channels = self.labels.len() as f32;
+        mean.iter_mut().for_each(|m| *m /= n_channels);
+        mean
+    }
+
+    /// Consume the block returning the labels and the row-major data
+    pub fn into_parts(self) -> (Vec<String>, Vec<f32>) {
+        (self.labels, self.data)
+    }
+}
 
 /// PhaseHandler
 ///
@@ -18,6 +265,58 @@ pub trait PhaseHandler {
     /// Returns the list of the labels associated to active channels
     fn labels(&self) -> Vec<String>;
 
+    /// Returns the metadata of the selected channel of the raw data stream,
+    /// if exists.
+    fn channel_info(&self, channel: &str) -> Result<ChannelInfo, SpikeError>;
+
+    /// Returns the date and time of the start of the recording, if known.
+    fn recording_start(&self) -> Option<DateTime>;
+
+    //--------------------------------------------------------------------------
+    // TIME CONVERSIONS
+
+    /// Returns the time in seconds of a sample from the start of the recording
+    fn sample_to_seconds(&self, sample: usize) -> f64 {
+        sample as f64 / self.sampling_frequency() as f64
+    }
+
+    /// Returns the nearest sample to a time in seconds from the start of the
+    /// recording. Negative times are clamped to the first sample.
+    fn seconds_to_sample(&self, seconds: f64) -> usize {
+        Time::Seconds(seconds).to_samples(self.sampling_frequency())
+    }
+
+    /// Returns the `start` and `end` samples of a span of the recording, to
+    /// be used with the accessors taking sample indices
+    fn span_to_samples(&self, span: &Span) -> (Option<usize>, Option<usize>) {
+        span.to_samples(self.sampling_frequency())
+    }
+
+    /// Returns the nearest sample to a time in microseconds from the start of
+    /// the recording, like the timestamps of the event streams
+    fn micros_to_sample(&self, micros: i64) -> usize {
+        self.seconds_to_sample(micros as f64 / 1e6)
+    }
+
+    /// Returns the absolute date and time of a sample, if the start of the
+    /// recording is known
+    fn sample_to_datetime(&self, sample: usize) -> Option<DateTime> {
+        let start = self.recording_start()?;
+        Some(start.add_micros((self.sample_to_seconds(sample) * 1e6).round() as i64))
+    }
+
+    /// Returns the nearest sample to an absolute date and time, if the start
+    /// of the recording is known and `datetime` is not before it. The sample
+    /// can be beyond `datalen` to place other recordings on the same timeline.
+    fn datetime_to_sample(&self, datetime: &DateTime) -> Option<usize> {
+        let micros = datetime.micros_since(&self.recording_start()?);
+        if micros < 0 {
+            None
+        } else {
+            Some(self.micros_to_sample(micros))
+        }
+    }
+
     //--------------------------------------------------------------------------
     // RAW DATA
 
@@ -31,6 +330,12 @@ pub trait PhaseHandler {
         end: Option<usize>,
     ) -> Result<Vec<f32>, SpikeError>;
 
+    /// Returns the raw data of the selected channel in `span`, see `raw_data`
+    fn raw_data_in(&self, channel: &str, span: &Span) -> Result<Vec<f32>, SpikeError> {
+        let (start, end) = self.span_to_samples(span);
+        self.raw_data(channel, start, end)
+    }
+
     /// Replace a slice of the raw data of the selected channel, if exists
     /// with the provided `data`.
     /// The `start` and `end` of the slice are optional and if omitted the
@@ -42,12 +347,106 @@ pub trait PhaseHandler {
         data: &[f32],
     ) -> Result<(), SpikeError>;
 
+    /// Returns the raw data of the selected channels on the same slice as a
+    /// channels x samples matrix, with the rows in the order of `channels`.
+    /// The `start` and `end` of the slice are optional and if omitted the
+    /// start and the end of the whole data will be used. Like `raw_data`, an
+    /// empty slice is an error, even with no channels.
+    fn raw_block(
+        &self,
+        channels: &[String],
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<RawBlock, SpikeError> {
+        let (actual_start, actual_end) = check_range(start, end, self.datalen())?;
+
+        let mut data = Vec::with_capacity(channels.len() * (actual_end - actual_start));
+        for channel in channels {
+            data.extend(self.raw_data(channel, Some(actual_start), Some(actual_end))?);
+        }
+        RawBlock::new(
+            channels.to_vec(),
+            actual_start,
+            actual_end - actual_start,
+            data,
+        )
+    }
+
+    /// Returns the raw data of the selected channels in `span`, see
+    /// `raw_block`
+    fn raw_block_in(&self, channels: &[String], span: &Span) -> Result<RawBlock, SpikeError> {
+        let (start, end) = self.span_to_samples(span);
+        self.raw_block(channels, start, end)
+    }
+
+    /// Returns an iterator over the raw data of the selected channel in
+    /// chunks of `chunk_len` samples, each one sharing `overlap` samples with
+    /// the previous one. See `RawDataChunks`.
+    fn raw_data_chunks(
+        &self,
+        channel: &str,
+        chunk_len: usize,
+        overlap: usize,
+    ) -> Result<RawDataChunks<'_, Self>, SpikeError>
+    where
+        Self: Sized,
+    {
+        if overlap >= chunk_len {
+            return Err(SpikeError::RawDataChunksOverlapTooLarge);
+        }
+        if !self.labels().iter().any(|label| label == channel) {
+            return Err(SpikeError::RawDataLabelNotFound);
+        }
+        Ok(RawDataChunks {
+            phase: self,
+            channel: channel.to_string(),
+            chunk_len,
+            step: chunk_len - overlap,
+            next_start: 0,
+            datalen: self.datalen(),
+        })
+    }
+
+    //--------------------------------------------------------------------------
+    // ANALOG STREAMS
+
+    /// Returns the labels of the analog streams stored in the recording,
+    /// digital streams excluded.
+    fn analog_streams(&self) -> Vec<String>;
+
+    /// Returns the label of the analog stream currently used as raw data.
+    fn raw_data_stream(&self) -> String;
+
+    /// Select the analog stream used as raw data. All the raw data methods,
+    /// `labels`, `datalen` and `sampling_frequency` will refer to it.
+    fn set_raw_data_stream(&mut self, stream: &str) -> Result<(), SpikeError>;
+
+    /// Returns the list of the labels of the channels of the selected analog
+    /// stream, if exists.
+    fn analog_labels(&self, stream: &str) -> Result<Vec<String>, SpikeError>;
+
+    /// Returns a slice of the data of a channel of the selected analog stream,
+    /// if exists, without changing the raw data stream.
+    /// The `start` and `end` of the slice are optional and if omitted the
+    /// start and the end of the whole data will be used.
+    fn analog_data(
+        &self,
+        stream: &str,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError>;
+
     //--------------------------------------------------------------------------
     // DIGITAL CHANNELS
 
     /// Returns the number of digital channels stored in the recording.
     fn n_digitals(&self) -> usize;
 
+    /// Returns the labels of the digital channels stored in the recording,
+    /// in the same order of their indices.
+    fn digital_labels(&self) -> Vec<String>;
+
     /// Returns a slice of the digital channel selected, if exists.
     /// The `start` and `end` of the slice are optional and if omitted the
     /// start and the end of the whole data will be used.
@@ -58,6 +457,12 @@ pub trait PhaseHandler {
         end: Option<usize>,
     ) -> Result<Vec<f32>, SpikeError>;
 
+    /// Returns the digital channel selected in `span`, see `digital`
+    fn digital_in(&self, index: usize, span: &Span) -> Result<Vec<f32>, SpikeError> {
+        let (start, end) = self.span_to_samples(span);
+        self.digital(index, start, end)
+    }
+
     /// Replace a slice of the digital channel selected, if exists, with
     /// the provided `data`.
     /// The `start` and `end` of the slice are optional and if omitted the
@@ -72,11 +477,43 @@ pub trait PhaseHandler {
     //--------------------------------------------------------------------------
     // EVENT CHANNELS
 
-    /// Returns the number of events arrays contained in the recording
+    /// Returns the number of event entities contained in the recording
     fn n_events(&self) -> usize;
 
-    /// Returns the selected events array, if exists.
-    fn events(&self, index: usize) -> Result<Vec<i64>, SpikeError>;
+    /// Returns the selected event entity, if exists.
+    fn events(&self, index: usize) -> Result<EventEntity, SpikeError>;
+
+    /// Returns the timestamps of the selected event entity converted to
+    /// samples of the raw data.
+    fn event_samples(&self, index: usize) -> Result<Vec<usize>, SpikeError> {
+        Ok(self
+            .events(index)?
+            .timestamps_us
+            .iter()
+            .map(|timestamp| self.micros_to_sample(*timestamp))
+            .collect())
+    }
+
+    /// Returns the (start, end) samples of the events of the selected event
+    /// entity, in the same format of the intervals of a digital channel.
+    /// Events with no duration last one sample, and the events that run past
+    /// the end of the data are clipped to the `datalen`.
+    fn event_intervals(&self, index: usize) -> Result<Vec<(usize, usize)>, SpikeError> {
+        let events = self.events(index)?;
+        Ok(events
+            .timestamps_us
+            .iter()
+            .zip(events.durations_us.iter())
+            .map(|(timestamp, duration)| {
+                let start = self.micros_to_sample(*timestamp);
+                let end = self
+                    .micros_to_sample(timestamp + duration)
+                    .min(self.datalen())
+                    .max(start + 1);
+                (start, end)
+            })
+            .collect())
+    }
 
     //--------------------------------------------------------------------------
     // PEAK TRAINS
@@ -92,13 +529,27 @@ pub trait PhaseHandler {
         end: Option<usize>,
     ) -> Result<(Vec<usize>, Vec<f32>), SpikeError>;
 
+    /// Returns the peak train of the selected channel in `span`, see
+    /// `peak_train`
+    fn peak_train_in(
+        &self,
+        channel: &str,
+        span: &Span,
+    ) -> Result<(Vec<usize>, Vec<f32>), SpikeError> {
+        let (start, end) = self.span_to_samples(span);
+        self.peak_train(channel, start, end)
+    }
+
     /// Replace a slice of the peak trains of the selected channel, if exists
     /// with the provided `data`.
     /// The `start` and `end` of the slice are optional and if omitted the
     /// start and the end of the whole data will be used.
     /// THE TRAIN IS SUPPOSED TO BE SORTED IN TIME AND THE PROVIDED `data`
-    /// MUST BE VALID IN THE REPLACED SLICE. PAY ATTENTION THAT THERE IS NO
-    /// CHECK ON THAT FOR NOW!!!
+    /// MUST BE VALID IN THE REPLACED SLICE. Only the lengths are checked here,
+    /// use `replace_peak_train_range`, `insert_peaks`, `remove_peaks_in` and
+    /// `remove_peaks_where` for validated edits.
+    /// Any write drops the metadata of the detection of the train, that no
+    /// longer describes it.
     fn set_peak_train(
         &mut self,
         channel: &str,
@@ -106,4 +557,1186 @@ pub trait PhaseHandler {
         end: Option<usize>,
         data: (Vec<usize>, Vec<f32>),
     ) -> Result<(), SpikeError>;
+
+    /// Replace the peaks of the selected channel in `[start, end)` with
+    /// `data`. The samples of `data` must be sorted, unique, inside the range
+    /// and the range inside the recording.
+    fn replace_peak_train_range(
+        &mut self,
+        channel: &str,
+        start: usize,
+        end: usize,
+        data: (Vec<usize>, Vec<f32>),
+    ) -> Result<(), SpikeError> {
+        if start >= end {
+            return Err(SpikeError::PeakTrainStartIsAfterEnd);
+        }
+        if end > self.datalen() {
+            return Err(SpikeError::PeakTrainOutOfBounds);
+        }
+        check_peaks(&data.0, &data.1, start, end)?;
+        self.set_peak_train(channel, Some(start), Some(end), data)
+    }
+
+    /// Add the peaks in `data` to the train of the selected channel, creating
+    /// it if not present. The samples of `data` must be sorted, inside the
+    /// recording and not already in the train.
+    fn insert_peaks(
+        &mut self,
+        channel: &str,
+        data: (Vec<usize>, Vec<f32>),
+    ) -> Result<(), SpikeError> {
+        check_peaks(&data.0, &data.1, 0, self.datalen())?;
+        let (samples, values) = self.peak_train(channel, None, None)?;
+
+        let mut new_samples = Vec::with_capacity(samples.len() + data.0.len());
+        let mut new_values = Vec::with_capacity(samples.len() + data.0.len());
+        let (mut i, mut j) = (0, 0);
+        while i < samples.len() || j < data.0.len() {
+            if j == data.0.len() || (i < samples.len() && samples[i] < data.0[j]) {
+                new_samples.push(samples[i]);
+                new_values.push(values[i]);
+                i += 1;
+            } else if i == samples.len() || data.0[j] < samples[i] {
+                new_samples.push(data.0[j]);
+                new_values.push(data.1[j]);
+                j += 1;
+            } else {
+                return Err(SpikeError::PeakTrainDuplicateSample(data.0[j]));
+            }
+        }
+
+        self.set_peak_train(channel, None, None, (new_samples, new_values))
+    }
+
+    /// Remove the peaks of the selected channel in `[start, end)`, returning
+    /// the number of removed peaks. The channel must have a peak train.
+    fn remove_peaks_in(
+        &mut self,
+        channel: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<usize, SpikeError> {
+        if start >= end {
+            return Err(SpikeError::PeakTrainStartIsAfterEnd);
+        }
+        if end > self.datalen() {
+            return Err(SpikeError::PeakTrainOutOfBounds);
+        }
+        if !self
+            .peak_train_labels()?
+            .iter()
+            .any(|label| label == channel)
+        {
+            return Err(SpikeError::PeakTrainNotFound);
+        }
+        let removed = self.peak_train(channel, Some(start), Some(end))?.0.len();
+        self.set_peak_train(channel, Some(start), Some(end), (vec![], vec![]))?;
+        Ok(removed)
+    }
+
+    /// Remove the peaks of the selected channel for which `predicate`, called
+    /// with the sample and the value of the peak, returns true. Returns the
+    /// number of removed peaks.
+    fn remove_peaks_where<F>(
+        &mut self,
+        channel: &str,
+        mut predicate: F,
+    ) -> Result<usize, SpikeError>
+    where
+        Self: Sized,
+        F: FnMut(usize, f32) -> bool,
+    {
+        let (samples, values) = self.peak_train(channel, None, None)?;
+        let (kept_samples, kept_values): (Vec<usize>, Vec<f32>) = samples
+            .iter()
+            .zip(values.iter())
+            .filter(|(sample, value)| !predicate(**sample, **value))
+            .unzip();
+        let removed = samples.len() - kept_samples.len();
+        if removed > 0 {
+            self.set_peak_train(channel, None, None, (kept_samples, kept_values))?;
+        }
+        Ok(removed)
+    }
+
+    /// Returns the labels of the channels with a stored peak train, sorted
+    /// alphabetically
+    fn peak_train_labels(&self) -> Result<Vec<String>, SpikeError>;
+
+    /// Delete the stored peak train of the selected channel
+    fn delete_peak_train(&mut self, channel: &str) -> Result<(), SpikeError>;
+
+    /// Store the peak train of `channel` under the `new_channel` label. Fails
+    /// if `new_channel` already has a peak train.
+    fn rename_peak_train(&mut self, channel: &str, new_channel: &str) -> Result<(), SpikeError>;
+
+    /// Returns the parameters of the detection that produced the peak train
+    /// of the selected channel, or None if the train was not computed by a
+    /// detection
+    fn peak_train_metadata(&self, channel: &str) -> Result<Option<PeakTrainMetadata>, SpikeError>;
+
+    /// Store the parameters of the detection that produced the peak train of
+    /// the selected channel. The peak train must already exist and the
+    /// metadata must be set after it, since writing a train drops them.
+    fn set_peak_train_metadata(
+        &mut self,
+        channel: &str,
+        metadata: &PeakTrainMetadata,
+    ) -> Result<(), SpikeError>;
+
+    /// Returns the name of the active detection run. All the peak train
+    /// methods read and write the peak trains of the active run.
+    fn peak_run(&self) -> String;
+
+    /// Returns the names of the stored detection runs
+    fn peak_runs(&self) -> Result<Vec<String>, SpikeError>;
+
+    /// Select the active detection run, creating it empty if not present.
+    /// A read-only phase can only select the runs already stored. The names
+    /// of the runs can't be empty nor contain a `/`.
+    fn set_peak_run(&mut self, run: &str) -> Result<(), SpikeError>;
+
+    /// Copy all the peak trains of `run` in `new_run`, replacing its content
+    /// if already present, e.g. to keep a good detection while trying another
+    /// one in the active run
+    fn copy_peak_run(&mut self, run: &str, new_run: &str) -> Result<(), SpikeError>;
+
+    /// Delete all the stored peak trains, e.g. before running again the
+    /// spike detection
+    fn clear_peak_trains(&mut self) -> Result<(), SpikeError> {
+        for label in self.peak_train_labels()? {
+            self.delete_peak_train(&label)?;
+        }
+        Ok(())
+    }
+}
+
+/// RawDataChunks
+///
+/// Iterator over the raw data of a channel, created by
+/// `PhaseHandler::raw_data_chunks`, to process long recordings in bounded
+/// memory. It yields the absolute start sample of each chunk and its converted
+/// data. A chunk starting at `start` covers `[start, start + chunk_len)` and
+/// the next one starts at `start + chunk_len - overlap`, so that the samples at
+/// the edges can be handled by both. The last chunk can be shorter and the
+/// iteration stops after the first error.
+pub struct RawDataChunks<'a, P: PhaseHandler> {
+    phase: &'a P,
+    channel: String,
+    chunk_len: usize,
+    step: usize,
+    next_start: usize,
+    datalen: usize,
+}
+
+impl<P: PhaseHandler> RawDataChunks<'_, P> {
+    /// Returns the start sample of the next chunk, or the `datalen` once the
+    /// iteration is over
+    pub fn next_start(&self) -> usize {
+        self.next_start
+    }
+
+    /// Resume the iteration from the chunk starting at `start`, a value
+    /// returned by `next_start`, e.g. to keep the position of an iterator
+    /// that can't hold a borrow of the phase
+    pub fn resume_at(mut self, start: usize) -> Self {
+        self.next_start = start;
+        self
+    }
+}
+
+impl<P: PhaseHandler> Iterator for RawDataChunks<'_, P> {
+    type Item = Result<(usize, Vec<f32>), SpikeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_start >= self.datalen {
+            return None;
+        }
+
+        let start = self.next_start;
+        let end = (start + self.chunk_len).min(self.datalen);
+        let data = self.phase.raw_data(&self.channel, Some(start), Some(end));
+
+        self.next_start = if end == self.datalen || data.is_err() {
+            self.datalen
+        } else {
+            start + self.step
+        };
+        Some(data.map(|data| (start, data)))
+    }
+}
+
+/// Check that the peaks have the same number of samples and values and that
+/// the samples are sorted, unique and in `[start, end)`
+fn check_peaks(
+    samples: &[usize],
+    values: &[f32],
+    start: usize,
+    end: usize,
+) -> Result<(), SpikeError> {
+    if samples.len() != values.len() {
+        return Err(SpikeError::SetPeakTrainSamplesValuesDifferentLen);
+    }
+    if samples.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(SpikeError::PeakTrainNotSorted);
+    }
+    match (samples.first(), samples.last()) {
+        (Some(first), Some(last)) if *first < start || *last >= end => {
+            Err(SpikeError::PeakTrainOutOfBounds)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Resolve the optional `start` and `end` of a range of raw data samples,
+/// defaulting to the whole recording of `datalen` samples, and check that
+/// the range is not empty and inside the recording
+pub fn check_range(
+    start: Option<usize>,
+    end: Option<usize>,
+    datalen: usize,
+) -> Result<(usize, usize), SpikeError> {
+    let actual_start = start.unwrap_or(0);
+    let actual_end = end.unwrap_or(datalen);
+
+    if actual_start >= actual_end {
+        return Err(SpikeError::RawDataStartIsAfterEnd);
+    }
+
+    if actual_end > datalen {
+        return Err(SpikeError::RawDataOutOfBounds);
+    }
+    Ok((actual_start, actual_end))
+}
+
+/// Like `check_range`, for a range of samples of a digital channel, with the
+/// errors of the digital channels
+pub fn check_digital_range(
+    start: Option<usize>,
+    end: Option<usize>,
+    datalen: usize,
+) -> Result<(usize, usize), SpikeError> {
+    check_range(start, end, datalen).map_err(|err| match err {
+        SpikeError::RawDataStartIsAfterEnd => SpikeError::DigitalStartIsAfterEnd,
+        SpikeError::RawDataOutOfBounds => SpikeError::DigitalOutOfBounds,
+        err => err,
+    })
+}
+
+/// Implement the peak train methods of `PhaseHandler` by forwarding them to
+/// the `MemoryPhase` in the field `$peaks`, for the read-only phases that
+/// keep the detected peaks in memory. To be used inside the
+/// `impl PhaseHandler` block.
+#[macro_export]
+macro_rules! delegate_peak_trains {
+    ($peaks:ident) => {
+        fn peak_train(
+            &self,
+            channel: &str,
+            start: Option<usize>,
+            end: Option<usize>,
+        ) -> Result<(Vec<usize>, Vec<f32>), $crate::error::SpikeError> {
+            self.$peaks.peak_train(channel, start, end)
+        }
+
+        fn set_peak_train(
+            &mut self,
+            channel: &str,
+            start: Option<usize>,
+            end: Option<usize>,
+            data: (Vec<usize>, Vec<f32>),
+        ) -> Result<(), $crate::error::SpikeError> {
+            self.$peaks.set_peak_train(channel, start, end, data)
+        }
+
+        fn peak_train_labels(&self) -> Result<Vec<String>, $crate::error::SpikeError> {
+            self.$peaks.peak_train_labels()
+        }
+
+        fn delete_peak_train(&mut self, channel: &str) -> Result<(), $crate::error::SpikeError> {
+            self.$peaks.delete_peak_train(channel)
+        }
+
+        fn rename_peak_train(
+            &mut self,
+            channel: &str,
+            new_channel: &str,
+        ) -> Result<(), $crate::error::SpikeError> {
+            self.$peaks.rename_peak_train(channel, new_channel)
+        }
+
+        fn peak_train_metadata(
+            &self,
+            channel: &str,
+        ) -> Result<Option<$crate::types::PeakTrainMetadata>, $crate::error::SpikeError> {
+            self.$peaks.peak_train_metadata(channel)
+        }
+
+        fn set_peak_train_metadata(
+            &mut self,
+            channel: &str,
+            metadata: &$crate::types::PeakTrainMetadata,
+        ) -> Result<(), $crate::error::SpikeError> {
+            self.$peaks.set_peak_train_metadata(channel, metadata)
+        }
+
+        fn peak_run(&self) -> String {
+            self.$peaks.peak_run()
+        }
+
+        fn peak_runs(&self) -> Result<Vec<String>, $crate::error::SpikeError> {
+            self.$peaks.peak_runs()
+        }
+
+        fn set_peak_run(&mut self, run: &str) -> Result<(), $crate::error::SpikeError> {
+            self.$peaks.set_peak_run(run)
+        }
+
+        fn copy_peak_run(
+            &mut self,
+            run: &str,
+            new_run: &str,
+        ) -> Result<(), $crate::error::SpikeError> {
+            self.$peaks.copy_peak_run(run, new_run)
+        }
+    };
+}
+
+/// MemoryPhase
+///
+/// In-memory implementer of `PhaseHandler`. It holds analog streams, digital
+/// streams, event arrays and peak trains in RAM, with the same bounds and
+/// errors of the HDF5 backed `Phase`, so that the analysis functions can be
+/// run on synthetic data or on data coming from other sources.
+/// All the analog streams share the same `datalen` and `sampling_frequency`,
+/// and list their channels in the order they were added.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryPhase {
+    sampling_frequency: f32,
+    datalen: usize,
+    recording_start: Option<DateTime>,
+    analogs: Vec<(String, AnalogChannels)>,
+    raw_data_index: usize,
+    channel_infos: HashMap<String, ChannelInfo>,
+    digitals: Vec<(String, Vec<f32>)>,
+    events: Vec<EventEntity>,
+    peak_trains: HashMap<String, (Vec<usize>, Vec<f32>)>,
+    peak_trains_metadata: HashMap<String, PeakTrainMetadata>,
+    peak_run: String,
+    // the inactive detection runs, with their peak trains and metadata
+    peak_runs: HashMap<String, PeakRun>,
+}
+
+/// Channels of an analog stream of a `MemoryPhase`, kept in insertion order
+#[derive(Debug, Clone, Default)]
+struct AnalogChannels {
+    channels: Vec<(String, Vec<f32>)>,
+    labels_map: HashMap<String, usize>,
+}
+
+impl AnalogChannels {
+    fn labels(&self) -> Vec<String> {
+        self.channels
+            .iter()
+            .map(|(label, _)| label.clone())
+            .collect()
+    }
+
+    fn get(&self, label: &str) -> Option<&Vec<f32>> {
+        let index = *self.labels_map.get(label)?;
+        Some(&self.channels[index].1)
+    }
+
+    fn get_mut(&mut self, label: &str) -> Option<&mut Vec<f32>> {
+        let index = *self.labels_map.get(label)?;
+        Some(&mut self.channels[index].1)
+    }
+
+    /// Add the channel `label`, replacing the data but keeping the position
+    /// of a channel already present
+    fn insert(&mut self, label: &str, data: Vec<f32>) {
+        match self.labels_map.get(label) {
+            Some(&index) => self.channels[index].1 = data,
+            None => {
+                self.labels_map
+                    .insert(label.to_string(), self.channels.len());
+                self.channels.push((label.to_string(), data));
+            }
+        }
+    }
+}
+
+type PeakRun = (
+    HashMap<String, (Vec<usize>, Vec<f32>)>,
+    HashMap<String, PeakTrainMetadata>,
+);
+
+impl MemoryPhase {
+    /// Label of the analog stream created by `MemoryPhase::new`
+    pub const DEFAULT_STREAM: &'static str = "Raw Data";
+
+    /// Create an empty phase of `datalen` samples recorded at
+    /// `sampling_frequency`, with an empty analog stream used as raw data
+    pub fn new(sampling_frequency: f32, datalen: usize) -> Self {
+        Self {
+            sampling_frequency,
+            datalen,
+            analogs: vec![(Self::DEFAULT_STREAM.to_string(), AnalogChannels::default())],
+            peak_run: DEFAULT_PEAK_RUN.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Add an empty analog stream labeled `label`, if not already present
+    pub fn add_analog_stream(&mut self, label: &str) {
+        if self.stream_index(label).is_none() {
+            self.analogs
+                .push((label.to_string(), AnalogChannels::default()));
+        }
+    }
+
+    /// Add a raw data channel labeled `label` to the current raw data stream,
+    /// replacing it if already present.
+    /// The length of `data` must be equal to the `datalen` of the phase.
+    pub fn add_raw_data(&mut self, label: &str, data: Vec<f32>) -> Result<(), SpikeError> {
+        let stream = self.raw_data_stream();
+        self.add_analog_data(&stream, label, data)
+    }
+
+    /// Add a channel labeled `label` to the analog stream `stream`, replacing
+    /// it if already present. The stream is created if missing.
+    /// The length of `data` must be equal to the `datalen` of the phase.
+    pub fn add_analog_data(
+        &mut self,
+        stream: &str,
+        label: &str,
+        data: Vec<f32>,
+    ) -> Result<(), SpikeError> {
+        if data.len() != self.datalen {
+            return Err(SpikeError::MemoryPhaseWrongDataLen(
+                data.len(),
+                self.datalen,
+            ));
+        }
+        self.add_analog_stream(stream);
+        let index = self.stream_index(stream).unwrap_or_default();
+        self.analogs[index].1.insert(label, data);
+        Ok(())
+    }
+
+    /// Set the date and time of the start of the recording
+    pub fn set_recording_start(&mut self, recording_start: Option<DateTime>) {
+        self.recording_start = recording_start;
+    }
+
+    /// Set the metadata returned by `channel_info` for the channel labeled
+    /// `info.label`. Channels without metadata get a default one.
+    pub fn set_channel_info(&mut self, info: ChannelInfo) {
+        self.channel_infos.insert(info.label.clone(), info);
+    }
+
+    /// Add a digital channel labeled `label`. The length of `data` must be
+    /// equal to the `datalen` of the phase.
+    pub fn add_digital(&mut self, label: &str, data: Vec<f32>) -> Result<(), SpikeError> {
+        if data.len() != self.datalen {
+            return Err(SpikeError::MemoryPhaseWrongDataLen(
+                data.len(),
+                self.datalen,
+            ));
+        }
+        self.digitals.push((label.to_string(), data));
+        Ok(())
+    }
+
+    /// Add an event entity
+    pub fn add_events(&mut self, events: EventEntity) {
+        self.events.push(events);
+    }
+
+    fn stream_index(&self, stream: &str) -> Option<usize> {
+        self.analogs.iter().position(|(label, _)| label == stream)
+    }
+}
+
+impl PhaseHandler for MemoryPhase {
+    fn sampling_frequency(&self) -> f32 {
+        self.sampling_frequency
+    }
+
+    fn datalen(&self) -> usize {
+        self.datalen
+    }
+
+    fn labels(&self) -> Vec<String> {
+        match self.analogs.get(self.raw_data_index) {
+            Some((_, channels)) => channels.labels(),
+            None => vec![],
+        }
+    }
+
+    fn recording_start(&self) -> Option<DateTime> {
+        self.recording_start
+    }
+
+    fn channel_info(&self, channel: &str) -> Result<ChannelInfo, SpikeError> {
+        if !self.labels().iter().any(|label| label == channel) {
+            return Err(SpikeError::RawDataLabelNotFound);
+        }
+
+        match self.channel_infos.get(channel) {
+            Some(info) => Ok(info.clone()),
+            None => Ok(ChannelInfo {
+                label: channel.to_string(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn raw_data(
+        &self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        let (actual_start, actual_end) = check_range(start, end, self.datalen)?;
+
+        match self
+            .analogs
+            .get(self.raw_data_index)
+            .and_then(|(_, channels)| channels.get(channel))
+        {
+            Some(data) => Ok(data[actual_start..actual_end].to_vec()),
+            None => Err(SpikeError::RawDataLabelNotFound),
+        }
+    }
+
+    fn set_raw_data(
+        &mut self,
+        channel: &str,
+        start: Option<usize>,
+        data: &[f32],
+    ) -> Result<(), SpikeError> {
+        let actual_start = start.unwrap_or(0);
+        let actual_end = actual_start + data.len();
+
+        if actual_end > self.datalen {
+            return Err(SpikeError::SetRawDataOutOfBounds);
+        }
+
+        match self
+            .analogs
+            .get_mut(self.raw_data_index)
+            .and_then(|(_, channels)| channels.get_mut(channel))
+        {
+            Some(channel_data) => {
+                channel_data[actual_start..actual_end].copy_from_slice(data);
+                Ok(())
+            }
+            None => Err(SpikeError::SetRawDataLabelNotFound),
+        }
+    }
+
+    fn analog_streams(&self) -> Vec<String> {
+        self.analogs
+            .iter()
+            .map(|(label, _)| label.clone())
+            .collect()
+    }
+
+    fn raw_data_stream(&self) -> String {
+        match self.analogs.get(self.raw_data_index) {
+            Some((label, _)) => label.clone(),
+            None => String::new(),
+        }
+    }
+
+    fn set_raw_data_stream(&mut self, stream: &str) -> Result<(), SpikeError> {
+        match self.stream_index(stream) {
+            Some(index) => {
+                self.raw_data_index = index;
+                Ok(())
+            }
+            None => Err(SpikeError::AnalogStreamNotFound),
+        }
+    }
+
+    fn analog_labels(&self, stream: &str) -> Result<Vec<String>, SpikeError> {
+        match self.stream_index(stream) {
+            Some(index) => Ok(self.analogs[index].1.labels()),
+            None => Err(SpikeError::AnalogStreamNotFound),
+        }
+    }
+
+    fn analog_data(
+        &self,
+        stream: &str,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        let index = match self.stream_index(stream) {
+            Some(index) => index,
+            None => return Err(SpikeError::AnalogStreamNotFound),
+        };
+
+        let (actual_start, actual_end) = check_range(start, end, self.datalen)?;
+
+        match self.analogs[index].1.get(channel) {
+            Some(data) => Ok(data[actual_start..actual_end].to_vec()),
+            None => Err(SpikeError::RawDataLabelNotFound),
+        }
+    }
+
+    fn n_digitals(&self) -> usize {
+        self.digitals.len()
+    }
+
+    fn digital_labels(&self) -> Vec<String> {
+        self.digitals
+            .iter()
+            .map(|(label, _)| label.clone())
+            .collect()
+    }
+
+    fn digital(
+        &self,
+        index: usize,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        if self.digitals.is_empty() {
+            return Err(SpikeError::DigitalNoDigitalPresent);
+        }
+
+        if index >= self.digitals.len() {
+            return Err(SpikeError::IndexOutOfRange);
+        }
+
+        let actual_start = start.unwrap_or(0);
+        let actual_end = end.unwrap_or(self.datalen);
+
+        if actual_start >= actual_end {
+            return Err(SpikeError::DigitalStartIsAfterEnd);
+        }
+
+        if actual_end > self.datalen {
+            return Err(SpikeError::DigitalOutOfBounds);
+        }
+
+        Ok(self.digitals[index].1[actual_start..actual_end].to_vec())
+    }
+
+    fn set_digital(
+        &mut self,
+        index: usize,
+        start: Option<usize>,
+        data: &[f32],
+    ) -> Result<(), SpikeError> {
+        if self.digitals.is_empty() {
+            return Err(SpikeError::DigitalNoDigitalPresent);
+        }
+
+        if index >= self.digitals.len() {
+            return Err(SpikeError::IndexOutOfRange);
+        }
+
+        let actual_start = start.unwrap_or(0);
+        let actual_end = actual_start + data.len();
+
+        if actual_end > self.datalen {
+            return Err(SpikeError::SetDigitalOutOfBounds);
+        }
+
+        if actual_start >= actual_end {
+            return Err(SpikeError::DigitalStartIsAfterEnd);
+        }
+
+        self.digitals[index].1[actual_start..actual_end].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn n_events(&self) -> usize {
+        self.events.len()
+    }
+
+    fn events(&self, index: usize) -> Result<EventEntity, SpikeError> {
+        match self.events.get(index) {
+            Some(events) => Ok(events.clone()),
+            None => Err(SpikeError::IndexOutOfRange),
+        }
+    }
+
+    fn peak_train(
+        &self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<(Vec<usize>, Vec<f32>), SpikeError> {
+        let (samples, values) = match self.peak_trains.get(channel) {
+            Some(peak_train) => peak_train,
+            None => return Ok((vec![], vec![])),
+        };
+
+        let start = start.unwrap_or(0);
+        let end = end.unwrap_or(usize::MAX);
+
+        // the train is sorted so the slice is found with two binary searches
+        let i_start = samples.partition_point(|x| *x < start);
+        let i_end = samples.partition_point(|x| *x < end).max(i_start);
+
+        Ok((
+            samples[i_start..i_end].to_vec(),
+            values[i_start..i_end].to_vec(),
+        ))
+    }
+
+    fn set_peak_train(
+        &mut self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+        data: (Vec<usize>, Vec<f32>),
+    ) -> Result<(), SpikeError> {
+        if data.0.len() != data.1.len() {
+            return Err(SpikeError::SetPeakTrainSamplesValuesDifferentLen);
+        }
+
+        let (samples, values) = self.peak_trains.remove(channel).unwrap_or_default();
+        self.peak_trains_metadata.remove(channel);
+
+        let start = start.unwrap_or(0);
+        let end = end.unwrap_or(usize::MAX);
+        let i_start = samples.partition_point(|x| *x < start);
+        let i_end = samples.partition_point(|x| *x < end).max(i_start);
+
+        // keep the peaks outside of [start, end) and put the new ones between
+        let mut new_samples = samples[..i_start].to_vec();
+        new_samples.extend_from_slice(&data.0);
+        new_samples.extend_from_slice(&samples[i_end..]);
+
+        let mut new_values = values[..i_start].to_vec();
+        new_values.extend_from_slice(&data.1);
+        new_values.extend_from_slice(&values[i_end..]);
+
+        self.peak_trains
+            .insert(channel.to_string(), (new_samples, new_values));
+        Ok(())
+    }
+
+    fn peak_train_labels(&self) -> Result<Vec<String>, SpikeError> {
+        let mut labels: Vec<String> = self.peak_trains.keys().cloned().collect();
+        labels.sort();
+        Ok(labels)
+    }
+
+    fn delete_peak_train(&mut self, channel: &str) -> Result<(), SpikeError> {
+        self.peak_trains_metadata.remove(channel);
+        match self.peak_trains.remove(channel) {
+            Some(_) => Ok(()),
+            None => Err(SpikeError::PeakTrainNotFound),
+        }
+    }
+
+    fn rename_peak_train(&mut self, channel: &str, new_channel: &str) -> Result<(), SpikeError> {
+        if self.peak_trains.contains_key(new_channel) {
+            return Err(SpikeError::PeakTrainAlreadyExists);
+        }
+        match self.peak_trains.remove(channel) {
+            Some(peak_train) => {
+                self.peak_trains.insert(new_channel.to_string(), peak_train);
+                if let Some(metadata) = self.peak_trains_metadata.remove(channel) {
+                    self.peak_trains_metadata
+                        .insert(new_channel.to_string(), metadata);
+                }
+                Ok(())
+            }
+            None => Err(SpikeError::PeakTrainNotFound),
+        }
+    }
+
+    fn peak_train_metadata(&self, channel: &str) -> Result<Option<PeakTrainMetadata>, SpikeError> {
+        if !self.peak_trains.contains_key(channel) {
+            return Err(SpikeError::PeakTrainNotFound);
+        }
+        Ok(self.peak_trains_metadata.get(channel).cloned())
+    }
+
+    fn set_peak_train_metadata(
+        &mut self,
+        channel: &str,
+        metadata: &PeakTrainMetadata,
+    ) -> Result<(), SpikeError> {
+        if !self.peak_trains.contains_key(channel) {
+            return Err(SpikeError::PeakTrainNotFound);
+        }
+        self.peak_trains_metadata
+            .insert(channel.to_string(), metadata.clone());
+        Ok(())
+    }
+
+    fn peak_run(&self) -> String {
+        self.peak_run.clone()
+    }
+
+    fn peak_runs(&self) -> Result<Vec<String>, SpikeError> {
+        let mut runs: Vec<String> = self.peak_runs.keys().cloned().collect();
+        runs.push(self.peak_run.clone());
+        runs.sort_by_key(|run| (run != DEFAULT_PEAK_RUN, run.clone()));
+        Ok(runs)
+    }
+
+    fn set_peak_run(&mut self, run: &str) -> Result<(), SpikeError> {
+        check_peak_run_name(run)?;
+        if run == self.peak_run {
+            return Ok(());
+        }
+        let (peak_trains, peak_trains_metadata) = self.peak_runs.remove(run).unwrap_or_default();
+        let old_run = (
+            std::mem::replace(&mut self.peak_trains, peak_trains),
+            std::mem::replace(&mut self.peak_trains_metadata, peak_trains_metadata),
+        );
+        self.peak_runs.insert(self.peak_run.clone(), old_run);
+        self.peak_run = run.to_string();
+        Ok(())
+    }
+
+    fn copy_peak_run(&mut self, run: &str, new_run: &str) -> Result<(), SpikeError> {
+        check_peak_run_name(new_run)?;
+        let copy = if run == self.peak_run {
+            (self.peak_trains.clone(), self.peak_trains_metadata.clone())
+        } else {
+            match self.peak_runs.get(run) {
+                Some(peak_run) => peak_run.clone(),
+                None => return Err(SpikeError::PeakRunNotFound),
+            }
+        };
+
+        if new_run == self.peak_run {
+            (self.peak_trains, self.peak_trains_metadata) = copy;
+        } else {
+            self.peak_runs.insert(new_run.to_string(), copy);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn physical_to_adc_rounds_to_nearest_step() {
+        let info = ChannelInfo {
+            ad_zero: 100,
+            conversion_factor: 1,
+            exponent: -6,
+            ..Default::default()
+        };
+        assert_eq!(
+            info.physical_to_adc(&[2.6e-6, -2.6e-6, 0.4e-6]),
+            vec![103, 97, 100]
+        );
+        assert_eq!(info.adc_to_physical(&[103]), vec![3e-6]);
+    }
+
+    fn phase_with_peaks() -> MemoryPhase {
+        let mut phase = MemoryPhase::new(10000f32, 1000);
+        phase
+            .set_peak_train(
+                "A1",
+                None,
+                None,
+                (vec![10, 20, 30, 40], vec![1., 2., 3., 4.]),
+            )
+            .unwrap();
+        phase
+    }
+
+    #[test]
+    fn replace_peak_train_range_round_trip() {
+        let mut phase = phase_with_peaks();
+        phase
+            .replace_peak_train_range("A1", 15, 35, (vec![16, 25], vec![6., 5.]))
+            .unwrap();
+        assert_eq!(
+            phase.peak_train("A1", None, None).unwrap(),
+            (vec![10, 16, 25, 40], vec![1., 6., 5., 4.])
+        );
+    }
+
+    #[test]
+    fn replace_peak_train_range_checks() {
+        let mut phase = phase_with_peaks();
+        assert!(matches!(
+            phase.replace_peak_train_range("A1", 15, 35, (vec![25, 16], vec![1., 2.])),
+            Err(SpikeError::PeakTrainNotSorted)
+        ));
+        assert!(matches!(
+            phase.replace_peak_train_range("A1", 15, 35, (vec![16, 35], vec![1., 2.])),
+            Err(SpikeError::PeakTrainOutOfBounds)
+        ));
+        assert!(matches!(
+            phase.replace_peak_train_range("A1", 15, 1001, (vec![16], vec![1.])),
+            Err(SpikeError::PeakTrainOutOfBounds)
+        ));
+        assert!(matches!(
+            phase.replace_peak_train_range("A1", 15, 35, (vec![16], vec![1., 2.])),
+            Err(SpikeError::SetPeakTrainSamplesValuesDifferentLen)
+        ));
+        // failed edits leave the train untouched
+        assert_eq!(
+            phase.peak_train("A1", None, None).unwrap().0,
+            vec![10, 20, 30, 40]
+        );
+    }
+
+    #[test]
+    fn insert_peaks_round_trip() {
+        let mut phase = phase_with_peaks();
+        phase
+            .insert_peaks("A1", (vec![0, 25, 999], vec![0., 2.5, 9.]))
+            .unwrap();
+        assert_eq!(
+            phase.peak_train("A1", None, None).unwrap(),
+            (
+                vec![0, 10, 20, 25, 30, 40, 999],
+                vec![0., 1., 2., 2.5, 3., 4., 9.]
+            )
+        );
+
+        assert!(matches!(
+            phase.insert_peaks("A1", (vec![30], vec![0.])),
+            Err(SpikeError::PeakTrainDuplicateSample(30))
+        ));
+        assert!(matches!(
+            phase.insert_peaks("A1", (vec![1000], vec![0.])),
+            Err(SpikeError::PeakTrainOutOfBounds)
+        ));
+
+        phase.insert_peaks("B1", (vec![5], vec![1.])).unwrap();
+        assert_eq!(
+            phase.peak_train("B1", None, None).unwrap(),
+            (vec![5], vec![1.])
+        );
+    }
+
+    #[test]
+    fn remove_peaks_round_trip() {
+        let mut phase = phase_with_peaks();
+        assert_eq!(phase.remove_peaks_in("A1", 20, 40).unwrap(), 2);
+        assert_eq!(
+            phase.peak_train("A1", None, None).unwrap(),
+            (vec![10, 40], vec![1., 4.])
+        );
+
+        assert!(matches!(
+            phase.remove_peaks_in("B1", 20, 40),
+            Err(SpikeError::PeakTrainNotFound)
+        ));
+        assert_eq!(phase.peak_train_labels().unwrap(), vec!["A1".to_string()]);
+
+        let mut phase = phase_with_peaks();
+        assert_eq!(
+            phase
+                .remove_peaks_where("A1", |_, value| value > 2.5)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            phase.peak_train("A1", None, None).unwrap(),
+            (vec![10, 20], vec![1., 2.])
+        );
+    }
+
+    #[test]
+    fn raw_data_chunks_partial_last_chunk() {
+        let mut phase = MemoryPhase::new(1000f32, 10);
+        phase
+            .add_raw_data("A1", (0..10).map(|x| x as f32).collect())
+            .unwrap();
+        phase
+            .add_digital("D", (0..10).map(|x| (x % 2) as f32).collect())
+            .unwrap();
+
+        let chunks: Vec<(usize, Vec<f32>)> = phase
+            .raw_data_chunks("A1", 4, 1)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            chunks,
+            vec![
+                (0, vec![0., 1., 2., 3.]),
+                (3, vec![3., 4., 5., 6.]),
+                (6, vec![6., 7., 8., 9.]),
+            ]
+        );
+        let chunks: Vec<usize> = phase
+            .raw_data_chunks("A1", 4, 0)
+            .unwrap()
+            .map(|chunk| chunk.unwrap().1.len())
+            .collect();
+        assert_eq!(chunks, vec![4, 4, 2]);
+
+        // the iteration can be resumed from the start of its next chunk
+        let mut chunks = phase.raw_data_chunks("A1", 4, 1).unwrap();
+        chunks.next();
+        assert_eq!(chunks.next_start(), 3);
+        let starts: Vec<usize> = phase
+            .raw_data_chunks("A1", 4, 1)
+            .unwrap()
+            .resume_at(chunks.next_start())
+            .map(|chunk| chunk.unwrap().0)
+            .collect();
+        assert_eq!(starts, vec![3, 6]);
+
+        // `end` is excluded, so the last sample is read with end == datalen
+        assert_eq!(
+            phase.raw_data("A1", Some(8), Some(10)).unwrap(),
+            vec![8., 9.]
+        );
+        assert_eq!(phase.digital(0, Some(8), Some(10)).unwrap(), vec![0., 1.]);
+        assert_eq!(phase.digital(0, None, None).unwrap().len(), 10);
+        assert!(matches!(
+            phase.digital(0, Some(8), Some(11)),
+            Err(SpikeError::DigitalOutOfBounds)
+        ));
+        phase.set_digital(0, Some(9), &[0.]).unwrap();
+        assert_eq!(phase.digital(0, Some(9), None).unwrap(), vec![0.]);
+        assert!(matches!(
+            phase.set_digital(0, Some(9), &[0., 0.]),
+            Err(SpikeError::SetDigitalOutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn raw_block_rejects_empty_range() {
+        let mut phase = MemoryPhase::new(1000f32, 10);
+        phase.add_raw_data("A1", vec![0f32; 10]).unwrap();
+        let channels = vec!["A1".to_string()];
+
+        assert_eq!(
+            phase
+                .raw_block(&channels, Some(9), None)
+                .unwrap()
+                .channel("A1"),
+            Some(&[0f32][..])
+        );
+        assert!(matches!(
+            phase.raw_block(&channels, Some(5), Some(5)),
+            Err(SpikeError::RawDataStartIsAfterEnd)
+        ));
+        assert!(matches!(
+            phase.raw_block(&[], Some(5), Some(5)),
+            Err(SpikeError::RawDataStartIsAfterEnd)
+        ));
+        assert!(matches!(
+            phase.raw_block(&[], None, Some(11)),
+            Err(SpikeError::RawDataOutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn peak_train_edits_drop_metadata() {
+        let mut phase = phase_with_peaks();
+        let metadata = PeakTrainMetadata {
+            threshold: 1.,
+            ..Default::default()
+        };
+        phase.set_peak_train_metadata("A1", &metadata).unwrap();
+
+        // no peak removed, so nothing is written
+        assert_eq!(phase.remove_peaks_where("A1", |_, _| false).unwrap(), 0);
+        assert_eq!(phase.peak_train_metadata("A1").unwrap(), Some(metadata));
+
+        phase.insert_peaks("A1", (vec![50], vec![5.])).unwrap();
+        assert_eq!(phase.peak_train_metadata("A1").unwrap(), None);
+    }
+
+    #[test]
+    fn labels_keep_insertion_order() {
+        let mut phase = MemoryPhase::new(1000f32, 2);
+        let labels = ["D4", "A1", "C12", "B2"];
+        for label in labels {
+            phase.add_raw_data(label, vec![0.; 2]).unwrap();
+        }
+        // replacing a channel keeps its position
+        phase.add_raw_data("A1", vec![1.; 2]).unwrap();
+        phase.add_analog_data("Stream", "Z", vec![0.; 2]).unwrap();
+        phase.add_analog_data("Stream", "Y", vec![0.; 2]).unwrap();
+
+        assert_eq!(phase.labels(), labels);
+        assert_eq!(phase.raw_data("A1", None, None).unwrap(), vec![1.; 2]);
+        assert_eq!(phase.analog_labels("Stream").unwrap(), ["Z", "Y"]);
+        phase.set_raw_data_stream("Stream").unwrap();
+        assert_eq!(phase.labels(), ["Z", "Y"]);
+    }
+
+    #[test]
+    fn event_samples_and_intervals() {
+        let mut phase = MemoryPhase::new(10000f32, 2000);
+        phase.add_events(EventEntity {
+            label: "Stimulus".to_string(),
+            event_id: 3,
+            timestamps_us: vec![1_000, 50_000, 199_900],
+            durations_us: vec![100, 0, 1_000],
+        });
+
+        assert_eq!(phase.event_samples(0).unwrap(), vec![10, 500, 1999]);
+        // the last event runs past the end of the data
+        assert_eq!(
+            phase.event_intervals(0).unwrap(),
+            vec![(10, 11), (500, 501), (1999, 2000)]
+        );
+        assert!(phase.event_samples(1).is_err());
+    }
+
+    #[test]
+    fn peak_runs_round_trip() {
+        let mut phase = phase_with_peaks();
+        let train = phase.peak_train("A1", None, None).unwrap();
+        assert_eq!(phase.peak_run(), DEFAULT_PEAK_RUN);
+
+        phase.set_peak_run("run 2").unwrap();
+        assert_eq!(phase.peak_run(), "run 2");
+        assert!(phase.peak_train_labels().unwrap().is_empty());
+        phase
+            .set_peak_train("A1", None, None, (vec![5], vec![3.]))
+            .unwrap();
+        assert_eq!(phase.peak_runs().unwrap(), [DEFAULT_PEAK_RUN, "run 2"]);
+
+        phase.copy_peak_run(DEFAULT_PEAK_RUN, "copy").unwrap();
+        assert_eq!(
+            phase.peak_runs().unwrap(),
+            [DEFAULT_PEAK_RUN, "copy", "run 2"]
+        );
+        phase.set_peak_run("copy").unwrap();
+        assert_eq!(phase.peak_train("A1", None, None).unwrap(), train);
+        // copying on the active run replaces its peak trains
+        phase.copy_peak_run("run 2", "copy").unwrap();
+        assert_eq!(
+            phase.peak_train("A1", None, None).unwrap(),
+            (vec![5], vec![3.])
+        );
+        phase.set_peak_run(DEFAULT_PEAK_RUN).unwrap();
+        assert_eq!(phase.peak_train("A1", None, None).unwrap(), train);
+
+        for name in ["", "a/b"] {
+            assert!(matches!(
+                phase.set_peak_run(name),
+                Err(SpikeError::PeakRunInvalidName)
+            ));
+            assert!(matches!(
+                phase.copy_peak_run(DEFAULT_PEAK_RUN, name),
+                Err(SpikeError::PeakRunInvalidName)
+            ));
+        }
+        assert!(matches!(
+            phase.copy_peak_run("missing", "other"),
+            Err(SpikeError::PeakRunNotFound)
+        ));
+        assert_eq!(phase.peak_run(), DEFAULT_PEAK_RUN);
+        assert_eq!(phase.peak_runs().unwrap().len(), 3);
+    }
 }
@@ -0,0 +1,632 @@
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr};
+
+use spike_rs::{
+    error::SpikeError,
+    io::json::parse_json,
+    time::DateTime,
+    types::{check_range, ChannelInfo, EventEntity, MemoryPhase, PhaseHandler, RawBlock},
+};
+
+use crate::{
+    h5::{c, H5Type},
+    sys, Error,
+};
+
+/// Well of the single well arrays
+pub const DEFAULT_WELL: &str = "Well_A1";
+
+/// Number of rows and columns of the grid of the electrodes of the HD-MEAs
+const GRID_SIDE: i32 = 64;
+
+const V3_RAW_PATH: &str = "/3BData/Raw";
+const V3_VARIABLES_PATH: &str = "/3BRecInfo/3BRecVars";
+const V3_CHANNELS_PATH: &str = "/3BRecInfo/3BMeaStreams/Raw/Chs";
+const V4_TOC_PATH: &str = "/TOC";
+
+fn invalid(reason: &str) -> SpikeError {
+    Error::BrwInvalid(reason.to_string()).into()
+}
+
+/// Returns the label of the electrode at `row` and `column` of the grid,
+/// starting from 1, e.g. `12_40`
+pub fn brw_label(row: i32, column: i32) -> String {
+    format!("{row}_{column}")
+}
+
+/// Returns the hyperslab of the samples in `start..end` of the channel at
+/// `column` in a dataset of frames of `n_channels` interleaved samples: the
+/// index of the first element, the stride and the count. Only the samples
+/// of the channel are selected.
+pub(crate) fn column_hyperslab(
+    column: usize,
+    n_channels: usize,
+    start: usize,
+    end: usize,
+) -> (usize, usize, usize) {
+    (start * n_channels + column, n_channels, end - start)
+}
+
+/// Layout of the samples in the file
+enum Storage {
+    /// Version 3: the samples of all the channels interleaved, frame by frame
+    V3Raw,
+    /// Version 4: the samples of all the channels of a well interleaved,
+    /// frame by frame
+    Raw { path: String },
+    /// Version 4, compressed: only the samples around the detected events of
+    /// each channel, in chunks of frames. `offsets` are the start of each
+    /// chunk in the data, `frames` its range of frames.
+    EventsBasedSparse {
+        path: String,
+        offsets: Vec<usize>,
+        frames: Vec<(usize, usize)>,
+        data_len: usize,
+    },
+}
+
+/// BrwPhase
+///
+/// A phase read from a recording of 3Brain BrainWave, the `.brw` HDF5 files
+/// of the high density MEAs, in both the version 3 and 4 layouts. Each
+/// channel is labeled with the row and column of its electrode on the grid,
+/// as in `brw_label`.
+/// The digital values are converted to volts with the ranges stored in the
+/// file. The compressed recordings store only the samples around the events
+/// detected by BrainWave: the others are read as 0.
+///
+/// The file is opened read only: peak trains are kept in memory and raw data
+/// can't be set.
+pub struct BrwPhase {
+    fid: sys::hid_t,
+    storage: Storage,
+    sampling_frequency: f32,
+    datalen: usize,
+    labels: Vec<String>,
+    labels_map: HashMap<String, usize>,
+    /// index of each channel in the grid, as stored by the compressed files
+    channel_indices: HashMap<i32, usize>,
+    /// volts of a step of the digital values
+    step: f64,
+    /// volts of the digital value 0
+    offset: f64,
+    peaks: MemoryPhase,
+}
+
+impl Drop for BrwPhase {
+    fn drop(&mut self) {
+        unsafe { sys::h5_close_file(self.fid) };
+    }
+}
+
+impl BrwPhase {
+    /// Open a BRW file, reading the first well of the version 4 files
+    pub fn open(filename: &str) -> Result<Self, SpikeError> {
+        Self::open_well(filename, DEFAULT_WELL)
+    }
+
+    /// Open a BRW file, reading the well `well` (e.g. `Well_B2`) of the
+    /// version 4 multiwell files. The well is ignored by the version 3 ones.
+    pub fn open_well(filename: &str, well: &str) -> Result<Self, SpikeError> {
+        let fid = unsafe { sys::h5_open_file(c(filename).as_ptr()) };
+        if fid <= 0 {
+            return Err(Error::OpenFile.into());
+        }
+        let mut phase = Self {
+            fid,
+            storage: Storage::V3Raw,
+            sampling_frequency: 0f32,
+            datalen: 0,
+            labels: vec![],
+            labels_map: HashMap::new(),
+            channel_indices: HashMap::new(),
+            step: 1f64,
+            offset: 0f64,
+            peaks: MemoryPhase::new(1f32, 0),
+        };
+        if phase.exists(V3_RAW_PATH) {
+            phase.read_v3_layout()?;
+        } else {
+            phase.read_v4_layout(well)?;
+        }
+        phase.labels_map = phase
+            .labels
+            .iter()
+            .enumerate()
+            .map(|(i, label)| (label.clone(), i))
+            .collect();
+        phase.peaks = MemoryPhase::new(phase.sampling_frequency, phase.datalen);
+        Ok(phase)
+    }
+
+    fn read_v3_layout(&mut self) -> Result<(), SpikeError> {
+        let variable = |name: &str| -> Result<f64, SpikeError> {
+            self.read::<f64>(&format!("{V3_VARIABLES_PATH}/{name}"))?
+                .first()
+                .copied()
+                .ok_or_else(|| invalid(&format!("missing {name}")))
+        };
+        let sampling_frequency = variable("SamplingRate")? as f32;
+        let n_frames = variable("NRecFrames")? as usize;
+        let max_volt = variable("MaxVolt")?;
+        let min_volt = variable("MinVolt")?;
+        let bit_depth = variable("BitDepth")?;
+        let inversion = variable("SignalInversion").unwrap_or(1f64);
+        self.sampling_frequency = sampling_frequency;
+
+        let rows = self.read_compound_member(V3_CHANNELS_PATH, "Row")?;
+        let columns = self.read_compound_member(V3_CHANNELS_PATH, "Col")?;
+        self.labels = rows
+            .iter()
+            .zip(columns.iter())
+            .map(|(row, column)| brw_label(*row, *column))
+            .collect();
+        if self.labels.is_empty() {
+            return Err(invalid("no channels"));
+        }
+
+        // the values are in microvolts
+        self.step = (max_volt - min_volt) / 2f64.powf(bit_depth) * inversion * 1e-6;
+        self.offset = min_volt * inversion * 1e-6;
+        self.datalen = usize::min(n_frames, self.dims(V3_RAW_PATH)?[0] / self.labels.len());
+        self.storage = Storage::V3Raw;
+        Ok(())
+    }
+
+    fn read_v4_layout(&mut self, well: &str) -> Result<(), SpikeError> {
+        let settings = parse_json(&self.read_string_attribute("/", "ExperimentSettings")?)?;
+        let number = |path: &[&str]| -> Result<f64, SpikeError> {
+            let mut value = &settings;
+            for key in path {
+                value = value
+                    .get(key)
+                    .ok_or_else(|| invalid(&format!("missing {}", path.join("."))))?;
+            }
+            value
+                .as_f64()
+                .ok_or_else(|| invalid(&format!("invalid {}", path.join("."))))
+        };
+        self.sampling_frequency = number(&["TimeConverter", "FrameRate"])? as f32;
+        let max_analog = number(&["ValueConverter", "MaxAnalogValue"])?;
+        let min_analog = number(&["ValueConverter", "MinAnalogValue"])?;
+        let max_digital = number(&["ValueConverter", "MaxDigitalValue"])?;
+        let min_digital = number(&["ValueConverter", "MinDigitalValue"])?;
+
+        // the values are in microvolts
+        let step = (max_analog - min_analog) / (max_digital - min_digital);
+        self.step = step * 1e-6;
+        self.offset = (min_analog - min_digital * step) * 1e-6;
+
+        let indices = self.read::<i32>(&format!("/{well}/StoredChIdxs"))?;
+        if indices.is_empty() {
+            return Err(invalid("no channels"));
+        }
+        self.labels = indices
+            .iter()
+            .map(|index| brw_label(index / GRID_SIDE + 1, index % GRID_SIDE + 1))
+            .collect();
+        self.channel_indices = indices
+            .iter()
+            .enumerate()
+            .map(|(i, index)| (*index, i))
+            .collect();
+
+        let raw_path = format!("/{well}/Raw");
+        let sparse_path = format!("/{well}/EventsBasedSparseRaw");
+        if self.exists(&raw_path) {
+            self.datalen = self.dims(&raw_path)?[0] / self.labels.len();
+            self.storage = Storage::Raw { path: raw_path };
+        } else if self.exists(&sparse_path) {
+            let toc = self.read::<i64>(V4_TOC_PATH)?;
+            let frames: Vec<(usize, usize)> = toc
+                .chunks_exact(2)
+                .map(|x| (x[0] as usize, x[1] as usize))
+                .collect();
+            let offsets: Vec<usize> = self
+                .read::<i64>(&format!("{sparse_path}TOC"))?
+                .iter()
+                .map(|x| *x as usize)
+                .collect();
+            if offsets.len() != frames.len() {
+                return Err(invalid("sparse raw TOC of different length"));
+            }
+            self.datalen = frames.last().map(|x| x.1).unwrap_or_default();
+            self.storage = Storage::EventsBasedSparse {
+                data_len: self.dims(&sparse_path)?[0],
+                path: sparse_path,
+                offsets,
+                frames,
+            };
+        } else {
+            return Err(invalid(&format!("no raw data in {well}")));
+        }
+        Ok(())
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        unsafe { sys::h5_object_exists(self.fid, c(path).as_ptr()) > 0 }
+    }
+
+    fn dims(&self, path: &str) -> Result<Vec<usize>, SpikeError> {
+        let mut dims = [0 as sys::hsize_t; 4];
+        let mut rank = 0;
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_dataset_dims(self.fid, c(path).as_ptr(), 4, &mut rank, dims.as_mut_ptr())
+        })?;
+        if rank == 0 {
+            return Ok(vec![1]);
+        }
+        Ok(dims[..rank as usize].iter().map(|x| *x as usize).collect())
+    }
+
+    /// Read a whole dataset, flattened
+    fn read<T: H5Type + Default>(&self, path: &str) -> Result<Vec<T>, SpikeError> {
+        let mut data = vec![T::default(); self.dims(path)?.iter().product()];
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_read_dataset(
+                self.fid,
+                c(path).as_ptr(),
+                T::TYPE,
+                data.as_mut_ptr().cast(),
+            )
+        })?;
+        Ok(data)
+    }
+
+    /// Read `count` elements from `start` of a 1 dimensional dataset
+    fn read_slice<T: H5Type + Default>(
+        &self,
+        path: &str,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<T>, SpikeError> {
+        let mut data = vec![T::default(); count];
+        if count == 0 {
+            return Ok(data);
+        }
+        let start = start as sys::hsize_t;
+        let count = count as sys::hsize_t;
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_read_dataset_slice(
+                self.fid,
+                c(path).as_ptr(),
+                T::TYPE,
+                &start,
+                &count,
+                data.as_mut_ptr().cast(),
+            )
+        })?;
+        Ok(data)
+    }
+
+    /// Read `count` elements of a 1 dimensional dataset, one every `stride`
+    /// from `start`
+    fn read_strided<T: H5Type + Default>(
+        &self,
+        path: &str,
+        start: usize,
+        stride: usize,
+        count: usize,
+    ) -> Result<Vec<T>, SpikeError> {
+        let mut data = vec![T::default(); count];
+        if count == 0 {
+            return Ok(data);
+        }
+        let start = start as sys::hsize_t;
+        let stride = stride as sys::hsize_t;
+        let count = count as sys::hsize_t;
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_read_dataset_strided(
+                self.fid,
+                c(path).as_ptr(),
+                T::TYPE,
+                &start,
+                &stride,
+                &count,
+                data.as_mut_ptr().cast(),
+            )
+        })?;
+        Ok(data)
+    }
+
+    fn read_compound_member(&self, path: &str, member: &str) -> Result<Vec<i32>, SpikeError> {
+        let mut data = vec![0i32; self.dims(path)?.iter().product()];
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_read_compound_member(
+                self.fid,
+                c(path).as_ptr(),
+                c(member).as_ptr(),
+                i32::TYPE,
+                data.as_mut_ptr().cast(),
+            )
+        })?;
+        Ok(data)
+    }
+
+    fn read_string_attribute(&self, object: &str, name: &str) -> Result<String, SpikeError> {
+        let mut len = 0;
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_string_attribute_len(self.fid, c(object).as_ptr(), c(name).as_ptr(), &mut len)
+        })?;
+        let mut buf = vec![0 as c_char; len.max(1)];
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_read_string_attribute(
+                self.fid,
+                c(object).as_ptr(),
+                c(name).as_ptr(),
+                buf.as_mut_ptr(),
+                buf.len(),
+            )
+        })?;
+        Ok(unsafe { CStr::from_ptr(buf.as_ptr()) }
+            .to_string_lossy()
+            .to_string())
+    }
+
+    /// Read the digital values of the channels at `columns` in `start..end`,
+    /// row by row. The dense layouts read only the samples of `columns`.
+    fn read_digital(
+        &self,
+        columns: &[usize],
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<i32>, SpikeError> {
+        let n_samples = end - start;
+        let mut data = vec![0i32; columns.len() * n_samples];
+        match &self.storage {
+            Storage::V3Raw | Storage::Raw { .. } => {
+                let path = match &self.storage {
+                    Storage::Raw { path } => path.as_str(),
+                    _ => V3_RAW_PATH,
+                };
+                let n_channels = self.labels.len();
+                for (row, column) in columns.iter().enumerate() {
+                    let (first, stride, count) = column_hyperslab(*column, n_channels, start, end);
+                    let values = self.read_strided::<i32>(path, first, stride, count)?;
+                    data[row * n_samples..(row + 1) * n_samples].copy_from_slice(&values);
+                }
+            }
+            Storage::EventsBasedSparse {
+                path,
+                offsets,
+                frames,
+                data_len,
+            } => {
+                // digital value of 0 volts, for the samples not stored
+                let zero = (-self.offset / self.step).round() as i32;
+                data.fill(zero);
+                let rows: HashMap<usize, usize> = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(row, column)| (*column, row))
+                    .collect();
+                for (chunk, (first, last)) in frames.iter().enumerate() {
+                    if *last <= start || *first >= end {
+                        continue;
+                    }
+                    let chunk_end = offsets.get(chunk + 1).copied().unwrap_or(*data_len);
+                    let chunk_data =
+                        self.read_slice::<i32>(path, offsets[chunk], chunk_end - offsets[chunk])?;
+                    self.parse_sparse_chunk(&chunk_data, &rows, start, end, &mut data)?;
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    /// Copy the samples in `start..end` of the channels in `rows` from a
+    /// chunk of the compressed data: a sequence of channels, each one with
+    /// its index and the length of its ranges (32 bit integers), followed by
+    /// its ranges, each one with the first and the last frame, excluded,
+    /// (64 bit integers) and the samples. All the integers are stored as 16
+    /// bit words, little endian.
+    fn parse_sparse_chunk(
+        &self,
+        chunk: &[i32],
+        rows: &HashMap<usize, usize>,
+        start: usize,
+        end: usize,
+        data: &mut [i32],
+    ) -> Result<(), SpikeError> {
+        let word = |i: usize| -> Result<i64, SpikeError> {
+            chunk
+                .get(i)
+                .map(|x| (*x as i64) & 0xffff)
+                .ok_or_else(|| invalid("truncated sparse raw chunk"))
+        };
+        let int32 = |i: usize| -> Result<i64, SpikeError> {
+            Ok((word(i)? | (word(i + 1)? << 16)) as i32 as i64)
+        };
+        let int64 = |i: usize| -> Result<i64, SpikeError> {
+            Ok(word(i)? | (word(i + 1)? << 16) | (word(i + 2)? << 32) | (word(i + 3)? << 48))
+        };
+
+        let n_samples = end - start;
+        let mut i = 0;
+        while i < chunk.len() {
+            let index = int32(i)? as i32;
+            let len = int32(i + 2)? as usize;
+            i += 4;
+            let channel_end = i + len;
+            let row = self
+                .channel_indices
+                .get(&index)
+                .and_then(|column| rows.get(column));
+            while i < channel_end {
+                let first = int64(i)? as usize;
+                let last = int64(i + 4)? as usize;
+                i += 8;
+                if let Some(row) = row {
+                    for frame in usize::max(first, start)..usize::min(last, end) {
+                        let value = *chunk
+                            .get(i + frame - first)
+                            .ok_or_else(|| invalid("truncated sparse raw chunk"))?;
+                        data[row * n_samples + frame - start] = value;
+                    }
+                }
+                i += last.saturating_sub(first);
+            }
+        }
+        Ok(())
+    }
+
+    fn column(&self, channel: &str) -> Result<usize, SpikeError> {
+        self.labels_map
+            .get(channel)
+            .copied()
+            .ok_or(SpikeError::RawDataLabelNotFound)
+    }
+}
+
+impl PhaseHandler for BrwPhase {
+    fn sampling_frequency(&self) -> f32 {
+        self.sampling_frequency
+    }
+
+    fn datalen(&self) -> usize {
+        self.datalen
+    }
+
+    fn labels(&self) -> Vec<String> {
+        self.labels.clone()
+    }
+
+    fn channel_info(&self, channel: &str) -> Result<ChannelInfo, SpikeError> {
+        let column = self.column(channel)?;
+        let mut info = ChannelInfo {
+            label: channel.to_string(),
+            row_index: column as i32,
+            raw_data_type: "Int".to_string(),
+            unit: "V".to_string(),
+            ad_zero: (-self.offset / self.step).round() as i32,
+            tick: (1e6 / self.sampling_frequency as f64).round() as i64,
+            adc_bits: 16,
+            ..Default::default()
+        };
+        info.set_adc_step(self.step);
+        Ok(info)
+    }
+
+    fn recording_start(&self) -> Option<DateTime> {
+        None
+    }
+
+    fn raw_data(
+        &self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        let (actual_start, actual_end) = check_range(start, end, self.datalen)?;
+        let column = self.column(channel)?;
+        Ok(self
+            .read_digital(&[column], actual_start, actual_end)?
+            .iter()
+            .map(|x| (*x as f64 * self.step + self.offset) as f32)
+            .collect())
+    }
+
+    fn set_raw_data(
+        &mut self,
+        _channel: &str,
+        _start: Option<usize>,
+        _data: &[f32],
+    ) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn raw_block(
+        &self,
+        channels: &[String],
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<RawBlock, SpikeError> {
+        let (actual_start, actual_end) = check_range(start, end, self.datalen)?;
+        let columns = channels
+            .iter()
+            .map(|channel| self.column(channel))
+            .collect::<Result<Vec<usize>, SpikeError>>()?;
+        let data = self
+            .read_digital(&columns, actual_start, actual_end)?
+            .iter()
+            .map(|x| (*x as f64 * self.step + self.offset) as f32)
+            .collect();
+        RawBlock::new(
+            channels.to_vec(),
+            actual_start,
+            actual_end - actual_start,
+            data,
+        )
+    }
+
+    fn analog_streams(&self) -> Vec<String> {
+        vec![MemoryPhase::DEFAULT_STREAM.to_string()]
+    }
+
+    fn raw_data_stream(&self) -> String {
+        MemoryPhase::DEFAULT_STREAM.to_string()
+    }
+
+    fn set_raw_data_stream(&mut self, stream: &str) -> Result<(), SpikeError> {
+        if stream != MemoryPhase::DEFAULT_STREAM {
+            return Err(SpikeError::AnalogStreamNotFound);
+        }
+        Ok(())
+    }
+
+    fn analog_labels(&self, stream: &str) -> Result<Vec<String>, SpikeError> {
+        if stream != MemoryPhase::DEFAULT_STREAM {
+            return Err(SpikeError::AnalogStreamNotFound);
+        }
+        Ok(self.labels())
+    }
+
+    fn analog_data(
+        &self,
+        stream: &str,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        if stream != MemoryPhase::DEFAULT_STREAM {
+            return Err(SpikeError::AnalogStreamNotFound);
+        }
+        self.raw_data(channel, start, end)
+    }
+
+    fn n_digitals(&self) -> usize {
+        0
+    }
+
+    fn digital_labels(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn digital(
+        &self,
+        _index: usize,
+        _start: Option<usize>,
+        _end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        Err(SpikeError::DigitalNoDigitalPresent)
+    }
+
+    fn set_digital(
+        &mut self,
+        _index: usize,
+        _start: Option<usize>,
+        _data: &[f32],
+    ) -> Result<(), SpikeError> {
+        Err(SpikeError::PhaseIsReadOnly)
+    }
+
+    fn n_events(&self) -> usize {
+        0
+    }
+
+    fn events(&self, _index: usize) -> Result<EventEntity, SpikeError> {
+        Err(SpikeError::IndexOutOfRange)
+    }
+
+    spike_rs::delegate_peak_trains!(peaks);
+}
@@ -0,0 +1,223 @@
+use std::ffi::{c_char, CString};
+
+use crate::{sys, Error};
+
+/// Number of samples read from the phase and written at once by the exports
+pub(crate) const CHUNK_LEN: usize = 1 << 16;
+
+/// Native types that can be written to and read from an HDF5 dataset
+pub(crate) trait H5Type: Copy {
+    const TYPE: sys::h5_type;
+}
+
+impl H5Type for i32 {
+    const TYPE: sys::h5_type = sys::h5_type_H5_TYPE_INT;
+}
+
+impl H5Type for i64 {
+    const TYPE: sys::h5_type = sys::h5_type_H5_TYPE_LLONG;
+}
+
+impl H5Type for u64 {
+    const TYPE: sys::h5_type = sys::h5_type_H5_TYPE_ULLONG;
+}
+
+impl H5Type for f32 {
+    const TYPE: sys::h5_type = sys::h5_type_H5_TYPE_FLOAT;
+}
+
+impl H5Type for f64 {
+    const TYPE: sys::h5_type = sys::h5_type_H5_TYPE_DOUBLE;
+}
+
+pub(crate) fn c(value: &str) -> CString {
+    CString::new(value).expect("h5: Failed to convert the CStr")
+}
+
+fn h5_dims(dims: &[usize]) -> Vec<sys::hsize_t> {
+    dims.iter().map(|x| *x as sys::hsize_t).collect()
+}
+
+/// H5File
+///
+/// A new HDF5 file written with the generic `h5_*` functions of the C
+/// library. The exports add the helpers of their own layout, NWB or Multi
+/// Channel Systems, in their modules.
+pub(crate) struct H5File {
+    pub(crate) fid: sys::hid_t,
+}
+
+impl H5File {
+    pub(crate) fn create(filename: &str) -> Result<Self, Error> {
+        let fid = unsafe { sys::h5_create_file(c(filename).as_ptr()) };
+        if fid <= 0 {
+            return Err(Error::H5CreateFile);
+        }
+        Ok(Self { fid })
+    }
+
+    pub(crate) fn close(self) -> Result<(), Error> {
+        Error::from_phaseh5_error(unsafe { sys::h5_close_file(self.fid) })
+    }
+
+    pub(crate) fn group(&self, path: &str) -> Result<(), Error> {
+        Error::from_phaseh5_error(unsafe { sys::h5_create_group(self.fid, c(path).as_ptr()) })
+    }
+
+    pub(crate) fn soft_link(&self, path: &str, target: &str) -> Result<(), Error> {
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_create_soft_link(self.fid, c(path).as_ptr(), c(target).as_ptr())
+        })
+    }
+
+    pub(crate) fn attribute(&self, object: &str, name: &str, value: &str) -> Result<(), Error> {
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_string_attribute(
+                self.fid,
+                c(object).as_ptr(),
+                c(name).as_ptr(),
+                c(value).as_ptr(),
+            )
+        })
+    }
+
+    pub(crate) fn float_attribute(
+        &self,
+        object: &str,
+        name: &str,
+        value: f32,
+    ) -> Result<(), Error> {
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_float_attribute(self.fid, c(object).as_ptr(), c(name).as_ptr(), value)
+        })
+    }
+
+    pub(crate) fn llong_attribute(
+        &self,
+        object: &str,
+        name: &str,
+        value: i64,
+    ) -> Result<(), Error> {
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_llong_attribute(self.fid, c(object).as_ptr(), c(name).as_ptr(), value)
+        })
+    }
+
+    pub(crate) fn strings_attribute(
+        &self,
+        object: &str,
+        name: &str,
+        values: &[&str],
+    ) -> Result<(), Error> {
+        let values: Vec<CString> = values.iter().map(|x| c(x)).collect();
+        let ptrs: Vec<*const c_char> = values.iter().map(|x| x.as_ptr()).collect();
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_string_array_attribute(
+                self.fid,
+                c(object).as_ptr(),
+                c(name).as_ptr(),
+                ptrs.as_ptr(),
+                ptrs.len(),
+            )
+        })
+    }
+
+    pub(crate) fn reference_attribute(
+        &self,
+        object: &str,
+        name: &str,
+        target: &str,
+    ) -> Result<(), Error> {
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_reference_attribute(
+                self.fid,
+                c(object).as_ptr(),
+                c(name).as_ptr(),
+                c(target).as_ptr(),
+            )
+        })
+    }
+
+    pub(crate) fn dataset<T: H5Type>(
+        &self,
+        path: &str,
+        dims: &[usize],
+        data: &[T],
+    ) -> Result<(), Error> {
+        let dims = h5_dims(dims);
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_dataset(
+                self.fid,
+                c(path).as_ptr(),
+                T::TYPE,
+                dims.len() as i32,
+                dims.as_ptr(),
+                data.as_ptr().cast(),
+            )
+        })
+    }
+
+    pub(crate) fn empty_dataset<T: H5Type>(&self, path: &str, dims: &[usize]) -> Result<(), Error> {
+        let dims = h5_dims(dims);
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_create_dataset(
+                self.fid,
+                c(path).as_ptr(),
+                T::TYPE,
+                dims.len() as i32,
+                dims.as_ptr(),
+            )
+        })
+    }
+
+    /// Write `data` to the `start`, `count` hyperslab of a dataset created
+    /// with `empty_dataset`
+    pub(crate) fn dataset_slice<T: H5Type>(
+        &self,
+        path: &str,
+        start: &[usize],
+        count: &[usize],
+        data: &[T],
+    ) -> Result<(), Error> {
+        let start = h5_dims(start);
+        let count = h5_dims(count);
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_dataset_slice(
+                self.fid,
+                c(path).as_ptr(),
+                T::TYPE,
+                start.as_ptr(),
+                count.as_ptr(),
+                data.as_ptr().cast(),
+            )
+        })
+    }
+
+    pub(crate) fn string_dataset(
+        &self,
+        path: &str,
+        dims: &[usize],
+        values: &[String],
+    ) -> Result<(), Error> {
+        let dims = h5_dims(dims);
+        let values: Vec<CString> = values.iter().map(|x| c(x)).collect();
+        let ptrs: Vec<*const c_char> = values.iter().map(|x| x.as_ptr()).collect();
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_string_dataset(
+                self.fid,
+                c(path).as_ptr(),
+                dims.len() as i32,
+                dims.as_ptr(),
+                ptrs.as_ptr(),
+            )
+        })
+    }
+
+    pub(crate) fn reference_dataset(&self, path: &str, targets: &[&str]) -> Result<(), Error> {
+        let targets: Vec<CString> = targets.iter().map(|x| c(x)).collect();
+        let ptrs: Vec<*const c_char> = targets.iter().map(|x| x.as_ptr()).collect();
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_reference_dataset(self.fid, c(path).as_ptr(), ptrs.as_ptr(), ptrs.len())
+        })
+    }
+}
@@ -1,8 +1,17 @@
 use std::collections::HashMap;
-use std::ffi::{CStr, CString};
+use std::ffi::{c_char, CStr, CString};
 
 use pyo3::prelude::*;
-use spike_rs::{analysis, error::SpikeError, types::PhaseHandler};
+use spike_rs::{
+    analysis,
+    error::SpikeError,
+    io::{kilosort, spycode},
+    time::{DateTime, Span, Time},
+    types::{
+        check_digital_range, check_range, ChannelInfo, EventEntity, PeakTrainMetadata,
+        PhaseHandler, RawBlock,
+    },
+};
 
 mod sys {
     #![allow(non_upper_case_globals)]
@@ -12,16 +21,26 @@ mod sys {
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 }
 
+pub mod brw;
+mod h5;
+pub mod mcs;
+pub mod nwb;
+
 #[derive(Debug)]
 pub enum Error {
     ErrorNotYetConverted(i32),
     CreatePeakTrainSamplesValuesDifferentLen,
     OpenFile,
+    OpenFileAccessProperties,
+    PhaseIsReadOnly,
     CloseFile,
     OpenDataGroup,
     OpenDateAttribute,
     ReadDateAttribute,
     OpenDateDatatype,
+    OpenAllocateAnalogs,
+    OpenAllocateInfoChannels,
+    OpenAllocateEvents,
     OpenAnalogGroup,
     OpenInfoChannelDataset,
     OpenInfoChannelDataspace,
@@ -31,11 +50,10 @@ pub enum Error {
     OpenLabelAttribute,
     ReadLabelAttribute,
     OpenLabelDatatype,
+    OpenDataSubtypeAttribute,
+    ReadDataSubtypeAttribute,
     ParseAnalogStream,
-    MultipleDigitalStreams,
-    MultipleRawDataStreams,
     MultipleSamplingFrequencies,
-    MultipleDatalens,
     OpenChannelData,
     OpenChannelDataDataspace,
     GetChannelDataDims,
@@ -43,23 +61,29 @@ pub enum Error {
     OpenEventStreamGroupLink,
     OpenEventStreamGroup,
     OpenEventStreamStream0GroupLink,
-    MaxEventStreamsExceeded,
     OpenEntityDataset,
+    OpenInfoEventDataset,
     EventEntityDatasetClose,
     OpenPeakTrainGroup,
     CreatePeakGroup,
+    SetRawDataStreamIndexOutOfBounds,
+    SetRawDataStreamIsDigital,
+    AnalogDataStreamIndexOutOfBounds,
+    AnalogDataChannelIndexOutOfBounds,
     RawDataEndBeforeStart,
     RawDataEndOutOfBounds,
     RawDataGetDataspace,
     RawDataSelectHyperslab,
     RawDataCreateMemoryDataspace,
     RawDataReadData,
+    RawDataBlockChannelIndexOutOfBounds,
     SetRawDataEndOutOfBounds,
     SetRawDataGetDataspace,
     SetRawDataSelectHyperslab,
     SetRawDataCreateMemoryDataspace,
     SetRawDataWriteDataset,
     DigitalNoDigital,
+    DigitalIndexOutOfBounds,
     DigitalEndBeforeStart,
     DigitalEndOutOfBounds,
     DigitalGetDataspaceFail,
@@ -67,6 +91,7 @@ pub enum Error {
     DigitalCreateMemoryDataspaceFail,
     DigitalReadDataFail,
     SetDigitalNoDigital,
+    SetDigitalIndexOutOfBounds,
     SetDigitalEndBeforeStart,
     SetDigitalEndOutOfBounds,
     SetDigitalGetDataspaceFail,
@@ -128,6 +153,46 @@ pub enum Error {
     SetPeakTrainCloseValuesMemoryDataspace,
     SetPeakTrainCloseSamplesDataset,
     SetPeakTrainCloseValuesDataset,
+    SetPeakTrainDeleteMetadata,
+    PeakTrainLabelsGetGroupInfo,
+    PeakTrainLabelGetName,
+    PeakTrainLabelTooLong,
+    PeakTrainPathTooLong,
+    DeletePeakTrainCheckLabelGroup,
+    DeletePeakTrainNoLabelGroup,
+    DeletePeakTrainGroup,
+    RenamePeakTrainCheckLabelGroup,
+    RenamePeakTrainNoLabelGroup,
+    RenamePeakTrainLabelAlreadyExists,
+    RenamePeakTrainMoveGroup,
+    PeakTrainMetadataCheckLabelGroup,
+    PeakTrainMetadataNoLabelGroup,
+    PeakTrainNoMetadata,
+    PeakTrainMetadataReadAttribute,
+    PeakRunCheckGroup,
+    PeakRunNotFound,
+    PeakRunNameTooLong,
+    PeakRunInvalidName,
+    PeakRunsGetGroupInfo,
+    PeakRunGetName,
+    CopyPeakRun,
+    H5CreateFile,
+    H5CloseFile,
+    H5CreateGroup,
+    H5DeleteLink,
+    H5CreateAttribute,
+    H5WriteAttribute,
+    H5CreateDataset,
+    H5WriteDataset,
+    H5CreateLink,
+    H5CreateReference,
+    H5OpenDataset,
+    H5SelectHyperslab,
+    H5DatasetRankTooLarge,
+    H5ReadDataset,
+    H5OpenAttribute,
+    H5ReadAttribute,
+    BrwInvalid(String),
 }
 
 impl std::fmt::Display for Error {
@@ -140,7 +205,17 @@ impl std::error::Error for Error {}
 
 impl From<Error> for SpikeError {
     fn from(err: Error) -> Self {
-        SpikeError::Implementation(format!("{:?}", err))
+        match err {
+            Error::PhaseIsReadOnly => SpikeError::PhaseIsReadOnly,
+            Error::PeakTrainNoPeakGroup
+            | Error::DeletePeakTrainNoLabelGroup
+            | Error::RenamePeakTrainNoLabelGroup
+            | Error::PeakTrainMetadataNoLabelGroup => SpikeError::PeakTrainNotFound,
+            Error::PeakRunNotFound => SpikeError::PeakRunNotFound,
+            Error::PeakRunInvalidName => SpikeError::PeakRunInvalidName,
+            Error::RenamePeakTrainLabelAlreadyExists => SpikeError::PeakTrainAlreadyExists,
+            _ => SpikeError::Implementation(format!("{:?}", err)),
+        }
     }
 }
 
@@ -149,11 +224,20 @@ impl Error {
         match code {
             sys::phaseh5_error_OK => Ok(()),
             sys::phaseh5_error_OPEN_FAIL => Err(Error::OpenFile),
+            sys::phaseh5_error_OPEN_FILE_ACCESS_PROPERTIES_FAIL => {
+                Err(Error::OpenFileAccessProperties)
+            }
+            sys::phaseh5_error_PHASE_IS_READ_ONLY => Err(Error::PhaseIsReadOnly),
             sys::phaseh5_error_CLOSE_FILE_FAIL => Err(Error::CloseFile),
             sys::phaseh5_error_OPEN_DATA_GROUP_FAIL => Err(Error::OpenDataGroup),
             sys::phaseh5_error_OPEN_DATE_ATTRIBUTE_FAIL => Err(Error::OpenDateAttribute),
             sys::phaseh5_error_READ_DATE_ATTRIBUTE_FAIL => Err(Error::ReadDateAttribute),
             sys::phaseh5_error_OPEN_DATE_DATATYPE_FAIL => Err(Error::OpenDateDatatype),
+            sys::phaseh5_error_OPEN_ALLOCATE_ANALOGS_FAIL => Err(Error::OpenAllocateAnalogs),
+            sys::phaseh5_error_OPEN_ALLOCATE_INFO_CHANNELS_FAIL => {
+                Err(Error::OpenAllocateInfoChannels)
+            }
+            sys::phaseh5_error_OPEN_ALLOCATE_EVENTS_FAIL => Err(Error::OpenAllocateEvents),
             sys::phaseh5_error_OPEN_ANALOG_GROUP_FAIL => Err(Error::OpenAnalogGroup),
             sys::phaseh5_error_OPEN_INFO_CHANNEL_DATASET_FAIL => Err(Error::OpenInfoChannelDataset),
             sys::phaseh5_error_OPEN_INFO_CHANNEL_DATASPACE_FAIL => {
@@ -166,14 +250,17 @@ impl Error {
             sys::phaseh5_error_OPEN_LABEL_ATTRIBUTE_FAIL => Err(Error::OpenLabelAttribute),
             sys::phaseh5_error_READ_LABEL_ATTRIBUTE_FAIL => Err(Error::ReadLabelAttribute),
             sys::phaseh5_error_OPEN_LABEL_DATATYPE_FAIL => Err(Error::OpenLabelDatatype),
+            sys::phaseh5_error_OPEN_DATA_SUBTYPE_ATTRIBUTE_FAIL => {
+                Err(Error::OpenDataSubtypeAttribute)
+            }
+            sys::phaseh5_error_READ_DATA_SUBTYPE_ATTRIBUTE_FAIL => {
+                Err(Error::ReadDataSubtypeAttribute)
+            }
             sys::phaseh5_error_READ_INFO_CHANNELS_FAIL => Err(Error::ReadInfoChannels),
             sys::phaseh5_error_PARSE_ANALOG_STREAM_DIFFERENT_TICK => Err(Error::ParseAnalogStream),
-            sys::phaseh5_error_MULTIPLE_DIGITAL_STREAMS => Err(Error::MultipleDigitalStreams),
-            sys::phaseh5_error_MULTIPLE_RAW_DATA_STREAMS => Err(Error::MultipleRawDataStreams),
             sys::phaseh5_error_MULTIPLE_SAMPLING_FREQUENCIES => {
                 Err(Error::MultipleSamplingFrequencies)
             }
-            sys::phaseh5_error_MULTIPLE_DATALENS => Err(Error::MultipleDatalens),
             sys::phaseh5_error_OPEN_CHANNEL_DATA_FAIL => Err(Error::OpenChannelData),
             sys::phaseh5_error_OPEN_CHANNEL_DATA_DATASPACE_FAIL => {
                 Err(Error::OpenChannelDataDataspace)
@@ -187,13 +274,25 @@ impl Error {
             sys::phaseh5_error_OPEN_EVENT_STREAM_STREAM_0_GROUP_LINK_FAIL => {
                 Err(Error::OpenEventStreamStream0GroupLink)
             }
-            sys::phaseh5_error_MAX_EVENT_STREAMS_EXCEEDED => Err(Error::MaxEventStreamsExceeded),
             sys::phaseh5_error_OPEN_ENTITY_DATASET_FAIL => Err(Error::OpenEntityDataset),
+            sys::phaseh5_error_OPEN_INFO_EVENT_DATASET_FAIL => Err(Error::OpenInfoEventDataset),
             sys::phaseh5_error_EVENT_ENTITY_DATASET_CLOSE_FAIL => {
                 Err(Error::EventEntityDatasetClose)
             }
             sys::phaseh5_error_OPEN_PEAK_TRAIN_GROUP_FAIL => Err(Error::OpenPeakTrainGroup),
             sys::phaseh5_error_CREATE_PEAK_GROUP_FAIL => Err(Error::CreatePeakGroup),
+            sys::phaseh5_error_SET_RAW_DATA_STREAM_INDEX_OUT_OF_BOUNDS => {
+                Err(Error::SetRawDataStreamIndexOutOfBounds)
+            }
+            sys::phaseh5_error_SET_RAW_DATA_STREAM_IS_DIGITAL => {
+                Err(Error::SetRawDataStreamIsDigital)
+            }
+            sys::phaseh5_error_ANALOG_DATA_STREAM_INDEX_OUT_OF_BOUNDS => {
+                Err(Error::AnalogDataStreamIndexOutOfBounds)
+            }
+            sys::phaseh5_error_ANALOG_DATA_CHANNEL_INDEX_OUT_OF_BOUNDS => {
+                Err(Error::AnalogDataChannelIndexOutOfBounds)
+            }
             sys::phaseh5_error_RAW_DATA_END_BEFORE_START => Err(Error::RawDataEndBeforeStart),
             sys::phaseh5_error_RAW_DATA_END_OUT_OF_BOUNDS => Err(Error::RawDataEndOutOfBounds),
             sys::phaseh5_error_RAW_DATA_GET_DATASPACE_FAIL => Err(Error::RawDataGetDataspace),
@@ -202,6 +301,9 @@ impl Error {
                 Err(Error::RawDataCreateMemoryDataspace)
             }
             sys::phaseh5_error_RAW_DATA_READ_DATA_FAIL => Err(Error::RawDataReadData),
+            sys::phaseh5_error_RAW_DATA_BLOCK_CHANNEL_INDEX_OUT_OF_BOUNDS => {
+                Err(Error::RawDataBlockChannelIndexOutOfBounds)
+            }
             sys::phaseh5_error_SET_RAW_DATA_GET_DATASPACE_FAIL => {
                 Err(Error::SetRawDataGetDataspace)
             }
@@ -215,6 +317,7 @@ impl Error {
                 Err(Error::SetRawDataWriteDataset)
             }
             sys::phaseh5_error_DIGITAL_NO_DIGITAL => Err(Error::DigitalNoDigital),
+            sys::phaseh5_error_DIGITAL_INDEX_OUT_OF_BOUNDS => Err(Error::DigitalIndexOutOfBounds),
             sys::phaseh5_error_DIGITAL_END_BEFORE_START => Err(Error::DigitalEndBeforeStart),
             sys::phaseh5_error_DIGITAL_END_OUT_OF_BOUNDS => Err(Error::DigitalEndOutOfBounds),
             sys::phaseh5_error_DIGITAL_GET_DATASPACE_FAIL => Err(Error::DigitalGetDataspaceFail),
@@ -226,6 +329,9 @@ impl Error {
             }
             sys::phaseh5_error_DIGITAL_READ_DATA_FAIL => Err(Error::DigitalReadDataFail),
             sys::phaseh5_error_SET_DIGITAL_NO_DIGITAL => Err(Error::SetDigitalNoDigital),
+            sys::phaseh5_error_SET_DIGITAL_INDEX_OUT_OF_BOUNDS => {
+                Err(Error::SetDigitalIndexOutOfBounds)
+            }
             sys::phaseh5_error_SET_DIGITAL_END_BEFORE_START => Err(Error::SetDigitalEndBeforeStart),
             sys::phaseh5_error_SET_DIGITAL_END_OUT_OF_BOUNDS => {
                 Err(Error::SetDigitalEndOutOfBounds)
@@ -386,6 +492,67 @@ impl Error {
             sys::phaseh5_error_SET_PEAK_TRAIN_CLOSE_VALUES_DATASET_FAIL => {
                 Err(Error::SetPeakTrainCloseValuesDataset)
             }
+            sys::phaseh5_error_SET_PEAK_TRAIN_DELETE_METADATA_FAIL => {
+                Err(Error::SetPeakTrainDeleteMetadata)
+            }
+            sys::phaseh5_error_PEAK_TRAIN_LABELS_GET_GROUP_INFO_FAIL => {
+                Err(Error::PeakTrainLabelsGetGroupInfo)
+            }
+            sys::phaseh5_error_PEAK_TRAIN_LABEL_GET_NAME_FAIL => Err(Error::PeakTrainLabelGetName),
+            sys::phaseh5_error_PEAK_TRAIN_LABEL_TOO_LONG => Err(Error::PeakTrainLabelTooLong),
+            sys::phaseh5_error_PEAK_TRAIN_PATH_TOO_LONG => Err(Error::PeakTrainPathTooLong),
+            sys::phaseh5_error_DELETE_PEAK_TRAIN_CHECK_LABEL_GROUP_FAIL => {
+                Err(Error::DeletePeakTrainCheckLabelGroup)
+            }
+            sys::phaseh5_error_DELETE_PEAK_TRAIN_NO_LABEL_GROUP => {
+                Err(Error::DeletePeakTrainNoLabelGroup)
+            }
+            sys::phaseh5_error_DELETE_PEAK_TRAIN_GROUP_FAIL => Err(Error::DeletePeakTrainGroup),
+            sys::phaseh5_error_RENAME_PEAK_TRAIN_CHECK_LABEL_GROUP_FAIL => {
+                Err(Error::RenamePeakTrainCheckLabelGroup)
+            }
+            sys::phaseh5_error_RENAME_PEAK_TRAIN_NO_LABEL_GROUP => {
+                Err(Error::RenamePeakTrainNoLabelGroup)
+            }
+            sys::phaseh5_error_RENAME_PEAK_TRAIN_LABEL_ALREADY_EXISTS => {
+                Err(Error::RenamePeakTrainLabelAlreadyExists)
+            }
+            sys::phaseh5_error_RENAME_PEAK_TRAIN_MOVE_GROUP_FAIL => {
+                Err(Error::RenamePeakTrainMoveGroup)
+            }
+            sys::phaseh5_error_PEAK_TRAIN_METADATA_CHECK_LABEL_GROUP_FAIL => {
+                Err(Error::PeakTrainMetadataCheckLabelGroup)
+            }
+            sys::phaseh5_error_PEAK_TRAIN_METADATA_NO_LABEL_GROUP => {
+                Err(Error::PeakTrainMetadataNoLabelGroup)
+            }
+            sys::phaseh5_error_PEAK_TRAIN_NO_METADATA => Err(Error::PeakTrainNoMetadata),
+            sys::phaseh5_error_PEAK_TRAIN_METADATA_READ_ATTRIBUTE_FAIL => {
+                Err(Error::PeakTrainMetadataReadAttribute)
+            }
+            sys::phaseh5_error_PEAK_RUN_CHECK_GROUP_FAIL => Err(Error::PeakRunCheckGroup),
+            sys::phaseh5_error_PEAK_RUN_NOT_FOUND => Err(Error::PeakRunNotFound),
+            sys::phaseh5_error_PEAK_RUN_NAME_TOO_LONG => Err(Error::PeakRunNameTooLong),
+            sys::phaseh5_error_PEAK_RUN_INVALID_NAME => Err(Error::PeakRunInvalidName),
+            sys::phaseh5_error_PEAK_RUNS_GET_GROUP_INFO_FAIL => Err(Error::PeakRunsGetGroupInfo),
+            sys::phaseh5_error_PEAK_RUN_GET_NAME_FAIL => Err(Error::PeakRunGetName),
+            sys::phaseh5_error_COPY_PEAK_RUN_FAIL => Err(Error::CopyPeakRun),
+            sys::phaseh5_error_H5_CREATE_FILE_FAIL => Err(Error::H5CreateFile),
+            sys::phaseh5_error_H5_CLOSE_FILE_FAIL => Err(Error::H5CloseFile),
+            sys::phaseh5_error_H5_CREATE_GROUP_FAIL => Err(Error::H5CreateGroup),
+            sys::phaseh5_error_H5_DELETE_LINK_FAIL => Err(Error::H5DeleteLink),
+            sys::phaseh5_error_H5_CREATE_ATTRIBUTE_FAIL => Err(Error::H5CreateAttribute),
+            sys::phaseh5_error_H5_WRITE_ATTRIBUTE_FAIL => Err(Error::H5WriteAttribute),
+            sys::phaseh5_error_H5_CREATE_DATASET_FAIL => Err(Error::H5CreateDataset),
+            sys::phaseh5_error_H5_WRITE_DATASET_FAIL => Err(Error::H5WriteDataset),
+            sys::phaseh5_error_H5_CREATE_LINK_FAIL => Err(Error::H5CreateLink),
+            sys::phaseh5_error_H5_CREATE_REFERENCE_FAIL => Err(Error::H5CreateReference),
+            sys::phaseh5_error_H5_OPEN_DATASET_FAIL => Err(Error::H5OpenDataset),
+            sys::phaseh5_error_H5_SELECT_HYPERSLAB_FAIL => Err(Error::H5SelectHyperslab),
+            sys::phaseh5_error_H5_DATASET_RANK_TOO_LARGE => Err(Error::H5DatasetRankTooLarge),
+            sys::phaseh5_error_H5_READ_DATASET_FAIL => Err(Error::H5ReadDataset),
+            sys::phaseh5_error_H5_OPEN_ATTRIBUTE_FAIL => Err(Error::H5OpenAttribute),
+            sys::phaseh5_error_H5_READ_ATTRIBUTE_FAIL => Err(Error::H5ReadAttribute),
             _ => Err(Error::ErrorNotYetConverted(code.try_into().unwrap())),
         }
     }
@@ -470,6 +637,45 @@ impl std::fmt::Debug for Phase {
     }
 }
 
+impl std::default::Default for sys::InfoChannel {
+    fn default() -> Self {
+        sys::InfoChannel {
+            channel_id: 0,
+            row_index: 0,
+            group_id: 0,
+            electrode_group: 0,
+            label: std::ptr::null(),
+            raw_data_type: std::ptr::null(),
+            unit: std::ptr::null(),
+            exponent: 0,
+            ad_zero: 0,
+            tick: 0,
+            conversion_factor: 0,
+            adc_bits: 0,
+            high_pass_filter_type: std::ptr::null(),
+            high_pass_filter_cutoff: std::ptr::null(),
+            high_pass_filter_order: 0,
+            low_pass_filter_type: std::ptr::null(),
+            low_pass_filter_cutoff: std::ptr::null(),
+            low_pass_filter_order: 0,
+        }
+    }
+}
+
+impl std::default::Default for sys::AnalogStream {
+    fn default() -> Self {
+        sys::AnalogStream {
+            label: [0; sys::ANALOG_LABEL_STRING_LEN as usize],
+            data_subtype: [0; sys::ANALOG_LABEL_STRING_LEN as usize],
+            n_channels: 0,
+            channel_data_dataset: 0,
+            datalen: 0,
+            sampling_frequency: 0f32,
+            info_channels: std::ptr::null_mut(),
+        }
+    }
+}
+
 impl std::default::Default for Phase {
     fn default() -> Self {
         Self {
@@ -477,91 +683,52 @@ impl std::default::Default for Phase {
             labels_map: HashMap::new(),
             phase: sys::PhaseH5 {
                 fid: 0,
+                read_only: false,
                 date: [0; sys::DATE_STRING_LEN as usize],
+                date_in_ticks: 0,
                 datalen: 0,
                 sampling_frequency: 0f32,
-                raw_data: sys::AnalogStream {
-                    label: [0; sys::ANALOG_LABEL_STRING_LEN as usize],
-                    n_channels: 0,
-                    channel_data_dataset: 0,
-                    datalen: 0,
-                    info_channels: [sys::InfoChannel {
-                        channel_id: 0,
-                        row_index: 0,
-                        group_id: 0,
-                        electrode_group: 0,
-                        label: std::ptr::null(),
-                        raw_data_type: std::ptr::null(),
-                        unit: std::ptr::null(),
-                        exponent: 0,
-                        ad_zero: 0,
-                        tick: 0,
-                        conversion_factor: 0,
-                        adc_bits: 0,
-                        high_pass_filter_type: std::ptr::null(),
-                        high_pass_filter_cutoff: std::ptr::null(),
-                        high_pass_filter_order: 0,
-                        low_pass_filter_type: std::ptr::null(),
-                        low_pass_filter_cutoff: std::ptr::null(),
-                        low_pass_filter_order: 0,
-                    }; sys::MAX_CHANNELS as usize],
-                },
-                has_digital: false,
-                digital: sys::AnalogStream {
-                    label: [0; sys::ANALOG_LABEL_STRING_LEN as usize],
-                    n_channels: 0,
-                    channel_data_dataset: 0,
-                    datalen: 0,
-                    info_channels: [sys::InfoChannel {
-                        channel_id: 0,
-                        row_index: 0,
-                        group_id: 0,
-                        electrode_group: 0,
-                        label: std::ptr::null(),
-                        raw_data_type: std::ptr::null(),
-                        unit: std::ptr::null(),
-                        exponent: 0,
-                        ad_zero: 0,
-                        tick: 0,
-                        conversion_factor: 0,
-                        adc_bits: 0,
-                        high_pass_filter_type: std::ptr::null(),
-                        high_pass_filter_cutoff: std::ptr::null(),
-                        high_pass_filter_order: 0,
-                        low_pass_filter_type: std::ptr::null(),
-                        low_pass_filter_cutoff: std::ptr::null(),
-                        low_pass_filter_order: 0,
-                    }; sys::MAX_CHANNELS as usize],
-                },
+                n_analogs: 0,
+                analogs: std::ptr::null_mut(),
+                raw_data_index: 0,
+                n_digitals: 0,
+                digital_indices: std::ptr::null_mut(),
                 n_events: 0,
-                event_entities: [0; sys::MAX_EVENT_STREAMS as usize],
+                event_entities: std::ptr::null_mut(),
+                event_ids: std::ptr::null_mut(),
+                n_info_events: 0,
+                info_events: std::ptr::null_mut(),
                 peaks_group: 0,
+                peak_run: [0; sys::PEAK_RUN_STRING_LEN as usize],
+                peaks_path: [0; sys::MAX_GROUP_STRING_LEN as usize],
             },
         }
     }
 }
 
 impl Phase {
+    /// Open the file with read and write access
     pub fn open(filename: &str) -> Result<Self, Error> {
+        Self::open_with_mode(filename, false)
+    }
+
+    /// Open the file without write access and without locking it, so that
+    /// more processes can read it at the same time. Any `set_*` method will
+    /// return `SpikeError::PhaseIsReadOnly`
+    pub fn open_read_only(filename: &str) -> Result<Self, Error> {
+        Self::open_with_mode(filename, true)
+    }
+
+    fn open_with_mode(filename: &str, read_only: bool) -> Result<Self, Error> {
         let mut phase = Self::default();
         phase.filename = filename.to_string();
         let cfilename = CString::new(filename).unwrap();
 
-        let res = unsafe { sys::phase_open(phase_ptr!(phase), cfilename.as_ptr()) };
+        let res = unsafe { sys::phase_open(phase_ptr!(phase), cfilename.as_ptr(), read_only) };
 
         match Error::from_phaseh5_error(res) {
             Ok(()) => {
-                for i in 0..phase.phase.raw_data.n_channels as usize {
-                    unsafe {
-                        phase.labels_map.insert(
-                            CStr::from_ptr(phase.phase.raw_data.info_channels[i].label)
-                                .to_str()
-                                .expect("Failed to convert the CStr")
-                                .to_string(),
-                            i,
-                        );
-                    }
-                }
+                phase.update_labels_map();
                 Ok(phase)
             }
             Err(err) => {
@@ -571,6 +738,116 @@ impl Phase {
         }
     }
 
+    /// Returns the date of the recording as saved by the acquisition software
+    pub fn date(&self) -> String {
+        unsafe { CStr::from_ptr(self.phase.date.as_ptr()) }
+            .to_string_lossy()
+            .to_string()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.phase.read_only
+    }
+
+    /// Rebuild the map from channel labels to indices of the raw data stream
+    fn update_labels_map(&mut self) {
+        self.labels_map = Self::stream_labels(self.raw_stream())
+            .into_iter()
+            .enumerate()
+            .map(|(i, label)| (label, i))
+            .collect();
+    }
+
+    /// Returns a slice over an array allocated by the C library, that could
+    /// be still unallocated
+    unsafe fn c_array<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+        if ptr.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(ptr, len)
+        }
+    }
+
+    fn analogs(&self) -> &[sys::AnalogStream] {
+        unsafe { Self::c_array(self.phase.analogs, self.phase.n_analogs) }
+    }
+
+    fn event_ids(&self) -> &[i32] {
+        unsafe { Self::c_array(self.phase.event_ids, self.phase.n_events as usize) }
+    }
+
+    fn info_events(&self) -> &[sys::InfoEvent] {
+        unsafe { Self::c_array(self.phase.info_events, self.phase.n_info_events) }
+    }
+
+    fn digital_indices(&self) -> &[usize] {
+        unsafe { Self::c_array(self.phase.digital_indices, self.phase.n_digitals) }
+    }
+
+    fn info_channels(stream: &sys::AnalogStream) -> &[sys::InfoChannel] {
+        unsafe { Self::c_array(stream.info_channels, stream.n_channels as usize) }
+    }
+
+    fn raw_stream(&self) -> &sys::AnalogStream {
+        &self.analogs()[self.phase.raw_data_index]
+    }
+
+    fn digital_stream(&self, index: usize) -> &sys::AnalogStream {
+        &self.analogs()[self.digital_indices()[index]]
+    }
+
+    fn is_digital_stream(&self, index: usize) -> bool {
+        self.digital_indices().contains(&index)
+    }
+
+    /// Index of the not digital analog stream labeled `stream`, if exists
+    fn stream_index(&self, stream: &str) -> Option<usize> {
+        (0..self.phase.n_analogs).find(|i| {
+            !self.is_digital_stream(*i) && Self::stream_label(&self.analogs()[*i]) == stream
+        })
+    }
+
+    fn stream_label(stream: &sys::AnalogStream) -> String {
+        unsafe { CStr::from_ptr(stream.label.as_ptr()) }
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn stream_labels(stream: &sys::AnalogStream) -> Vec<String> {
+        Self::info_channels(stream)
+            .iter()
+            .map(|info_channel| unsafe {
+                CStr::from_ptr(info_channel.label)
+                    .to_str()
+                    .expect("Failed to convert the CStr")
+                    .to_string()
+            })
+            .collect()
+    }
+
+    fn info_channel_string(ptr: *const c_char) -> String {
+        if ptr.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(ptr) }.to_string_lossy().to_string()
+        }
+    }
+
+    /// Returns the ADC conversion parameters of a channel
+    fn adc_info(info_channel: &sys::InfoChannel) -> ChannelInfo {
+        ChannelInfo {
+            exponent: info_channel.exponent,
+            ad_zero: info_channel.ad_zero,
+            conversion_factor: info_channel.conversion_factor,
+            ..Default::default()
+        }
+    }
+
+    /// Convert the ADC values of a channel to physical units
+    fn convert_adc(info_channel: &sys::InfoChannel, data: &[i32]) -> Vec<f32> {
+        Self::adc_info(info_channel).adc_to_physical(data)
+    }
+
     pub fn events_len(&self, index: usize) -> usize {
         let mut dims = 0u64;
         unsafe {
@@ -614,28 +891,52 @@ impl PhaseHandler for Phase {
         ret
     }
 
+    fn recording_start(&self) -> Option<DateTime> {
+        if self.phase.date_in_ticks > 0 {
+            Some(DateTime::from_dotnet_ticks(self.phase.date_in_ticks as i64))
+        } else {
+            None
+        }
+    }
+
+    fn channel_info(&self, channel: &str) -> Result<ChannelInfo, SpikeError> {
+        let index = match self.labels_map.get(channel) {
+            Some(index) => *index,
+            None => return Err(SpikeError::RawDataLabelNotFound),
+        };
+
+        let info_channel = &Self::info_channels(self.raw_stream())[index];
+        Ok(ChannelInfo {
+            label: Self::info_channel_string(info_channel.label),
+            channel_id: info_channel.channel_id,
+            row_index: info_channel.row_index,
+            group_id: info_channel.group_id,
+            electrode_group: info_channel.electrode_group,
+            raw_data_type: Self::info_channel_string(info_channel.raw_data_type),
+            unit: Self::info_channel_string(info_channel.unit),
+            exponent: info_channel.exponent,
+            ad_zero: info_channel.ad_zero,
+            tick: info_channel.tick as i64,
+            conversion_factor: info_channel.conversion_factor as i64,
+            adc_bits: info_channel.adc_bits,
+            high_pass_filter_type: Self::info_channel_string(info_channel.high_pass_filter_type),
+            high_pass_filter_cutoff: Self::info_channel_string(
+                info_channel.high_pass_filter_cutoff,
+            ),
+            high_pass_filter_order: info_channel.high_pass_filter_order,
+            low_pass_filter_type: Self::info_channel_string(info_channel.low_pass_filter_type),
+            low_pass_filter_cutoff: Self::info_channel_string(info_channel.low_pass_filter_cutoff),
+            low_pass_filter_order: info_channel.low_pass_filter_order,
+        })
+    }
+
     fn raw_data(
         &self,
         channel: &str,
         start: Option<usize>,
         end: Option<usize>,
     ) -> Result<Vec<f32>, SpikeError> {
-        let actual_start = match start {
-            Some(val) => val,
-            None => 0,
-        };
-        let actual_end = match end {
-            Some(val) => val,
-            None => self.datalen() - 1,
-        };
-
-        if actual_start >= actual_end {
-            return Err(SpikeError::RawDataStartIsAfterEnd);
-        }
-
-        if actual_end >= self.datalen() {
-            return Err(SpikeError::RawDataOutOfBounds);
-        }
+        let (actual_start, actual_end) = check_range(start, end, self.datalen())?;
 
         if !self.labels_map.contains_key(channel) {
             return Err(SpikeError::RawDataLabelNotFound);
@@ -656,20 +957,10 @@ impl PhaseHandler for Phase {
         });
 
         match res {
-            Ok(()) => {
-                let conversion_factor = self.phase.raw_data.info_channels[index].conversion_factor
-                    as f32
-                    * f32::powf(
-                        10f32,
-                        self.phase.raw_data.info_channels[index].exponent as f32,
-                    );
-                let offset = self.phase.raw_data.info_channels[index].ad_zero;
-
-                Ok(ret
-                    .iter()
-                    .map(|x| (*x - offset) as f32 * conversion_factor)
-                    .collect())
-            }
+            Ok(()) => Ok(Self::convert_adc(
+                &Self::info_channels(self.raw_stream())[index],
+                &ret,
+            )),
             Err(err) => Err(err.into()),
         }
     }
@@ -680,6 +971,10 @@ impl PhaseHandler for Phase {
         start: Option<usize>,
         data: &[f32],
     ) -> Result<(), SpikeError> {
+        if self.is_read_only() {
+            return Err(SpikeError::PhaseIsReadOnly);
+        }
+
         let actual_start = match start {
             Some(val) => val,
             None => 0,
@@ -687,7 +982,7 @@ impl PhaseHandler for Phase {
 
         let actual_end = actual_start + data.len();
 
-        if actual_end >= self.datalen() {
+        if actual_end > self.datalen() {
             return Err(SpikeError::SetRawDataOutOfBounds);
         }
 
@@ -697,16 +992,9 @@ impl PhaseHandler for Phase {
 
         let index = self.labels_map[channel];
 
-        let conversion_factor = self.phase.raw_data.info_channels[index].conversion_factor as f32
-            * f32::powf(
-                10f32,
-                self.phase.raw_data.info_channels[index].exponent as f32,
-            );
-        let offset = self.phase.raw_data.info_channels[index].ad_zero;
-        let buf: Vec<i32> = data
-            .iter()
-            .map(|x| (*x / conversion_factor) as i32 + offset)
-            .collect();
+        let info_channel = &Self::info_channels(self.raw_stream())[index];
+        // rounded to the nearest ADC step, not truncated
+        let buf = Self::adc_info(info_channel).physical_to_adc(data);
 
         let res = unsafe {
             sys::set_raw_data(
@@ -721,55 +1009,173 @@ impl PhaseHandler for Phase {
         Ok(Error::from_phaseh5_error(res)?)
     }
 
-    fn n_digitals(&self) -> usize {
-        if self.phase.has_digital {
-            1
-        } else {
-            0
+    fn raw_block(
+        &self,
+        channels: &[String],
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<RawBlock, SpikeError> {
+        let (actual_start, actual_end) = check_range(start, end, self.datalen())?;
+
+        let mut rows = Vec::with_capacity(channels.len());
+        for channel in channels {
+            match self.labels_map.get(channel) {
+                Some(index) => rows.push(*index),
+                None => return Err(SpikeError::RawDataLabelNotFound),
+            }
+        }
+
+        // the rows are read at once in the order of the file
+        let mut indices = rows.clone();
+        indices.sort_unstable();
+        indices.dedup();
+
+        let n_samples = actual_end - actual_start;
+        let mut buf = vec![0i32; indices.len() * n_samples];
+
+        if !indices.is_empty() {
+            let res = unsafe {
+                sys::raw_data_block(
+                    phase_ptr!(self),
+                    indices.as_ptr(),
+                    indices.len(),
+                    actual_start,
+                    actual_end,
+                    buf.as_mut_ptr(),
+                )
+            };
+            Error::from_phaseh5_error(res)?;
+        }
+
+        let info_channels = Self::info_channels(self.raw_stream());
+        let mut data = Vec::with_capacity(rows.len() * n_samples);
+        for index in rows {
+            let position = indices.partition_point(|x| *x < index);
+            data.extend(Self::convert_adc(
+                &info_channels[index],
+                &buf[position * n_samples..(position + 1) * n_samples],
+            ));
         }
+
+        RawBlock::new(channels.to_vec(), actual_start, n_samples, data)
     }
 
-    fn digital(
+    fn analog_streams(&self) -> Vec<String> {
+        (0..self.phase.n_analogs)
+            .filter(|i| !self.is_digital_stream(*i))
+            .map(|i| Self::stream_label(&self.analogs()[i]))
+            .collect()
+    }
+
+    fn raw_data_stream(&self) -> String {
+        Self::stream_label(self.raw_stream())
+    }
+
+    fn set_raw_data_stream(&mut self, stream: &str) -> Result<(), SpikeError> {
+        let index = match self.stream_index(stream) {
+            Some(index) => index,
+            None => return Err(SpikeError::AnalogStreamNotFound),
+        };
+
+        let res = unsafe { sys::set_raw_data_stream(phase_ptr!(self), index) };
+        Error::from_phaseh5_error(res)?;
+        self.update_labels_map();
+        Ok(())
+    }
+
+    fn analog_labels(&self, stream: &str) -> Result<Vec<String>, SpikeError> {
+        match self.stream_index(stream) {
+            Some(index) => Ok(Self::stream_labels(&self.analogs()[index])),
+            None => Err(SpikeError::AnalogStreamNotFound),
+        }
+    }
+
+    fn analog_data(
         &self,
-        index: usize,
+        stream: &str,
+        channel: &str,
         start: Option<usize>,
         end: Option<usize>,
     ) -> Result<Vec<f32>, SpikeError> {
-        if index > 0 {
-            panic!("digital: no more than one index can be processed atm");
-        }
+        let stream_index = match self.stream_index(stream) {
+            Some(index) => index,
+            None => return Err(SpikeError::AnalogStreamNotFound),
+        };
+        let analog_stream = &self.analogs()[stream_index];
 
-        if self.phase.has_digital == false {
-            return Err(SpikeError::DigitalNoDigitalPresent);
-        }
+        let (actual_start, actual_end) = check_range(start, end, analog_stream.datalen)?;
 
-        let actual_start = match start {
-            Some(val) => val,
-            None => 0,
+        let index = match Self::stream_labels(analog_stream)
+            .iter()
+            .position(|label| label == channel)
+        {
+            Some(index) => index,
+            None => return Err(SpikeError::RawDataLabelNotFound),
         };
 
-        let actual_end = match end {
-            Some(val) => val,
-            None => self.datalen() - 1,
-        };
+        let mut ret = vec![0; actual_end - actual_start];
 
-        if actual_start >= actual_end {
-            return Err(SpikeError::DigitalStartIsAfterEnd);
+        let res = Error::from_phaseh5_error(unsafe {
+            sys::analog_data(
+                phase_ptr!(self),
+                stream_index,
+                index,
+                actual_start,
+                actual_end,
+                ret.as_mut_ptr(),
+            )
+        });
+
+        match res {
+            Ok(()) => Ok(Self::convert_adc(
+                &Self::info_channels(analog_stream)[index],
+                &ret,
+            )),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn n_digitals(&self) -> usize {
+        self.phase.n_digitals
+    }
+
+    fn digital_labels(&self) -> Vec<String> {
+        (0..self.phase.n_digitals)
+            .map(|i| Self::stream_label(self.digital_stream(i)))
+            .collect()
+    }
+
+    fn digital(
+        &self,
+        index: usize,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<Vec<f32>, SpikeError> {
+        if self.n_digitals() == 0 {
+            return Err(SpikeError::DigitalNoDigitalPresent);
+        }
+
+        if index >= self.n_digitals() {
+            return Err(SpikeError::IndexOutOfRange);
         }
 
-        let mut buf = vec![0f32; actual_end - actual_start];
+        let datalen = self.digital_stream(index).datalen;
+        let (actual_start, actual_end) = check_digital_range(start, end, datalen)?;
+
+        let mut buf = vec![0i32; actual_end - actual_start];
 
         let res = unsafe {
             sys::digital(
                 phase_ptr!(self),
+                index,
                 actual_start,
                 actual_end,
-                buf.as_mut_ptr().cast(),
+                buf.as_mut_ptr(),
             )
         };
 
         match Error::from_phaseh5_error(res) {
-            Ok(()) => Ok(buf),
+            Ok(()) => Ok(buf.iter().map(|x| *x as f32).collect()),
             Err(err) => Err(err.into()),
         }
     }
@@ -780,12 +1186,16 @@ impl PhaseHandler for Phase {
         start: Option<usize>,
         data: &[f32],
     ) -> Result<(), SpikeError> {
-        if index > 0 {
-            panic!("set_digital: no more than one index can be processed atm");
+        if self.is_read_only() {
+            return Err(SpikeError::PhaseIsReadOnly);
+        }
+
+        if self.n_digitals() == 0 {
+            return Err(SpikeError::DigitalNoDigitalPresent);
         }
 
-        if self.phase.has_digital == false {
-            panic!("set_digital: no digital present");
+        if index >= self.n_digitals() {
+            return Err(SpikeError::IndexOutOfRange);
         }
 
         let actual_start = match start {
@@ -794,20 +1204,23 @@ impl PhaseHandler for Phase {
         };
         let actual_end = actual_start + data.len();
 
-        if actual_end >= self.datalen() {
-            panic!("set_digital: [end] is greater than [datalen]");
+        if actual_end > self.digital_stream(index).datalen {
+            return Err(SpikeError::SetDigitalOutOfBounds);
         }
 
         if actual_start >= actual_end {
-            panic!("set_digital: [start] is not before [end]");
+            return Err(SpikeError::DigitalStartIsAfterEnd);
         }
 
+        let buf: Vec<i32> = data.iter().map(|x| *x as i32).collect();
+
         let res = unsafe {
             sys::set_digital(
                 phase_ptr!(self),
+                index,
                 actual_start,
                 actual_end,
-                data.as_ptr().cast(),
+                buf.as_ptr(),
             )
         };
 
@@ -821,16 +1234,35 @@ impl PhaseHandler for Phase {
         self.phase.n_events as usize
     }
 
-    fn events(&self, index: usize) -> Result<Vec<i64>, SpikeError> {
+    fn events(&self, index: usize) -> Result<EventEntity, SpikeError> {
+        if index >= self.n_events() {
+            return Err(SpikeError::IndexOutOfRange);
+        }
         let len = self.events_len(index);
-        let mut data = vec![0i64; len];
+        let mut timestamps_us = vec![0i64; len];
+        let mut durations_us = vec![0i64; len];
 
-        let res = unsafe { sys::events(phase_ptr!(self), index, data.as_mut_ptr()) };
+        let res = unsafe { sys::events(phase_ptr!(self), index, timestamps_us.as_mut_ptr()) };
+        Error::from_phaseh5_error(res)?;
+        let res =
+            unsafe { sys::event_durations(phase_ptr!(self), index, durations_us.as_mut_ptr()) };
+        Error::from_phaseh5_error(res)?;
 
-        match Error::from_phaseh5_error(res) {
-            Ok(()) => Ok(data),
-            Err(err) => Err(err.into()),
-        }
+        let event_id = self.event_ids()[index];
+        let label = self
+            .info_events()
+            .iter()
+            .find(|info_event| info_event.event_id == event_id)
+            .map(|info_event| Self::info_channel_string(info_event.label))
+            .filter(|label| !label.is_empty())
+            .unwrap_or_else(|| format!("EventEntity_{event_id}"));
+
+        Ok(EventEntity {
+            label,
+            event_id,
+            timestamps_us,
+            durations_us,
+        })
     }
 
     fn peak_train(
@@ -859,29 +1291,18 @@ impl PhaseHandler for Phase {
                 let (samples, values) = (peak_train.samples, peak_train.values);
                 if start.is_none() && end.is_none() {
                     return Ok((samples, values));
-                } else {
-                    let start = start.unwrap_or(samples[0]);
-                    let end = end.unwrap_or(samples[samples.len() - 1]);
-                    let mut i_start = 0;
-                    let mut i_end = samples.len() - 1;
-
-                    for (i, val) in samples.iter().enumerate() {
-                        if *val >= start {
-                            i_start = i;
-                            break;
-                        }
-                    }
-                    for (i, val) in samples.iter().enumerate() {
-                        if *val >= end {
-                            i_end = i;
-                            break;
-                        }
-                    }
-                    Ok((
-                        samples[i_start..i_end].iter().map(|x| *x).collect(),
-                        values[i_start..i_end].iter().map(|x| *x).collect(),
-                    ))
                 }
+
+                // the train is sorted so the slice is found with two binary searches
+                let start = start.unwrap_or(0);
+                let end = end.unwrap_or(usize::MAX);
+                let i_start = samples.partition_point(|x| *x < start);
+                let i_end = samples.partition_point(|x| *x < end).max(i_start);
+
+                Ok((
+                    samples[i_start..i_end].to_vec(),
+                    values[i_start..i_end].to_vec(),
+                ))
             }
             Err(err) => Err(err.into()),
         }
@@ -894,190 +1315,1074 @@ impl PhaseHandler for Phase {
         end: Option<usize>,
         data: (Vec<usize>, Vec<f32>),
     ) -> Result<(), SpikeError> {
-        let channel_c = CString::new(channel).expect("peak_train_len: Failed to convert the CStr");
-        let peak_train_len = self.peak_train_len(channel);
+        if self.is_read_only() {
+            return Err(SpikeError::PhaseIsReadOnly);
+        }
 
-        if peak_train_len == 0 {
-            // there is no group yet. Create a peak_train and set the data to it
+        if data.0.len() != data.1.len() {
+            return Err(SpikeError::SetPeakTrainSamplesValuesDifferentLen);
+        }
 
-            let mut peak_train = match PeakTrain::from(data.0, data.1) {
-                Ok(peak_train) => peak_train,
-                Err(err) => {
-                    return Err(err.into());
-                }
-            };
+        let channel_c = CString::new(channel).expect("peak_train_len: Failed to convert the CStr");
 
-            let mut peak_train_c = peak_train.as_c_repr();
+        // keep the peaks outside of [start, end) and put the new ones between
+        let (samples, values) = self.peak_train(channel, None, None)?;
+        let start = start.unwrap_or(0);
+        let end = end.unwrap_or(usize::MAX);
+        let i_start = samples.partition_point(|x| *x < start);
+        let i_end = samples.partition_point(|x| *x < end).max(i_start);
 
-            let res = unsafe {
-                sys::set_peak_train(
-                    phase_ptr!(self),
-                    channel_c.as_ptr(),
-                    peak_train_ptr!(peak_train_c),
-                )
-            };
-            match Error::from_phaseh5_error(res) {
-                Ok(()) => Ok(()),
-                Err(err) => Err(err.into()),
-            }
-        } else {
-            let mut peak_train = PeakTrain::new(peak_train_len);
-            let mut peak_train_c = peak_train.as_c_repr();
-            let res = unsafe {
-                sys::peak_train(
-                    phase_ptr!(self),
-                    channel_c.as_ptr(),
-                    peak_train_ptr!(peak_train_c),
-                )
-            };
+        let mut new_samples = samples[..i_start].to_vec();
+        new_samples.extend_from_slice(&data.0);
+        new_samples.extend_from_slice(&samples[i_end..]);
 
-            match Error::from_phaseh5_error(res) {
-                Ok(()) => {
-                    // there is a group. Get the data and replace the new ones
-                    let (samples, values) = (peak_train.samples, peak_train.values);
-
-                    // the spikes train in present and contains data so the new data must be
-                    // inserted between start and stop positions
-                    let start = start.unwrap_or(samples[0]);
-                    let end = end.unwrap_or(samples[samples.len() - 1]);
-                    let mut i_start = 0;
-                    let mut i_end = samples.len() - 1;
-                    for (i, val) in samples.iter().enumerate() {
-                        if *val >= start {
-                            i_start = i;
-                            break;
-                        }
-                    }
-                    for (i, val) in samples.iter().enumerate() {
-                        if *val >= end {
-                            i_end = i;
-                            break;
-                        }
-                    }
+        let mut new_values = values[..i_start].to_vec();
+        new_values.extend_from_slice(&data.1);
+        new_values.extend_from_slice(&values[i_end..]);
 
-                    // get all values before start
-                    let before_start_samples = samples[0..i_start].to_vec();
-                    let before_start_values = values[0..i_start].to_vec();
-
-                    // get all values after end
-                    let after_end_samples = samples[i_end..].to_vec();
-                    let after_end_values = values[i_end..].to_vec();
-
-                    // join the values with data
-                    let mut new_samples = vec![];
-                    let mut new_values = vec![];
-
-                    new_samples.extend_from_slice(before_start_samples.as_slice());
-                    new_samples.extend_from_slice(data.0.as_slice());
-                    new_samples.extend_from_slice(after_end_samples.as_slice());
-
-                    new_values.extend_from_slice(before_start_values.as_slice());
-                    new_values.extend_from_slice(data.1.as_slice());
-                    new_values.extend_from_slice(after_end_values.as_slice());
-
-                    // create the new PeakTrain
-                    let mut new_peak_train = PeakTrain::new(new_samples.len());
-                    let mut new_peak_train_c = new_peak_train.as_c_repr();
-
-                    // try to write it
-                    let res = unsafe {
-                        sys::set_peak_train(
-                            phase_ptr!(self),
-                            channel_c.as_ptr(),
-                            peak_train_ptr!(new_peak_train_c),
-                        )
-                    };
-
-                    match Error::from_phaseh5_error(res) {
-                        Ok(()) => Ok(()),
-                        Err(err) => Err(err.into()),
-                    }
-                }
+        let mut peak_train = PeakTrain::from(new_samples, new_values)?;
+        let mut peak_train_c = peak_train.as_c_repr();
 
-                Err(Error::PeakTrainNoPeakGroup) => {
-                    // there is no group yet. Just pass the new data
-                    let res = unsafe {
-                        sys::set_peak_train(
-                            phase_ptr!(self),
-                            channel_c.as_ptr(),
-                            peak_train_ptr!(peak_train_c),
-                        )
-                    };
-                    match Error::from_phaseh5_error(res) {
-                        Ok(()) => Ok(()),
-                        Err(err) => Err(err.into()),
-                    }
-                }
+        let res = unsafe {
+            sys::set_peak_train(
+                phase_ptr!(self),
+                channel_c.as_ptr(),
+                peak_train_ptr!(peak_train_c),
+            )
+        };
 
-                Err(err) => Err(err.into()),
-            }
+        match Error::from_phaseh5_error(res) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(err.into()),
         }
     }
-}
-
-#[pyclass(unsendable)]
-pub struct PyPhase {
-    phase: Option<Phase>,
-}
 
-#[pymethods]
-impl PyPhase {
-    #[new]
-    pub fn new(filename: &str) -> Self {
-        PyPhase {
-            phase: Some(Phase::open(filename).expect(&format!("Failed to open {filename}"))),
+    fn peak_train_labels(&self) -> Result<Vec<String>, SpikeError> {
+        let mut n = 0usize;
+        let res = unsafe { sys::n_peak_trains(phase_ptr!(self), &mut n as *mut _) };
+        Error::from_phaseh5_error(res)?;
+
+        let mut labels = Vec::with_capacity(n);
+        for i in 0..n {
+            let mut label = [0 as c_char; sys::CHANNEL_LABEL_STRING_LEN as usize];
+            let res = unsafe { sys::peak_train_label(phase_ptr!(self), i, label.as_mut_ptr()) };
+            Error::from_phaseh5_error(res)?;
+            labels.push(Self::info_channel_string(label.as_ptr()));
         }
+        Ok(labels)
     }
 
-    pub fn datalen(&self) -> Option<usize> {
-        match &self.phase {
-            None => None,
-            Some(phase) => Some(phase.datalen()),
+    fn delete_peak_train(&mut self, channel: &str) -> Result<(), SpikeError> {
+        let channel_c =
+            CString::new(channel).expect("delete_peak_train: Failed to convert the CStr");
+        let res = unsafe { sys::delete_peak_train(phase_ptr!(self), channel_c.as_ptr()) };
+
+        match Error::from_phaseh5_error(res) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(err.into()),
         }
     }
 
-    pub fn sampling_frequency(&self) -> Option<f32> {
-        match &self.phase {
-            None => None,
-            Some(phase) => Some(phase.sampling_frequency()),
+    fn rename_peak_train(&mut self, channel: &str, new_channel: &str) -> Result<(), SpikeError> {
+        let channel_c =
+            CString::new(channel).expect("rename_peak_train: Failed to convert the CStr");
+        let new_channel_c =
+            CString::new(new_channel).expect("rename_peak_train: Failed to convert the CStr");
+        let res = unsafe {
+            sys::rename_peak_train(phase_ptr!(self), channel_c.as_ptr(), new_channel_c.as_ptr())
+        };
+
+        match Error::from_phaseh5_error(res) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(err.into()),
         }
     }
 
-    pub fn labels(&self) -> Option<Vec<String>> {
+    fn peak_train_metadata(&self, channel: &str) -> Result<Option<PeakTrainMetadata>, SpikeError> {
+        let channel_c =
+            CString::new(channel).expect("peak_train_metadata: Failed to convert the CStr");
+        let mut metadata = sys::PeakTrainMetadata {
+            threshold: 0f32,
+            multiplier: 0f32,
+            peak_duration: 0f32,
+            refractory_time: 0f32,
+            start: 0,
+            end: 0,
+            created: 0,
+            library_version: [0; sys::VERSION_STRING_LEN as usize],
+        };
+        let res = unsafe {
+            sys::peak_train_metadata(
+                phase_ptr!(self),
+                channel_c.as_ptr(),
+                &mut metadata as *mut _,
+            )
+        };
+
+        match Error::from_phaseh5_error(res) {
+            Ok(()) => Ok(Some(PeakTrainMetadata {
+                threshold: metadata.threshold,
+                multiplier: metadata.multiplier,
+                peak_duration: metadata.peak_duration,
+                refractory_time: metadata.refractory_time,
+                start: metadata.start as usize,
+                end: metadata.end as usize,
+                library_version: Self::info_channel_string(metadata.library_version.as_ptr()),
+                created: DateTime::from_unix_micros(metadata.created as i64),
+            })),
+            Err(Error::PeakTrainNoMetadata) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn set_peak_train_metadata(
+        &mut self,
+        channel: &str,
+        metadata: &PeakTrainMetadata,
+    ) -> Result<(), SpikeError> {
+        let channel_c =
+            CString::new(channel).expect("set_peak_train_metadata: Failed to convert the CStr");
+        let mut library_version = [0 as c_char; sys::VERSION_STRING_LEN as usize];
+        for (dst, src) in library_version
+            .iter_mut()
+            .zip(metadata.library_version.bytes())
+            .take(sys::VERSION_STRING_LEN as usize - 1)
+        {
+            *dst = src as c_char;
+        }
+        let metadata_c = sys::PeakTrainMetadata {
+            threshold: metadata.threshold,
+            multiplier: metadata.multiplier,
+            peak_duration: metadata.peak_duration,
+            refractory_time: metadata.refractory_time,
+            start: metadata.start as _,
+            end: metadata.end as _,
+            created: metadata.created.unix_micros() as _,
+            library_version,
+        };
+        let res = unsafe {
+            sys::set_peak_train_metadata(
+                phase_ptr!(self),
+                channel_c.as_ptr(),
+                &metadata_c as *const _,
+            )
+        };
+
+        match Error::from_phaseh5_error(res) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn peak_run(&self) -> String {
+        Self::info_channel_string(self.phase.peak_run.as_ptr())
+    }
+
+    fn peak_runs(&self) -> Result<Vec<String>, SpikeError> {
+        let mut n = 0usize;
+        let res = unsafe { sys::n_peak_runs(phase_ptr!(self), &mut n as *mut _) };
+        Error::from_phaseh5_error(res)?;
+
+        let mut runs = Vec::with_capacity(n);
+        for i in 0..n {
+            let mut run = [0 as c_char; sys::PEAK_RUN_STRING_LEN as usize];
+            let res = unsafe { sys::peak_run_name(phase_ptr!(self), i, run.as_mut_ptr()) };
+            Error::from_phaseh5_error(res)?;
+            runs.push(Self::info_channel_string(run.as_ptr()));
+        }
+        Ok(runs)
+    }
+
+    fn set_peak_run(&mut self, run: &str) -> Result<(), SpikeError> {
+        let run_c = CString::new(run).expect("set_peak_run: Failed to convert the CStr");
+        let res = unsafe { sys::set_peak_run(phase_ptr!(self), run_c.as_ptr()) };
+
+        match Error::from_phaseh5_error(res) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn copy_peak_run(&mut self, run: &str, new_run: &str) -> Result<(), SpikeError> {
+        let run_c = CString::new(run).expect("copy_peak_run: Failed to convert the CStr");
+        let new_run_c = CString::new(new_run).expect("copy_peak_run: Failed to convert the CStr");
+        let res =
+            unsafe { sys::copy_peak_run(phase_ptr!(self), run_c.as_ptr(), new_run_c.as_ptr()) };
+
+        match Error::from_phaseh5_error(res) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[pyclass(name = "ChannelInfo", get_all)]
+#[derive(Clone)]
+pub struct PyChannelInfo {
+    label: String,
+    channel_id: i32,
+    row_index: i32,
+    group_id: i32,
+    electrode_group: i32,
+    raw_data_type: String,
+    unit: String,
+    exponent: i32,
+    ad_zero: i32,
+    tick: i64,
+    conversion_factor: i64,
+    adc_bits: i32,
+    high_pass_filter_type: String,
+    high_pass_filter_cutoff: String,
+    high_pass_filter_order: i32,
+    low_pass_filter_type: String,
+    low_pass_filter_cutoff: String,
+    low_pass_filter_order: i32,
+}
+
+impl From<ChannelInfo> for PyChannelInfo {
+    fn from(info: ChannelInfo) -> Self {
+        PyChannelInfo {
+            label: info.label,
+            channel_id: info.channel_id,
+            row_index: info.row_index,
+            group_id: info.group_id,
+            electrode_group: info.electrode_group,
+            raw_data_type: info.raw_data_type,
+            unit: info.unit,
+            exponent: info.exponent,
+            ad_zero: info.ad_zero,
+            tick: info.tick,
+            conversion_factor: info.conversion_factor,
+            adc_bits: info.adc_bits,
+            high_pass_filter_type: info.high_pass_filter_type,
+            high_pass_filter_cutoff: info.high_pass_filter_cutoff,
+            high_pass_filter_order: info.high_pass_filter_order,
+            low_pass_filter_type: info.low_pass_filter_type,
+            low_pass_filter_cutoff: info.low_pass_filter_cutoff,
+            low_pass_filter_order: info.low_pass_filter_order,
+        }
+    }
+}
+
+#[pymethods]
+impl PyChannelInfo {
+    fn __repr__(&self) -> String {
+        format!(
+            "ChannelInfo(label={}, channel_id={}, unit={}, adc_bits={}, high_pass={} {} order {}, low_pass={} {} order {})",
+            self.label,
+            self.channel_id,
+            self.unit,
+            self.adc_bits,
+            self.high_pass_filter_type,
+            self.high_pass_filter_cutoff,
+            self.high_pass_filter_order,
+            self.low_pass_filter_type,
+            self.low_pass_filter_cutoff,
+            self.low_pass_filter_order,
+        )
+    }
+}
+
+#[pyclass(name = "EventEntity", get_all)]
+#[derive(Clone)]
+pub struct PyEventEntity {
+    label: String,
+    event_id: i32,
+    timestamps_us: Vec<i64>,
+    durations_us: Vec<i64>,
+}
+
+impl From<EventEntity> for PyEventEntity {
+    fn from(entity: EventEntity) -> Self {
+        PyEventEntity {
+            label: entity.label,
+            event_id: entity.event_id,
+            timestamps_us: entity.timestamps_us,
+            durations_us: entity.durations_us,
+        }
+    }
+}
+
+#[pymethods]
+impl PyEventEntity {
+    fn __repr__(&self) -> String {
+        format!(
+            "EventEntity(label={}, event_id={}, n_events={})",
+            self.label,
+            self.event_id,
+            self.timestamps_us.len(),
+        )
+    }
+}
+
+#[pyclass(name = "PeakTrainMetadata", get_all)]
+#[derive(Clone)]
+pub struct PyPeakTrainMetadata {
+    threshold: f32,
+    multiplier: f32,
+    peak_duration: f32,
+    refractory_time: f32,
+    start: usize,
+    end: usize,
+    library_version: String,
+    created: String,
+}
+
+impl From<PeakTrainMetadata> for PyPeakTrainMetadata {
+    fn from(metadata: PeakTrainMetadata) -> Self {
+        PyPeakTrainMetadata {
+            threshold: metadata.threshold,
+            multiplier: metadata.multiplier,
+            peak_duration: metadata.peak_duration,
+            refractory_time: metadata.refractory_time,
+            start: metadata.start,
+            end: metadata.end,
+            library_version: metadata.library_version,
+            created: metadata.created.to_string(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyPeakTrainMetadata {
+    fn __repr__(&self) -> String {
+        format!(
+            "PeakTrainMetadata(threshold={}, multiplier={}, peak_duration={}, refractory_time={}, start={}, end={}, library_version={}, created={})",
+            self.threshold,
+            self.multiplier,
+            self.peak_duration,
+            self.refractory_time,
+            self.start,
+            self.end,
+            self.library_version,
+            self.created,
+        )
+    }
+}
+
+#[pyclass(name = "Span")]
+#[derive(Clone, Default)]
+pub struct PySpan {
+    span: Span,
+}
+
+#[pymethods]
+impl PySpan {
+    #[staticmethod]
+    #[pyo3(signature = (start=None, end=None))]
+    pub fn samples(start: Option<usize>, end: Option<usize>) -> Self {
+        PySpan {
+            span: Span::new(start.map(Time::Samples), end.map(Time::Samples)),
+        }
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (start=None, end=None))]
+    pub fn seconds(start: Option<f64>, end: Option<f64>) -> Self {
+        PySpan {
+            span: Span::new(start.map(Time::Seconds), end.map(Time::Seconds)),
+        }
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (start=None, end=None))]
+    pub fn millis(start: Option<f64>, end: Option<f64>) -> Self {
+        PySpan {
+            span: Span::new(start.map(Time::millis), end.map(Time::millis)),
+        }
+    }
+
+    #[staticmethod]
+    pub fn all() -> Self {
+        PySpan { span: Span::all() }
+    }
+
+    pub fn to_samples(&self, sampling_frequency: f32) -> (Option<usize>, Option<usize>) {
+        self.span.to_samples(sampling_frequency)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Span(start={:?}, end={:?})", self.span.start, self.span.end)
+    }
+}
+
+#[pyclass(unsendable)]
+pub struct PyPhase {
+    phase: Option<Phase>,
+}
+
+#[pyclass(name = "RawBlock")]
+pub struct PyRawBlock {
+    block: RawBlock,
+}
+
+#[pymethods]
+impl PyRawBlock {
+    pub fn labels(&self) -> Vec<String> {
+        self.block.labels().to_vec()
+    }
+
+    pub fn start(&self) -> usize {
+        self.block.start()
+    }
+
+    pub fn n_channels(&self) -> usize {
+        self.block.n_channels()
+    }
+
+    pub fn n_samples(&self) -> usize {
+        self.block.n_samples()
+    }
+
+    /// the whole matrix, row-major
+    pub fn data(&self) -> Vec<f32> {
+        self.block.data().to_vec()
+    }
+
+    pub fn rows(&self) -> Vec<Vec<f32>> {
+        self.block.rows().map(|row| row.to_vec()).collect()
+    }
+
+    pub fn channel(&self, label: &str) -> Option<Vec<f32>> {
+        self.block.channel(label).map(|row| row.to_vec())
+    }
+
+    pub fn mean_row(&self) -> Vec<f32> {
+        self.block.mean_row()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "RawBlock(n_channels={}, n_samples={}, start={})",
+            self.block.n_channels(),
+            self.block.n_samples(),
+            self.block.start(),
+        )
+    }
+}
+
+/// Python iterator over the raw data chunks of a channel, see `RawDataChunks`.
+/// It keeps the position of the iteration and resumes a `RawDataChunks` on
+/// each step, since it can't hold a borrow of the phase. Errors are raised
+/// as `RuntimeError` and stop the iteration.
+#[pyclass(name = "RawDataChunks", unsendable)]
+pub struct PyRawDataChunks {
+    phase: Py<PyPhase>,
+    channel: String,
+    chunk_len: usize,
+    overlap: usize,
+    next_start: usize,
+}
+
+#[pymethods]
+impl PyRawDataChunks {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> PyResult<Option<(usize, Vec<f32>)>> {
+        let py = slf.py();
+        let py_phase = slf.phase.clone_ref(py);
+        let py_phase = py_phase.borrow(py);
+        let phase = match py_phase.phase.as_ref() {
+            Some(phase) => phase,
+            None => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "The phase is closed",
+                ))
+            }
+        };
+
+        let to_py_err =
+            |err: SpikeError| pyo3::exceptions::PyRuntimeError::new_err(format!("{err:?}"));
+        let mut chunks = phase
+            .raw_data_chunks(&slf.channel, slf.chunk_len, slf.overlap)
+            .map_err(to_py_err)?
+            .resume_at(slf.next_start);
+        let chunk = chunks.next();
+        slf.next_start = chunks.next_start();
+        chunk.transpose().map_err(to_py_err)
+    }
+}
+
+#[pymethods]
+impl PyPhase {
+    /// `mode` can be "r+" to open the file with write access or "r" to open
+    /// it read only
+    #[new]
+    #[pyo3(signature = (filename, mode="r+"))]
+    pub fn new(filename: &str, mode: &str) -> PyResult<Self> {
+        let phase = match mode {
+            "r+" => Phase::open(filename),
+            "r" => Phase::open_read_only(filename),
+            _ => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Invalid mode {mode}, expected \"r\" or \"r+\""
+                )))
+            }
+        };
+        Ok(PyPhase {
+            phase: Some(phase.expect(&format!("Failed to open {filename}"))),
+        })
+    }
+
+    pub fn is_read_only(&self) -> Option<bool> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.is_read_only()),
+        }
+    }
+
+    pub fn datalen(&self) -> Option<usize> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.datalen()),
+        }
+    }
+
+    pub fn sampling_frequency(&self) -> Option<f32> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.sampling_frequency()),
+        }
+    }
+
+    pub fn labels(&self) -> Option<Vec<String>> {
         match &self.phase {
             None => None,
             Some(phase) => Some(phase.labels()),
         }
     }
 
-    #[pyo3(signature = (channel, start=None, end=None))]
-    pub fn raw_data(
-        &self,
+    pub fn date(&self) -> Option<String> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.date()),
+        }
+    }
+
+    /// The start of the recording in ISO 8601 format
+    pub fn recording_start(&self) -> Option<String> {
+        match &self.phase {
+            None => None,
+            Some(phase) => phase.recording_start().map(|x| x.to_string()),
+        }
+    }
+
+    pub fn sample_to_seconds(&self, sample: usize) -> Option<f64> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.sample_to_seconds(sample)),
+        }
+    }
+
+    pub fn seconds_to_sample(&self, seconds: f64) -> Option<usize> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.seconds_to_sample(seconds)),
+        }
+    }
+
+    pub fn micros_to_sample(&self, micros: i64) -> Option<usize> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.micros_to_sample(micros)),
+        }
+    }
+
+    /// The absolute time of a sample in ISO 8601 format
+    pub fn sample_to_datetime(&self, sample: usize) -> Option<String> {
+        match &self.phase {
+            None => None,
+            Some(phase) => phase.sample_to_datetime(sample).map(|x| x.to_string()),
+        }
+    }
+
+    pub fn channel_info(&self, channel: &str) -> Option<PyChannelInfo> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.channel_info(channel) {
+                Ok(res) => Some(res.into()),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    #[pyo3(signature = (channel, start=None, end=None))]
+    pub fn raw_data(
+        &self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Option<Vec<f32>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.raw_data(channel, start, end) {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn raw_data_in(&self, channel: &str, span: PySpan) -> Option<Vec<f32>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.raw_data_in(channel, &span.span) {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    #[pyo3(signature = (channels, start=None, end=None))]
+    pub fn raw_block(
+        &self,
+        channels: Vec<String>,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Option<PyRawBlock> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.raw_block(&channels, start, end) {
+                Ok(block) => Some(PyRawBlock { block }),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn raw_block_in(&self, channels: Vec<String>, span: PySpan) -> Option<PyRawBlock> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.raw_block_in(&channels, &span.span) {
+                Ok(res) => Some(PyRawBlock { block: res }),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    #[pyo3(signature = (channel, chunk_len, overlap=0))]
+    pub fn raw_data_chunks(
+        slf: PyRef<'_, Self>,
+        channel: &str,
+        chunk_len: usize,
+        overlap: usize,
+    ) -> Option<PyRawDataChunks> {
+        match &slf.phase {
+            None => return None,
+            Some(phase) => {
+                if let Err(err) = phase.raw_data_chunks(channel, chunk_len, overlap) {
+                    println!("{err:?}");
+                    return None;
+                }
+            }
+        }
+        Some(PyRawDataChunks {
+            phase: slf.into(),
+            channel: channel.to_string(),
+            chunk_len,
+            overlap,
+            next_start: 0,
+        })
+    }
+
+    #[pyo3(signature = (channel, data, start=None))]
+    pub fn set_raw_data(
+        &mut self,
+        channel: &str,
+        data: Vec<f32>,
+        start: Option<usize>,
+    ) -> Option<bool> {
+        match &mut self.phase {
+            None => None,
+            Some(phase) => match phase.set_raw_data(channel, start, data[..].as_ref()) {
+                Ok(()) => Some(true),
+                Err(err) => {
+                    println!("{err:?}");
+                    Some(false)
+                }
+            },
+        }
+    }
+
+    /// `utc_offset` is the offset of the local time of the recording, as
+    /// "+HH:MM" or "-HH:MM"
+    #[pyo3(signature = (filename, session_description="", utc_offset="+00:00"))]
+    pub fn export_nwb(
+        &self,
+        filename: &str,
+        session_description: &str,
+        utc_offset: &str,
+    ) -> Option<bool> {
+        match &self.phase {
+            None => None,
+            Some(phase) => {
+                match nwb::export_nwb(phase, filename, session_description, utc_offset) {
+                    Ok(()) => Some(true),
+                    Err(err) => {
+                        println!("{err:?}");
+                        Some(false)
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn export_mcs(&self, filename: &str) -> Option<bool> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match mcs::export_mcs(phase, filename) {
+                Ok(()) => Some(true),
+                Err(err) => {
+                    println!("{err:?}");
+                    Some(false)
+                }
+            },
+        }
+    }
+
+    pub fn crop(&self, filename: &str, start: usize, end: usize) -> Option<bool> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match mcs::crop_mcs(phase, filename, start, end) {
+                Ok(()) => Some(true),
+                Err(err) => {
+                    println!("{err:?}");
+                    Some(false)
+                }
+            },
+        }
+    }
+
+    pub fn split_by_digital(&self, digital: usize, filename_prefix: &str) -> Option<Vec<String>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match mcs::split_mcs(phase, digital, filename_prefix) {
+                Ok(filenames) => Some(filenames),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    #[pyo3(signature = (folder, channels=None, start=None, end=None))]
+    pub fn export_kilosort(
+        &self,
+        folder: &str,
+        channels: Option<Vec<String>>,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Option<Vec<String>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => {
+                match kilosort::export_kilosort(phase, folder, channels.as_deref(), start, end) {
+                    Ok(labels) => Some(labels),
+                    Err(err) => {
+                        println!("{err:?}");
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn export_spycode_peak_trains(
+        &self,
+        folder: &str,
+        experiment: &str,
+    ) -> Option<Vec<String>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match spycode::export_peak_trains(phase, folder, experiment) {
+                Ok(labels) => Some(labels),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn import_spycode_peak_trains(
+        &mut self,
+        folder: &str,
+        experiment: &str,
+    ) -> Option<Vec<String>> {
+        match &mut self.phase {
+            None => None,
+            Some(phase) => match spycode::import_peak_trains(phase, folder, experiment) {
+                Ok(labels) => Some(labels),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn analog_streams(&self) -> Option<Vec<String>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.analog_streams()),
+        }
+    }
+
+    pub fn raw_data_stream(&self) -> Option<String> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.raw_data_stream()),
+        }
+    }
+
+    pub fn set_raw_data_stream(&mut self, stream: &str) -> Option<bool> {
+        match &mut self.phase {
+            None => None,
+            Some(phase) => match phase.set_raw_data_stream(stream) {
+                Ok(()) => Some(true),
+                Err(err) => {
+                    println!("{err:?}");
+                    Some(false)
+                }
+            },
+        }
+    }
+
+    pub fn analog_labels(&self, stream: &str) -> Option<Vec<String>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.analog_labels(stream) {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    #[pyo3(signature = (stream, channel, start=None, end=None))]
+    pub fn analog_data(
+        &self,
+        stream: &str,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Option<Vec<f32>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.analog_data(stream, channel, start, end) {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn n_digitals(&self) -> Option<usize> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.n_digitals()),
+        }
+    }
+
+    pub fn digital_labels(&self) -> Option<Vec<String>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.digital_labels()),
+        }
+    }
+
+    #[pyo3(signature = (index, start=None, end=None))]
+    pub fn digital(
+        &self,
+        index: usize,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Option<Vec<f32>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.digital(index, start, end) {
+                Ok(ret) => Some(ret),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn digital_in(&self, index: usize, span: PySpan) -> Option<Vec<f32>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.digital_in(index, &span.span) {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    #[pyo3(signature = (index, data, start=None))]
+    pub fn set_digital(
+        &mut self,
+        index: usize,
+        data: Vec<f32>,
+        start: Option<usize>,
+    ) -> Option<bool> {
+        match &mut self.phase {
+            None => None,
+            Some(phase) => match phase.set_digital(index, start, data[..].as_ref()) {
+                Ok(()) => Some(true),
+                Err(err) => {
+                    println!("{err:?}");
+                    Some(false)
+                }
+            },
+        }
+    }
+
+    pub fn n_events(&self) -> Option<usize> {
+        match &self.phase {
+            None => None,
+            Some(phase) => Some(phase.n_events()),
+        }
+    }
+
+    pub fn events(&self, index: usize) -> Option<PyEventEntity> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.events(index) {
+                Ok(res) => Some(res.into()),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn event_samples(&self, index: usize) -> Option<Vec<usize>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.event_samples(index) {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn event_intervals(&self, index: usize) -> Option<Vec<(usize, usize)>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.event_intervals(index) {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    #[pyo3(signature = (channel, start=None, end=None))]
+    pub fn peak_train(
+        &self,
+        channel: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Option<(Vec<usize>, Vec<f32>)> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.peak_train(channel, start, end) {
+                Ok(ret) => Some(ret),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn peak_train_in(&self, channel: &str, span: PySpan) -> Option<(Vec<usize>, Vec<f32>)> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.peak_train_in(channel, &span.span) {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
+        }
+    }
+
+    #[pyo3(signature = (channel, data, start=None, end=None))]
+    pub fn set_peak_train(
+        &mut self,
+        channel: &str,
+        data: (Vec<usize>, Vec<f32>),
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Option<bool> {
+        match &mut self.phase {
+            None => None,
+            Some(phase) => match phase.set_peak_train(channel, start, end, data) {
+                Ok(()) => Some(true),
+                Err(err) => {
+                    println!("{err:?}");
+                    Some(false)
+                }
+            },
+        }
+    }
+
+    pub fn replace_peak_train_range(
+        &mut self,
         channel: &str,
-        start: Option<usize>,
-        end: Option<usize>,
-    ) -> Option<Vec<f32>> {
-        match &self.phase {
+        start: usize,
+        end: usize,
+        data: (Vec<usize>, Vec<f32>),
+    ) -> Option<bool> {
+        match &mut self.phase {
             None => None,
-            Some(phase) => match phase.raw_data(channel, start, end) {
-                Ok(res) => Some(res),
+            Some(phase) => match phase.replace_peak_train_range(channel, start, end, data) {
+                Ok(()) => Some(true),
                 Err(err) => {
                     println!("{err:?}");
-                    None
+                    Some(false)
                 }
             },
         }
     }
-    #[pyo3(signature = (channel, data, start=None))]
-    pub fn set_raw_data(
-        &mut self,
-        channel: &str,
-        data: Vec<f32>,
-        start: Option<usize>,
-    ) -> Option<bool> {
+
+    pub fn insert_peaks(&mut self, channel: &str, data: (Vec<usize>, Vec<f32>)) -> Option<bool> {
         match &mut self.phase {
             None => None,
-            Some(phase) => match phase.set_raw_data(channel, start, data[..].as_ref()) {
+            Some(phase) => match phase.insert_peaks(channel, data) {
                 Ok(()) => Some(true),
                 Err(err) => {
                     println!("{err:?}");
@@ -1087,24 +2392,62 @@ impl PyPhase {
         }
     }
 
-    pub fn n_digitals(&self) -> Option<usize> {
-        match &self.phase {
+    pub fn remove_peaks_in(&mut self, channel: &str, start: usize, end: usize) -> Option<usize> {
+        match &mut self.phase {
             None => None,
-            Some(phase) => Some(phase.n_digitals()),
+            Some(phase) => match phase.remove_peaks_in(channel, start, end) {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
         }
     }
 
-    #[pyo3(signature = (index, start=None, end=None))]
-    pub fn digital(
-        &self,
-        index: usize,
-        start: Option<usize>,
-        end: Option<usize>,
-    ) -> Option<Vec<f32>> {
+    /// `predicate` is called with the sample and the value of each peak and
+    /// the peaks for which it returns True are removed
+    pub fn remove_peaks_where(
+        &mut self,
+        channel: &str,
+        predicate: &Bound<'_, PyAny>,
+    ) -> Option<usize> {
+        let phase = self.phase.as_mut()?;
+        let mut call_err = None;
+        let res = phase.remove_peaks_where(channel, |sample, value| {
+            if call_err.is_some() {
+                return false;
+            }
+            match predicate
+                .call1((sample, value))
+                .and_then(|ret| ret.extract::<bool>())
+            {
+                Ok(ret) => ret,
+                Err(err) => {
+                    call_err = Some(err);
+                    false
+                }
+            }
+        });
+
+        if let Some(err) = call_err {
+            println!("{err:?}");
+            return None;
+        }
+        match res {
+            Ok(res) => Some(res),
+            Err(err) => {
+                println!("{err:?}");
+                None
+            }
+        }
+    }
+
+    pub fn peak_train_labels(&self) -> Option<Vec<String>> {
         match &self.phase {
             None => None,
-            Some(phase) => match phase.digital(index, start, end) {
-                Ok(ret) => Some(ret),
+            Some(phase) => match phase.peak_train_labels() {
+                Ok(res) => Some(res),
                 Err(err) => {
                     println!("{err:?}");
                     None
@@ -1113,16 +2456,10 @@ impl PyPhase {
         }
     }
 
-    #[pyo3(signature = (index, data, start=None))]
-    pub fn set_digital(
-        &mut self,
-        index: usize,
-        data: Vec<f32>,
-        start: Option<usize>,
-    ) -> Option<bool> {
+    pub fn delete_peak_train(&mut self, channel: &str) -> Option<bool> {
         match &mut self.phase {
             None => None,
-            Some(phase) => match phase.set_digital(index, start, data[..].as_ref()) {
+            Some(phase) => match phase.delete_peak_train(channel) {
                 Ok(()) => Some(true),
                 Err(err) => {
                     println!("{err:?}");
@@ -1132,24 +2469,44 @@ impl PyPhase {
         }
     }
 
-    pub fn n_events(&self) -> Option<usize> {
+    pub fn rename_peak_train(&mut self, channel: &str, new_channel: &str) -> Option<bool> {
+        match &mut self.phase {
+            None => None,
+            Some(phase) => match phase.rename_peak_train(channel, new_channel) {
+                Ok(()) => Some(true),
+                Err(err) => {
+                    println!("{err:?}");
+                    Some(false)
+                }
+            },
+        }
+    }
+
+    pub fn peak_train_metadata(&self, channel: &str) -> Option<PyPeakTrainMetadata> {
         match &self.phase {
             None => None,
-            Some(phase) => Some(phase.n_events()),
+            Some(phase) => match phase.peak_train_metadata(channel) {
+                Ok(res) => res.map(|metadata| metadata.into()),
+                Err(err) => {
+                    println!("{err:?}");
+                    None
+                }
+            },
         }
     }
 
-    #[pyo3(signature = (channel, start=None, end=None))]
-    pub fn peak_train(
-        &self,
-        channel: &str,
-        start: Option<usize>,
-        end: Option<usize>,
-    ) -> Option<(Vec<usize>, Vec<f32>)> {
+    pub fn peak_run(&self) -> Option<String> {
         match &self.phase {
             None => None,
-            Some(phase) => match phase.peak_train(channel, start, end) {
-                Ok(ret) => Some(ret),
+            Some(phase) => Some(phase.peak_run()),
+        }
+    }
+
+    pub fn peak_runs(&self) -> Option<Vec<String>> {
+        match &self.phase {
+            None => None,
+            Some(phase) => match phase.peak_runs() {
+                Ok(res) => Some(res),
                 Err(err) => {
                     println!("{err:?}");
                     None
@@ -1158,17 +2515,36 @@ impl PyPhase {
         }
     }
 
-    #[pyo3(signature = (channel, data, start=None, end=None))]
-    pub fn set_peak_train(
-        &mut self,
-        channel: &str,
-        data: (Vec<usize>, Vec<f32>),
-        start: Option<usize>,
-        end: Option<usize>,
-    ) -> Option<bool> {
+    pub fn set_peak_run(&mut self, run: &str) -> Option<bool> {
         match &mut self.phase {
             None => None,
-            Some(phase) => match phase.set_peak_train(channel, start, end, data) {
+            Some(phase) => match phase.set_peak_run(run) {
+                Ok(()) => Some(true),
+                Err(err) => {
+                    println!("{err:?}");
+                    Some(false)
+                }
+            },
+        }
+    }
+
+    pub fn copy_peak_run(&mut self, run: &str, new_run: &str) -> Option<bool> {
+        match &mut self.phase {
+            None => None,
+            Some(phase) => match phase.copy_peak_run(run, new_run) {
+                Ok(()) => Some(true),
+                Err(err) => {
+                    println!("{err:?}");
+                    Some(false)
+                }
+            },
+        }
+    }
+
+    pub fn clear_peak_trains(&mut self) -> Option<bool> {
+        match &mut self.phase {
+            None => None,
+            Some(phase) => match phase.clear_peak_trains() {
                 Ok(()) => Some(true),
                 Err(err) => {
                     println!("{err:?}");
@@ -1273,6 +2649,12 @@ pub fn lowess(data: Vec<f32>, span: f32) -> Vec<f32> {
 #[pymodule(name = "pycode")]
 fn pycode_rs_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyPhase>()?;
+    m.add_class::<PyChannelInfo>()?;
+    m.add_class::<PyEventEntity>()?;
+    m.add_class::<PyPeakTrainMetadata>()?;
+    m.add_class::<PyRawDataChunks>()?;
+    m.add_class::<PyRawBlock>()?;
+    m.add_class::<PySpan>()?;
     m.add_function(wrap_pyfunction!(py_init, m)?)?;
     m.add_function(wrap_pyfunction!(py_close, m)?)?;
     m.add_function(wrap_pyfunction!(compute_threshold, m)?)?;
@@ -1283,3 +2665,929 @@ fn pycode_rs_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(lowess, m)?)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spike_rs::layout::{ElectrodeKind, MeaLayout};
+    use spike_rs::types::{MemoryPhase, DEFAULT_PEAK_RUN};
+    use std::sync::Mutex;
+
+    /// the HDF5 library is not thread safe, so the tests run one at a time
+    static HDF5_LOCK: Mutex<()> = Mutex::new(());
+
+    const DATALEN: usize = 2000;
+    const DIGITAL_LABEL: &str = "Digital Data";
+    const SECOND_DIGITAL_LABEL: &str = "LED Digital Data";
+    const AUXILIARY_LABEL: &str = "Auxiliary Data";
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    fn sample_value(channel: usize, sample: usize) -> i32 {
+        (channel * 1000 + sample % 1000) as i32
+    }
+
+    fn info_channel(label: &CString, channel_id: i32, strings: &[CString; 3]) -> sys::InfoChannel {
+        sys::InfoChannel {
+            channel_id,
+            row_index: channel_id,
+            label: label.as_ptr(),
+            raw_data_type: strings[0].as_ptr(),
+            unit: strings[1].as_ptr(),
+            tick: 100,
+            conversion_factor: 1,
+            adc_bits: 24,
+            high_pass_filter_type: strings[2].as_ptr(),
+            high_pass_filter_cutoff: strings[2].as_ptr(),
+            low_pass_filter_type: strings[2].as_ptr(),
+            low_pass_filter_cutoff: strings[2].as_ptr(),
+            ..Default::default()
+        }
+    }
+
+    /// Write the analog stream `Stream_{index}` labeled `label`, with a
+    /// channel for each of `channels` and their samples concatenated in `data`
+    fn write_analog_stream(
+        fid: sys::hid_t,
+        index: usize,
+        label: &str,
+        subtype: Option<&str>,
+        channels: &[String],
+        data: &[i32],
+    ) -> Result<(), Error> {
+        let strings = [c("Int"), c("V"), c("")];
+        let path = format!("/Data/Recording_0/AnalogStream/Stream_{index}");
+        let group = c(&path);
+        let channel_strings: Vec<CString> = channels.iter().map(|x| c(x)).collect();
+        let info_channels: Vec<sys::InfoChannel> = channel_strings
+            .iter()
+            .enumerate()
+            .map(|(i, label)| info_channel(label, i as i32, &strings))
+            .collect();
+
+        unsafe {
+            Error::from_phaseh5_error(sys::h5_create_group(fid, group.as_ptr()))?;
+            Error::from_phaseh5_error(sys::h5_write_string_attribute(
+                fid,
+                group.as_ptr(),
+                c("Label").as_ptr(),
+                c(label).as_ptr(),
+            ))?;
+            if let Some(subtype) = subtype {
+                Error::from_phaseh5_error(sys::h5_write_string_attribute(
+                    fid,
+                    group.as_ptr(),
+                    c("DataSubType").as_ptr(),
+                    c(subtype).as_ptr(),
+                ))?;
+            }
+            Error::from_phaseh5_error(sys::h5_write_info_channels(
+                fid,
+                c(&format!("{path}/InfoChannel")).as_ptr(),
+                info_channels.as_ptr(),
+                info_channels.len(),
+            ))?;
+            let dims = [channels.len() as sys::hsize_t, DATALEN as sys::hsize_t];
+            Error::from_phaseh5_error(sys::h5_write_dataset(
+                fid,
+                c(&format!("{path}/ChannelData")).as_ptr(),
+                sys::h5_type_H5_TYPE_INT,
+                2,
+                dims.as_ptr(),
+                data.as_ptr().cast(),
+            ))
+        }
+    }
+
+    /// Write a file with the same structure of a Multi Channel Systems
+    /// recording: a raw data stream with a channel for each label, two
+    /// digital streams, an auxiliary stream and two event entities
+    fn write_phase(filename: &str, labels: &[String]) -> Result<(), Error> {
+        let fid = unsafe { sys::h5_create_file(c(filename).as_ptr()) };
+        if fid <= 0 {
+            return Err(Error::H5CreateFile);
+        }
+
+        unsafe {
+            Error::from_phaseh5_error(sys::h5_create_group(fid, c("/Data").as_ptr()))?;
+            Error::from_phaseh5_error(sys::h5_write_string_attribute(
+                fid,
+                c("/Data").as_ptr(),
+                c("Date").as_ptr(),
+                c("Tuesday, March 5, 2024").as_ptr(),
+            ))?;
+            Error::from_phaseh5_error(sys::h5_write_llong_attribute(
+                fid,
+                c("/Data").as_ptr(),
+                c("DateInTicks").as_ptr(),
+                638_452_453_310_000_000,
+            ))?;
+        }
+
+        // raw data stream
+        let data: Vec<i32> = (0..labels.len())
+            .flat_map(|channel| (0..DATALEN).map(move |sample| sample_value(channel, sample)))
+            .collect();
+        write_analog_stream(
+            fid,
+            0,
+            "Electrode Raw Data",
+            Some("Electrode"),
+            labels,
+            &data,
+        )?;
+
+        // digital streams, the first one recognized by its single channel
+        let digital: Vec<i32> = (0..DATALEN).map(|i| ((i / 100) % 2) as i32).collect();
+        write_analog_stream(fid, 1, DIGITAL_LABEL, None, &["D1".to_string()], &digital)?;
+        let digital: Vec<i32> = (0..DATALEN).map(|i| ((i / 250) % 2) as i32).collect();
+        write_analog_stream(
+            fid,
+            2,
+            SECOND_DIGITAL_LABEL,
+            Some("Digital"),
+            &["D1".to_string()],
+            &digital,
+        )?;
+
+        // auxiliary stream, with the opposite values of the raw data
+        let aux_labels = ["T1".to_string(), "T2".to_string()];
+        let data: Vec<i32> = (0..aux_labels.len())
+            .flat_map(|channel| (0..DATALEN).map(move |sample| -sample_value(channel, sample)))
+            .collect();
+        write_analog_stream(
+            fid,
+            3,
+            AUXILIARY_LABEL,
+            Some("Auxiliary"),
+            &aux_labels,
+            &data,
+        )?;
+
+        unsafe {
+            // events, timestamps on the first row and durations on the second
+            let events: [i64; 6] = [1_000, 2_000, 3_000, 100, 100, 100];
+            let dims = [2, 3];
+            Error::from_phaseh5_error(sys::h5_write_dataset(
+                fid,
+                c("/Data/Recording_0/EventStream/Stream_0/EventEntity_0").as_ptr(),
+                sys::h5_type_H5_TYPE_LLONG,
+                2,
+                dims.as_ptr(),
+                events.as_ptr().cast(),
+            ))?;
+            // the last event runs past the end of the data
+            let events: [i64; 4] = [50_000, 199_900, 0, 1_000];
+            let dims = [2, 2];
+            Error::from_phaseh5_error(sys::h5_write_dataset(
+                fid,
+                c("/Data/Recording_0/EventStream/Stream_0/EventEntity_1").as_ptr(),
+                sys::h5_type_H5_TYPE_LLONG,
+                2,
+                dims.as_ptr(),
+                events.as_ptr().cast(),
+            ))?;
+
+            Error::from_phaseh5_error(sys::h5_close_file(fid))
+        }
+    }
+
+    fn check_phase(name: &str, labels: Vec<String>) {
+        let _lock = HDF5_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        spike_c_init().unwrap();
+
+        let filename = std::env::temp_dir().join(format!("spike_rs_test_{name}.h5"));
+        let filename = filename.to_str().unwrap();
+        write_phase(filename, &labels).unwrap();
+
+        {
+            let mut phase = Phase::open(filename).unwrap();
+
+            assert_eq!(phase.datalen(), DATALEN);
+            assert_eq!(phase.sampling_frequency(), 10000f32);
+            assert!(phase.recording_start().is_some());
+
+            let mut phase_labels = phase.labels();
+            let mut expected_labels = labels.clone();
+            phase_labels.sort();
+            expected_labels.sort();
+            assert_eq!(phase_labels, expected_labels);
+
+            for (channel, label) in labels.iter().enumerate() {
+                let data = phase.raw_data(label, Some(100), Some(200)).unwrap();
+                let expected: Vec<f32> = (100..200)
+                    .map(|sample| sample_value(channel, sample) as f32)
+                    .collect();
+                assert_eq!(data, expected);
+                assert_eq!(phase.channel_info(label).unwrap().label, *label);
+            }
+
+            let mut block_labels = labels.clone();
+            block_labels.reverse();
+            let block = phase
+                .raw_block(&block_labels, Some(100), Some(200))
+                .unwrap();
+            assert_eq!(block.n_samples(), 100);
+            for label in labels.iter() {
+                let data = phase.raw_data(label, Some(100), Some(200)).unwrap();
+                assert_eq!(block.channel(label).unwrap(), data.as_slice());
+            }
+
+            let fs = phase.sampling_frequency() as f64;
+            let span = Span::seconds(100f64 / fs, 200f64 / fs);
+            for label in labels.iter() {
+                let data = phase.raw_data(label, Some(100), Some(200)).unwrap();
+                assert_eq!(phase.raw_data_in(label, &span).unwrap(), data);
+            }
+
+            assert_eq!(phase.n_digitals(), 2);
+            assert_eq!(
+                phase.digital_labels(),
+                vec![DIGITAL_LABEL.to_string(), SECOND_DIGITAL_LABEL.to_string()]
+            );
+            assert_eq!(phase.digital(0, Some(95), Some(105)).unwrap()[5], 1f32);
+            assert_eq!(phase.digital(1, Some(95), Some(105)).unwrap()[5], 0f32);
+            assert_eq!(phase.digital(1, Some(245), Some(255)).unwrap()[5], 1f32);
+            assert!(phase.digital(2, None, None).is_err());
+
+            // the auxiliary stream is listed and read, also as raw data
+            let raw_data_stream = phase.raw_data_stream();
+            assert_eq!(raw_data_stream, "Electrode Raw Data");
+            assert!(phase
+                .analog_streams()
+                .contains(&AUXILIARY_LABEL.to_string()));
+            let mut aux_labels = phase.analog_labels(AUXILIARY_LABEL).unwrap();
+            aux_labels.sort();
+            assert_eq!(aux_labels, vec!["T1".to_string(), "T2".to_string()]);
+            let expected: Vec<f32> = (100..200)
+                .map(|sample| -sample_value(1, sample) as f32)
+                .collect();
+            assert_eq!(
+                phase
+                    .analog_data(AUXILIARY_LABEL, "T2", Some(100), Some(200))
+                    .unwrap(),
+                expected
+            );
+            assert!(phase
+                .analog_data(AUXILIARY_LABEL, &labels[0], None, None)
+                .is_err());
+            assert!(phase.analog_labels("Missing Stream").is_err());
+            phase.set_raw_data_stream(AUXILIARY_LABEL).unwrap();
+            assert_eq!(phase.raw_data_stream(), AUXILIARY_LABEL);
+            assert_eq!(
+                phase.raw_data("T2", Some(100), Some(200)).unwrap(),
+                expected
+            );
+            assert!(phase.raw_data(&labels[0], None, None).is_err());
+            assert!(phase.set_raw_data_stream("Missing Stream").is_err());
+            phase.set_raw_data_stream(&raw_data_stream).unwrap();
+            assert_eq!(phase.labels().len(), labels.len());
+            let events = phase.events(0).unwrap();
+            assert_eq!(events.event_id, 0);
+            assert_eq!(events.timestamps_us, vec![1_000, 2_000, 3_000]);
+            assert_eq!(events.durations_us, vec![100, 100, 100]);
+            assert_eq!(phase.n_events(), 2);
+            assert_eq!(phase.event_samples(0).unwrap(), vec![10, 20, 30]);
+            assert_eq!(
+                phase.event_intervals(0).unwrap(),
+                vec![(10, 11), (20, 21), (30, 31)]
+            );
+            assert_eq!(phase.event_samples(1).unwrap(), vec![500, 1999]);
+            assert_eq!(
+                phase.event_intervals(1).unwrap(),
+                vec![(500, 501), (1999, DATALEN)]
+            );
+
+            // the paths built from labels too long are rejected, not truncated
+            let label = &labels[0];
+            phase
+                .set_peak_train(label, None, None, (vec![1], vec![1.]))
+                .unwrap();
+            let long_label = "L".repeat(300);
+            assert!(phase
+                .set_peak_train(&long_label, None, None, (vec![1], vec![1.]))
+                .is_err());
+            assert!(phase.rename_peak_train(label, &long_label).is_err());
+            assert!(phase.peak_train(label, None, None).is_ok());
+
+            // writing the peak train, also through the validated edits, drops
+            // its detection metadata
+            phase
+                .set_peak_train_metadata(label, &PeakTrainMetadata::default())
+                .unwrap();
+            assert!(phase.peak_train_metadata(label).unwrap().is_some());
+            phase
+                .set_peak_train(label, None, None, (vec![1, 2], vec![1., 2.]))
+                .unwrap();
+            assert_eq!(phase.peak_train_metadata(label).unwrap(), None);
+            phase
+                .set_peak_train_metadata(label, &PeakTrainMetadata::default())
+                .unwrap();
+            phase
+                .replace_peak_train_range(label, 0, 10, (vec![5], vec![3.]))
+                .unwrap();
+            assert_eq!(phase.peak_train_metadata(label).unwrap(), None);
+
+            // peak train edits are written and read back
+            phase
+                .set_peak_train(
+                    label,
+                    None,
+                    None,
+                    (vec![10, 20, 30, 40], vec![1., 2., 3., 4.]),
+                )
+                .unwrap();
+            phase
+                .replace_peak_train_range(label, 15, 35, (vec![16, 25], vec![6., 5.]))
+                .unwrap();
+            assert_eq!(
+                phase.peak_train(label, None, None).unwrap(),
+                (vec![10, 16, 25, 40], vec![1., 6., 5., 4.])
+            );
+            phase.insert_peaks(label, (vec![50], vec![7.])).unwrap();
+            assert_eq!(phase.remove_peaks_in(label, 0, 20).unwrap(), 2);
+            assert_eq!(
+                phase
+                    .remove_peaks_where(label, |_, value| value > 6.5)
+                    .unwrap(),
+                1
+            );
+            assert_eq!(
+                phase.peak_train(label, None, None).unwrap(),
+                (vec![25, 40], vec![5., 4.])
+            );
+
+            // peak trains are listed, renamed, deleted and cleared
+            phase
+                .set_peak_train(&labels[1], None, None, (vec![3], vec![1.]))
+                .unwrap();
+            let mut trains = phase.peak_train_labels().unwrap();
+            trains.sort();
+            let mut expected = vec![labels[0].clone(), labels[1].clone()];
+            expected.sort();
+            assert_eq!(trains, expected);
+            phase.rename_peak_train(&labels[1], "renamed").unwrap();
+            let mut trains = phase.peak_train_labels().unwrap();
+            trains.sort();
+            let mut expected = vec![labels[0].clone(), "renamed".to_string()];
+            expected.sort();
+            assert_eq!(trains, expected);
+            assert_eq!(
+                phase.peak_train("renamed", None, None).unwrap(),
+                (vec![3], vec![1.])
+            );
+            phase.delete_peak_train("renamed").unwrap();
+            assert_eq!(phase.peak_train_labels().unwrap(), vec![labels[0].clone()]);
+            assert!(phase.delete_peak_train("renamed").is_err());
+            phase.clear_peak_trains().unwrap();
+            assert!(phase.peak_train_labels().unwrap().is_empty());
+        }
+
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    /// Read a whole dataset with the generic reading functions, returning
+    /// its dimensions and data
+    fn read_dataset<T: h5::H5Type + Default>(fid: sys::hid_t, path: &str) -> (Vec<usize>, Vec<T>) {
+        let mut dims = [0 as sys::hsize_t; 4];
+        let mut rank = 0;
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_dataset_dims(fid, c(path).as_ptr(), 4, &mut rank, dims.as_mut_ptr())
+        })
+        .unwrap();
+        let dims: Vec<usize> = dims[..rank as usize].iter().map(|x| *x as usize).collect();
+        let mut data = vec![T::default(); dims.iter().product()];
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_read_dataset(fid, c(path).as_ptr(), T::TYPE, data.as_mut_ptr().cast())
+        })
+        .unwrap();
+        (dims, data)
+    }
+
+    fn read_attribute(fid: sys::hid_t, object: &str, name: &str) -> String {
+        let mut buf = vec![0 as c_char; 256];
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_read_string_attribute(
+                fid,
+                c(object).as_ptr(),
+                c(name).as_ptr(),
+                buf.as_mut_ptr(),
+                buf.len(),
+            )
+        })
+        .unwrap();
+        unsafe { CStr::from_ptr(buf.as_ptr()) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    /// Write a 1 dimensional dataset with the generic writing functions
+    fn write_vector<T: h5::H5Type>(fid: sys::hid_t, path: &str, data: &[T]) {
+        let dims = [data.len() as sys::hsize_t];
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_dataset(
+                fid,
+                c(path).as_ptr(),
+                T::TYPE,
+                1,
+                dims.as_ptr(),
+                data.as_ptr().cast(),
+            )
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn open_brw_v4() {
+        let _lock = HDF5_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        spike_c_init().unwrap();
+
+        let filename = std::env::temp_dir().join("spike_rs_test_brw.brw");
+        let filename = filename.to_str().unwrap();
+        let fid = unsafe { sys::h5_create_file(c(filename).as_ptr()) };
+        assert!(fid > 0);
+        let settings = r#"{"TimeConverter": {"FrameRate": 10000.0},
+            "ValueConverter": {"MaxAnalogValue": 2048, "MinAnalogValue": -2048,
+            "MaxDigitalValue": 2048, "MinDigitalValue": -2048}}"#;
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_string_attribute(
+                fid,
+                c("/").as_ptr(),
+                c("ExperimentSettings").as_ptr(),
+                c(settings).as_ptr(),
+            )
+        })
+        .unwrap();
+
+        // dense well, the frames of the electrodes (1, 1) and (2, 2)
+        write_vector(fid, "/Well_A1/StoredChIdxs", &[0i32, 65]);
+        let raw: Vec<i32> = (0..5).flat_map(|i| [i, -i]).collect();
+        write_vector(fid, "/Well_A1/Raw", &raw);
+
+        // compressed well, two chunks with a range of samples each
+        let dims = [2 as sys::hsize_t, 2];
+        let toc = [0i64, 5, 5, 10];
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_dataset(
+                fid,
+                c("/TOC").as_ptr(),
+                sys::h5_type_H5_TYPE_LLONG,
+                2,
+                dims.as_ptr(),
+                toc.as_ptr().cast(),
+            )
+        })
+        .unwrap();
+        write_vector(fid, "/Well_B1/StoredChIdxs", &[0i32, 65]);
+        let sparse = [
+            65, 0, 10, 0, 1, 0, 0, 0, 3, 0, 0, 0, 7, 8, // chunk 0
+            0, 0, 9, 0, 6, 0, 0, 0, 7, 0, 0, 0, -5, // chunk 1
+        ];
+        write_vector(fid, "/Well_B1/EventsBasedSparseRaw", &sparse);
+        write_vector(fid, "/Well_B1/EventsBasedSparseRawTOC", &[0i64, 14]);
+        Error::from_phaseh5_error(unsafe { sys::h5_close_file(fid) }).unwrap();
+
+        {
+            let phase = brw::BrwPhase::open(filename).unwrap();
+            assert_eq!(phase.sampling_frequency(), 10000f32);
+            assert_eq!(phase.datalen(), 5);
+            assert_eq!(phase.labels(), vec!["1_1".to_string(), "2_2".to_string()]);
+            assert_eq!(
+                phase.raw_data("2_2", Some(1), Some(3)).unwrap(),
+                vec![-1e-6, -2e-6]
+            );
+            let block = phase
+                .raw_block(&["2_2".to_string(), "1_1".to_string()], Some(3), None)
+                .unwrap();
+            assert_eq!(block.channel("1_1").unwrap(), &[3e-6, 4e-6]);
+            let info = phase.channel_info("1_1").unwrap();
+            assert_eq!((info.conversion_factor, info.exponent), (1, -6));
+
+            let phase = brw::BrwPhase::open_well(filename, "Well_B1").unwrap();
+            assert_eq!(phase.datalen(), 10);
+            assert_eq!(
+                phase.raw_data("2_2", Some(0), Some(4)).unwrap(),
+                vec![0., 7e-6, 8e-6, 0.]
+            );
+            assert_eq!(
+                phase.raw_data("1_1", Some(4), Some(8)).unwrap(),
+                vec![0., 0., -5e-6, 0.]
+            );
+        }
+
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn brw_column_hyperslab_is_bounded() {
+        // 4096 channels, 100 samples of the channel 130 from the sample 1000
+        let (first, stride, count) = brw::column_hyperslab(130, 4096, 1000, 1100);
+        assert_eq!(count, 100);
+        let selected: Vec<usize> = (0..count).map(|i| first + i * stride).collect();
+        assert!(selected.iter().all(|x| x % 4096 == 130));
+        assert_eq!(selected[0] / 4096, 1000);
+        assert_eq!(selected[count - 1] / 4096, 1099);
+    }
+
+    #[test]
+    fn nwb_datetime_with_offset() {
+        let datetime = DateTime::from_unix_micros(1_709_634_131_000_000);
+        assert_eq!(
+            nwb::offset_datetime(&datetime, "+01:00").unwrap(),
+            "2024-03-05T10:22:11.000000+01:00"
+        );
+        assert_eq!(
+            nwb::offset_datetime(&datetime, "-05:30").unwrap(),
+            "2024-03-05T10:22:11.000000-05:30"
+        );
+        for offset in [
+            "", "Z", "+1:00", "01:00", "+01:60", "+15:00", "++1:00", "+01-00",
+        ] {
+            assert!(matches!(
+                nwb::offset_datetime(&datetime, offset),
+                Err(SpikeError::NwbInvalidUtcOffset(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn export_nwb_round_trip() {
+        let _lock = HDF5_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        spike_c_init().unwrap();
+
+        let labels = MeaLayout::mea60().labels_of_kind(ElectrodeKind::Recording);
+        let filename = std::env::temp_dir().join("spike_rs_test_nwb_phase.h5");
+        let filename = filename.to_str().unwrap();
+        let nwb_filename = std::env::temp_dir().join("spike_rs_test_nwb.nwb");
+        let nwb_filename = nwb_filename.to_str().unwrap();
+        write_phase(filename, &labels).unwrap();
+
+        {
+            let mut phase = Phase::open(filename).unwrap();
+            phase
+                .set_peak_train(&labels[0], None, None, (vec![10, 20, 30], vec![1., 2., 3.]))
+                .unwrap();
+            phase
+                .set_peak_train(&labels[2], None, None, (vec![5], vec![4.]))
+                .unwrap();
+            nwb::export_nwb(&phase, nwb_filename, "round trip", "+01:00").unwrap();
+
+            let fid = unsafe { sys::h5_open_file(c(nwb_filename).as_ptr()) };
+            assert!(fid > 0);
+            assert_eq!(read_attribute(fid, "/", "neurodata_type"), "NWBFile");
+            assert_eq!(read_attribute(fid, "/", "nwb_version"), nwb::NWB_VERSION);
+
+            // raw data, a row for each sample
+            let (dims, data) = read_dataset::<f32>(fid, "/acquisition/ElectricalSeries/data");
+            assert_eq!(dims, vec![DATALEN, labels.len()]);
+            for (channel, label) in labels.iter().enumerate() {
+                let column: Vec<f32> = data
+                    .iter()
+                    .skip(channel)
+                    .step_by(labels.len())
+                    .copied()
+                    .collect();
+                assert_eq!(column, phase.raw_data(label, None, None).unwrap());
+            }
+            let (_, ids) = read_dataset::<i64>(fid, "/general/extracellular_ephys/electrodes/id");
+            assert_eq!(ids.len(), labels.len());
+
+            // ttl intervals
+            let intervals =
+                analysis::digital::get_digital_intervals(&phase.digital(0, None, None).unwrap());
+            let (_, start_times) = read_dataset::<f64>(fid, "/intervals/TTL_0/start_time");
+            let expected: Vec<f64> = intervals
+                .iter()
+                .map(|(start, _)| phase.sample_to_seconds(*start))
+                .collect();
+            assert_eq!(start_times, expected);
+
+            // units
+            let (_, spike_times) = read_dataset::<f64>(fid, "/units/spike_times");
+            let expected: Vec<f64> = [10, 20, 30, 5]
+                .iter()
+                .map(|sample| phase.sample_to_seconds(*sample))
+                .collect();
+            assert_eq!(spike_times, expected);
+            let (_, index) = read_dataset::<u64>(fid, "/units/spike_times_index");
+            assert_eq!(index, vec![3, 4]);
+            let (_, electrodes) = read_dataset::<i32>(fid, "/units/electrodes");
+            assert_eq!(electrodes, vec![0, 2]);
+
+            Error::from_phaseh5_error(unsafe { sys::h5_close_file(fid) }).unwrap();
+        }
+
+        std::fs::remove_file(filename).unwrap();
+        std::fs::remove_file(nwb_filename).unwrap();
+    }
+
+    #[test]
+    fn export_mcs_round_trip() {
+        let _lock = HDF5_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        spike_c_init().unwrap();
+
+        let filename = std::env::temp_dir().join("spike_rs_test_mcs_export.h5");
+        let filename = filename.to_str().unwrap();
+        let start = DateTime::from_unix_micros(1_709_634_131_000_000);
+
+        let mut source = MemoryPhase::new(10000f32, DATALEN);
+        source.set_recording_start(Some(start));
+        // a channel with the ADC conversion and one that needs a fitted one
+        source
+            .add_raw_data("12", (0..DATALEN).map(|x| x as f32 * 1e-7).collect())
+            .unwrap();
+        source.set_channel_info(ChannelInfo {
+            label: "12".to_string(),
+            channel_id: 7,
+            unit: "V".to_string(),
+            exponent: -7,
+            conversion_factor: 1,
+            ..Default::default()
+        });
+        source
+            .add_raw_data(
+                "13",
+                (0..DATALEN).map(|x| (x % 10) as f32 * -2e-5).collect(),
+            )
+            .unwrap();
+        let digital: Vec<f32> = (0..DATALEN).map(|x| ((x / 100) % 2) as f32).collect();
+        source.add_digital("Digital Data", digital.clone()).unwrap();
+        source.add_events(EventEntity {
+            label: "Stimulus".to_string(),
+            event_id: 3,
+            timestamps_us: vec![1_000, 2_000],
+            durations_us: vec![100, 200],
+        });
+        source
+            .set_peak_train("13", None, None, (vec![10, 20], vec![-1e-4, -2e-4]))
+            .unwrap();
+        let metadata = PeakTrainMetadata {
+            threshold: -5e-5,
+            end: DATALEN,
+            library_version: "test".to_string(),
+            ..Default::default()
+        };
+        source.set_peak_train_metadata("13", &metadata).unwrap();
+
+        mcs::export_mcs(&source, filename).unwrap();
+
+        {
+            let phase = Phase::open_read_only(filename).unwrap();
+            assert_eq!(phase.date(), "Tuesday, March 5, 2024");
+            assert_eq!(phase.recording_start(), Some(start));
+            assert_eq!(phase.sampling_frequency(), 10000f32);
+            assert_eq!(phase.datalen(), DATALEN);
+            assert_eq!(phase.channel_info("12").unwrap().channel_id, 7);
+            for label in ["12", "13"] {
+                let expected = source.raw_data(label, None, None).unwrap();
+                let data = phase.raw_data(label, None, None).unwrap();
+                let step = phase.channel_info(label).unwrap().adc_step();
+                assert!(data
+                    .iter()
+                    .zip(expected.iter())
+                    .all(|(x, y)| (x - y).abs() <= step));
+            }
+
+            assert_eq!(phase.digital_labels(), vec!["Digital Data".to_string()]);
+            assert_eq!(phase.digital(0, None, None).unwrap(), digital);
+
+            let events = phase.events(0).unwrap();
+            assert_eq!(events.label, "Stimulus");
+            assert_eq!(events.event_id, 3);
+            assert_eq!(events.timestamps_us, vec![1_000, 2_000]);
+            assert_eq!(events.durations_us, vec![100, 200]);
+
+            assert_eq!(phase.peak_train_labels().unwrap(), vec!["13".to_string()]);
+            assert_eq!(
+                phase.peak_train("13", None, None).unwrap(),
+                (vec![10, 20], vec![-1e-4, -2e-4])
+            );
+            assert_eq!(phase.peak_train_metadata("13").unwrap(), Some(metadata));
+        }
+
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn split_mcs_by_digital() {
+        let _lock = HDF5_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        spike_c_init().unwrap();
+
+        let labels = MeaLayout::mea60().labels_of_kind(ElectrodeKind::Recording);
+        let filename = std::env::temp_dir().join("spike_rs_test_split_phase.h5");
+        let filename = filename.to_str().unwrap();
+        let prefix = std::env::temp_dir().join("spike_rs_test_split");
+        write_phase(filename, &labels).unwrap();
+
+        {
+            let phase = Phase::open_read_only(filename).unwrap();
+            let filenames = mcs::split_mcs(&phase, 0, prefix.to_str().unwrap()).unwrap();
+            // the digital is active for 100 samples every 200
+            assert_eq!(filenames.len(), DATALEN / 200);
+
+            let block = Phase::open_read_only(&filenames[1]).unwrap();
+            assert_eq!(block.datalen(), 100);
+            assert_eq!(
+                block.raw_data(&labels[0], None, None).unwrap(),
+                phase.raw_data(&labels[0], Some(300), Some(400)).unwrap()
+            );
+            assert!(block
+                .digital(0, None, None)
+                .unwrap()
+                .iter()
+                .all(|x| *x == 1f32));
+            assert_eq!(block.recording_start(), phase.sample_to_datetime(300));
+            for filename in filenames {
+                std::fs::remove_file(filename).unwrap();
+            }
+
+            // the events at 2 ms and 3 ms are after the new zero at 1.5 ms
+            let cropped = format!("{}_cropped.h5", prefix.to_str().unwrap());
+            mcs::crop_mcs(&phase, &cropped, 15, 500).unwrap();
+            let block = Phase::open_read_only(&cropped).unwrap();
+            assert_eq!(block.events(0).unwrap().timestamps_us, vec![500, 1_500]);
+            drop(block);
+            std::fs::remove_file(cropped).unwrap();
+        }
+
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    /// The sampling frequency is computed from the tick, the sampling period
+    /// in microseconds, so that the recordings not at 10 kHz are read right
+    #[test]
+    fn sampling_frequency_from_tick() {
+        let _lock = HDF5_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        spike_c_init().unwrap();
+
+        let filename = std::env::temp_dir().join("spike_rs_test_tick.h5");
+        let filename = filename.to_str().unwrap();
+        for (sampling_frequency, tick) in [(20000f32, 50), (25000f32, 40), (1000f32, 1000)] {
+            let mut source = MemoryPhase::new(sampling_frequency, 10);
+            source.add_raw_data("12", vec![0f32; 10]).unwrap();
+            mcs::export_mcs(&source, filename).unwrap();
+
+            let phase = Phase::open_read_only(filename).unwrap();
+            assert_eq!(phase.channel_info("12").unwrap().tick, tick);
+            assert_eq!(phase.sampling_frequency(), sampling_frequency);
+            drop(phase);
+            std::fs::remove_file(filename).unwrap();
+        }
+    }
+
+    #[test]
+    fn peak_runs_round_trip() {
+        let _lock = HDF5_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        spike_c_init().unwrap();
+
+        let labels = MeaLayout::mea60().labels_of_kind(ElectrodeKind::Recording);
+        let label = &labels[0];
+        let filename = std::env::temp_dir().join("spike_rs_test_peak_runs.h5");
+        let filename = filename.to_str().unwrap();
+        write_phase(filename, &labels).unwrap();
+        let train = (vec![1, 2], vec![1., 2.]);
+
+        {
+            let mut phase = Phase::open(filename).unwrap();
+            phase
+                .set_peak_train(label, None, None, train.clone())
+                .unwrap();
+            assert_eq!(phase.peak_run(), DEFAULT_PEAK_RUN);
+            assert_eq!(phase.peak_runs().unwrap(), [DEFAULT_PEAK_RUN]);
+
+            phase.set_peak_run("run 2").unwrap();
+            assert_eq!(phase.peak_run(), "run 2");
+            assert!(phase.peak_train_labels().unwrap().is_empty());
+            phase
+                .set_peak_train(label, None, None, (vec![5], vec![3.]))
+                .unwrap();
+            assert_eq!(phase.peak_runs().unwrap(), [DEFAULT_PEAK_RUN, "run 2"]);
+
+            phase.copy_peak_run(DEFAULT_PEAK_RUN, "copy").unwrap();
+            assert_eq!(
+                phase.peak_runs().unwrap(),
+                [DEFAULT_PEAK_RUN, "copy", "run 2"]
+            );
+            phase.set_peak_run("copy").unwrap();
+            assert_eq!(phase.peak_train(label, None, None).unwrap(), train);
+            // copying on the active run replaces its peak trains
+            phase.copy_peak_run("run 2", "copy").unwrap();
+            assert_eq!(
+                phase.peak_train(label, None, None).unwrap(),
+                (vec![5], vec![3.])
+            );
+
+            for name in ["", "a/b"] {
+                assert!(matches!(
+                    phase.set_peak_run(name),
+                    Err(SpikeError::PeakRunInvalidName)
+                ));
+                assert!(matches!(
+                    phase.copy_peak_run(DEFAULT_PEAK_RUN, name),
+                    Err(SpikeError::PeakRunInvalidName)
+                ));
+            }
+            assert!(matches!(
+                phase.copy_peak_run("missing", "other"),
+                Err(SpikeError::PeakRunNotFound)
+            ));
+            assert_eq!(phase.peak_run(), "copy");
+        }
+
+        // the runs are stored in the file
+        {
+            let mut phase = Phase::open_read_only(filename).unwrap();
+            assert_eq!(
+                phase.peak_runs().unwrap(),
+                [DEFAULT_PEAK_RUN, "copy", "run 2"]
+            );
+            assert_eq!(phase.peak_train(label, None, None).unwrap(), train);
+            phase.set_peak_run("run 2").unwrap();
+            assert_eq!(
+                phase.peak_train(label, None, None).unwrap(),
+                (vec![5], vec![3.])
+            );
+        }
+
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn read_only_phase_rejects_writes() {
+        let _lock = HDF5_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        spike_c_init().unwrap();
+
+        let labels = MeaLayout::mea60().labels_of_kind(ElectrodeKind::Recording);
+        let label = &labels[0];
+        let filename = std::env::temp_dir().join("spike_rs_test_read_only.h5");
+        let filename = filename.to_str().unwrap();
+        write_phase(filename, &labels).unwrap();
+
+        {
+            let mut phase = Phase::open(filename).unwrap();
+            assert!(!phase.is_read_only());
+            phase
+                .set_peak_train(label, None, None, (vec![1], vec![1.]))
+                .unwrap();
+        }
+
+        {
+            let mut phase = Phase::open_read_only(filename).unwrap();
+            assert!(phase.is_read_only());
+            let is_read_only =
+                |res: Result<(), SpikeError>| matches!(res, Err(SpikeError::PhaseIsReadOnly));
+
+            assert!(is_read_only(phase.set_raw_data(label, None, &[0.])));
+            assert!(is_read_only(phase.set_digital(0, None, &[0.])));
+            assert!(is_read_only(phase.set_peak_train(
+                label,
+                None,
+                None,
+                (vec![2], vec![2.])
+            )));
+            assert!(is_read_only(
+                phase.set_peak_train_metadata(label, &PeakTrainMetadata::default())
+            ));
+            assert!(is_read_only(phase.delete_peak_train(label)));
+            assert!(is_read_only(phase.rename_peak_train(label, "renamed")));
+            assert!(is_read_only(phase.clear_peak_trains()));
+            assert!(is_read_only(phase.set_peak_run("new run")));
+            assert!(is_read_only(phase.copy_peak_run(DEFAULT_PEAK_RUN, "copy")));
+
+            // nothing was written and the stored runs can still be selected
+            assert_eq!(phase.raw_data(label, Some(0), Some(1)).unwrap(), vec![0.]);
+            assert_eq!(
+                phase.peak_train(label, None, None).unwrap(),
+                (vec![1], vec![1.])
+            );
+            assert_eq!(
+                phase.peak_runs().unwrap(),
+                vec![DEFAULT_PEAK_RUN.to_string()]
+            );
+            phase.set_peak_run(DEFAULT_PEAK_RUN).unwrap();
+        }
+
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn open_120_channels() {
+        let labels: Vec<String> = MeaLayout::mea120()
+            .electrodes()
+            .iter()
+            .map(|electrode| electrode.label.clone())
+            .collect();
+        assert_eq!(labels.len(), 120);
+        check_phase("120", labels);
+    }
+
+    #[test]
+    fn open_252_channels() {
+        let labels = MeaLayout::mea256().labels_of_kind(ElectrodeKind::Recording);
+        assert_eq!(labels.len(), 252);
+        check_phase("252", labels);
+    }
+}
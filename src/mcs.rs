@@ -0,0 +1,380 @@
+use std::ffi::CString;
+
+use spike_rs::{
+    analysis::digital::get_digital_intervals,
+    error::SpikeError,
+    operations::crop::CroppedPhase,
+    time::DateTime,
+    types::{ChannelInfo, PhaseHandler},
+};
+
+use crate::{
+    h5::{c, H5File, H5Type, CHUNK_LEN},
+    sys, Error, Phase,
+};
+
+/// Attribute of `/Data` with the sample of the source recording that is the
+/// sample 0 of a cropped one
+pub const SAMPLE_OFFSET_ATTRIBUTE: &str = "SampleOffset";
+
+const RAW_DATA_STREAM_PATH: &str = "/Data/Recording_0/AnalogStream/Stream_0";
+const EVENT_STREAM_PATH: &str = "/Data/Recording_0/EventStream/Stream_0";
+
+/// Largest ADC value used when the ADC conversion of a channel is fitted on
+/// its data, leaving some margin to the i32 range
+const FITTED_ADC_RANGE: f64 = (1 << 30) as f64;
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Format a date like the `Date` attribute of the Multi Channel Systems
+/// files, e.g. `Tuesday, March 5, 2024`
+fn mcs_date(date: &DateTime) -> String {
+    format!(
+        "{}, {} {}, {}",
+        WEEKDAYS[date.weekday() as usize],
+        MONTHS[(date.month as usize).clamp(1, 12) - 1],
+        date.day,
+        date.year
+    )
+}
+
+/// Returns the ADC conversion of a channel whose data has no valid one, with
+/// a power of ten step that keeps `max`, the largest absolute value of the
+/// data, in range
+fn fitted_channel_info(label: &str, max: f64) -> ChannelInfo {
+    let exponent = if max > 0f64 {
+        ((max / FITTED_ADC_RANGE).log10().ceil() as i32).clamp(-18, 0)
+    } else {
+        -9
+    };
+    ChannelInfo {
+        label: label.to_string(),
+        raw_data_type: "Int".to_string(),
+        unit: "V".to_string(),
+        exponent,
+        conversion_factor: 1,
+        adc_bits: 32,
+        ..Default::default()
+    }
+}
+
+/// Helpers to write the Multi Channel Systems layout
+impl H5File {
+    /// Write the samples of the row `row` of a [n_channels, datalen] dataset
+    /// starting from the sample `start`
+    fn row_slice<T: H5Type>(
+        &self,
+        path: &str,
+        row: usize,
+        start: usize,
+        data: &[T],
+    ) -> Result<(), Error> {
+        self.dataset_slice(path, &[row, start], &[1, data.len()], data)
+    }
+
+    fn info_channels(&self, path: &str, infos: &[ChannelInfo], tick: i64) -> Result<(), Error> {
+        // the strings pointed by the rows, kept alive until they are written
+        let strings: Vec<[CString; 7]> = infos
+            .iter()
+            .map(|info| {
+                [
+                    c(&info.label),
+                    c(&info.raw_data_type),
+                    c(&info.unit),
+                    c(&info.high_pass_filter_type),
+                    c(&info.high_pass_filter_cutoff),
+                    c(&info.low_pass_filter_type),
+                    c(&info.low_pass_filter_cutoff),
+                ]
+            })
+            .collect();
+        let rows: Vec<sys::InfoChannel> = infos
+            .iter()
+            .zip(strings.iter())
+            .enumerate()
+            .map(|(i, (info, strings))| sys::InfoChannel {
+                channel_id: info.channel_id,
+                row_index: i as i32,
+                group_id: info.group_id,
+                electrode_group: info.electrode_group,
+                label: strings[0].as_ptr(),
+                raw_data_type: strings[1].as_ptr(),
+                unit: strings[2].as_ptr(),
+                exponent: info.exponent,
+                ad_zero: info.ad_zero,
+                tick,
+                conversion_factor: info.conversion_factor,
+                adc_bits: info.adc_bits,
+                high_pass_filter_type: strings[3].as_ptr(),
+                high_pass_filter_cutoff: strings[4].as_ptr(),
+                high_pass_filter_order: info.high_pass_filter_order,
+                low_pass_filter_type: strings[5].as_ptr(),
+                low_pass_filter_cutoff: strings[6].as_ptr(),
+                low_pass_filter_order: info.low_pass_filter_order,
+            })
+            .collect();
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_info_channels(self.fid, c(path).as_ptr(), rows.as_ptr(), rows.len())
+        })
+    }
+
+    fn info_events(&self, path: &str, events: &[(i32, String)]) -> Result<(), Error> {
+        let labels: Vec<CString> = events.iter().map(|(_, label)| c(label)).collect();
+        let empty = c("");
+        let rows: Vec<sys::InfoEvent> = events
+            .iter()
+            .zip(labels.iter())
+            .map(|((event_id, _), label)| sys::InfoEvent {
+                event_id: *event_id,
+                group_id: 0,
+                label: label.as_ptr(),
+                source_channel_ids: empty.as_ptr(),
+                source_channel_labels: empty.as_ptr(),
+            })
+            .collect();
+        Error::from_phaseh5_error(unsafe {
+            sys::h5_write_info_events(self.fid, c(path).as_ptr(), rows.as_ptr(), rows.len())
+        })
+    }
+}
+
+/// Export a phase to a new HDF5 file with the layout of the Multi Channel
+/// Systems recordings, the one read by `Phase::open`, so that cropped,
+/// filtered or synthetic data can be saved and reopened.
+///
+/// The file contains:
+/// * the `Date` of the recording start, or of the export if unknown, and the
+///   `DateInTicks` of the recording start if known;
+/// * the raw data stream as `Stream_0`, with the `channel_info` of each
+///   channel. The channels without a valid ADC conversion, e.g. the ones of
+///   a `MemoryPhase`, get one fitted on their data;
+/// * a `Digital` analog stream for each digital channel;
+/// * the event entities, with their labels in the `InfoEvent` table;
+/// * the peak trains of the active detection run, with their metadata.
+///
+/// The other analog streams are not exported. The sampling period is saved
+/// in whole microseconds, so the sampling frequency is kept exactly only if
+/// it's a divisor of 1 MHz.
+pub fn export_mcs(phase: &impl PhaseHandler, filename: &str) -> Result<(), SpikeError> {
+    export(phase, filename, None)
+}
+
+/// Export the `[start, end)` samples of a phase to a new HDF5 file, like
+/// `export_mcs`, with all the data shifted so that `start` is the new sample
+/// 0. The offset is saved in the `SampleOffset` attribute of `/Data`.
+pub fn crop_mcs(
+    phase: &impl PhaseHandler,
+    filename: &str,
+    start: usize,
+    end: usize,
+) -> Result<(), SpikeError> {
+    export(
+        &CroppedPhase::new(phase, start, end)?,
+        filename,
+        Some(start),
+    )
+}
+
+/// Export each active interval of the digital channel `digital`, e.g. each
+/// stimulation block, to its own file, like `crop_mcs`. The files are named
+/// `<filename_prefix>_<n>.h5`, with `n` counting the intervals from 0.
+/// Returns the names of the written files.
+pub fn split_mcs(
+    phase: &impl PhaseHandler,
+    digital: usize,
+    filename_prefix: &str,
+) -> Result<Vec<String>, SpikeError> {
+    let intervals = get_digital_intervals(&phase.digital(digital, None, None)?);
+    let mut filenames = Vec::with_capacity(intervals.len());
+    for (i, (start, end)) in intervals.into_iter().enumerate() {
+        let filename = format!("{filename_prefix}_{i}.h5");
+        crop_mcs(phase, &filename, start, end)?;
+        filenames.push(filename);
+    }
+    Ok(filenames)
+}
+
+fn export(
+    phase: &impl PhaseHandler,
+    filename: &str,
+    sample_offset: Option<usize>,
+) -> Result<(), SpikeError> {
+    let file = H5File::create(filename)?;
+    let res = write_mcs(&file, phase, sample_offset);
+    let closed = file.close();
+    res?;
+    closed?;
+    copy_peak_trains(phase, filename)
+}
+
+fn write_mcs(
+    file: &H5File,
+    phase: &impl PhaseHandler,
+    sample_offset: Option<usize>,
+) -> Result<(), SpikeError> {
+    let tick = (1e6f64 / phase.sampling_frequency() as f64).round() as i64;
+    let recording_start = phase.recording_start();
+
+    file.group("/Data")?;
+    let date = recording_start.unwrap_or_else(DateTime::now);
+    file.attribute("/Data", "Date", &mcs_date(&date))?;
+    if let Some(start) = recording_start {
+        file.llong_attribute("/Data", "DateInTicks", start.dotnet_ticks())?;
+    }
+    if let Some(offset) = sample_offset {
+        file.llong_attribute("/Data", SAMPLE_OFFSET_ATTRIBUTE, offset as i64)?;
+    }
+
+    write_raw_data(file, phase, tick)?;
+    write_digitals(file, phase, tick)?;
+    write_events(file, phase)
+}
+
+fn write_raw_data(file: &H5File, phase: &impl PhaseHandler, tick: i64) -> Result<(), SpikeError> {
+    let labels = phase.labels();
+    let datalen = phase.datalen();
+    let data_path = format!("{RAW_DATA_STREAM_PATH}/ChannelData");
+
+    file.group(RAW_DATA_STREAM_PATH)?;
+    file.attribute(RAW_DATA_STREAM_PATH, "Label", &phase.raw_data_stream())?;
+    file.attribute(RAW_DATA_STREAM_PATH, "DataSubType", "Electrode")?;
+    file.empty_dataset::<i32>(&data_path, &[labels.len(), datalen])?;
+
+    let mut infos = Vec::with_capacity(labels.len());
+    for (row, label) in labels.iter().enumerate() {
+        let info = match phase.channel_info(label) {
+            Ok(info) if info.adc_step() != 0f32 && info.adc_step().is_finite() => ChannelInfo {
+                label: label.clone(),
+                ..info
+            },
+            _ => {
+                // the fitted conversion needs a first pass for the largest value
+                let mut max = 0f64;
+                for chunk in phase.raw_data_chunks(label, CHUNK_LEN, 0)? {
+                    let (_, data) = chunk?;
+                    max = data
+                        .iter()
+                        .fold(max, |max, x| f64::max(max, x.abs() as f64));
+                }
+                fitted_channel_info(label, max)
+            }
+        };
+        for chunk in phase.raw_data_chunks(label, CHUNK_LEN, 0)? {
+            let (start, data) = chunk?;
+            file.row_slice(&data_path, row, start, &info.physical_to_adc(&data))?;
+        }
+        infos.push(info);
+    }
+
+    file.info_channels(&format!("{RAW_DATA_STREAM_PATH}/InfoChannel"), &infos, tick)?;
+    Ok(())
+}
+
+/// Write each digital channel in its own analog stream, after the raw data
+/// one, chunk by chunk
+fn write_digitals(file: &H5File, phase: &impl PhaseHandler, tick: i64) -> Result<(), SpikeError> {
+    let datalen = phase.datalen();
+
+    for (index, label) in phase.digital_labels().iter().enumerate() {
+        let stream_path = format!("/Data/Recording_0/AnalogStream/Stream_{}", index + 1);
+        let data_path = format!("{stream_path}/ChannelData");
+
+        file.group(&stream_path)?;
+        file.attribute(&stream_path, "Label", label)?;
+        file.attribute(&stream_path, "DataSubType", "Digital")?;
+        file.empty_dataset::<i32>(&data_path, &[1, datalen])?;
+
+        let mut start = 0;
+        while start < datalen {
+            let end = usize::min(start + CHUNK_LEN, datalen);
+            let data: Vec<i32> = phase
+                .digital(index, Some(start), Some(end))?
+                .iter()
+                .map(|x| x.round() as i32)
+                .collect();
+            file.row_slice(&data_path, 0, start, &data)?;
+            start = end;
+        }
+
+        let info = ChannelInfo {
+            label: label.clone(),
+            raw_data_type: "Int".to_string(),
+            conversion_factor: 1,
+            ..Default::default()
+        };
+        file.info_channels(&format!("{stream_path}/InfoChannel"), &[info], tick)?;
+    }
+    Ok(())
+}
+
+fn write_events(file: &H5File, phase: &impl PhaseHandler) -> Result<(), SpikeError> {
+    if phase.n_events() == 0 {
+        return Ok(());
+    }
+
+    let mut info_events: Vec<(i32, String)> = vec![];
+    for index in 0..phase.n_events() {
+        let event = phase.events(index)?;
+        // the ids name the datasets, so they must be unique
+        let event_id = if info_events.iter().any(|(id, _)| *id == event.event_id) {
+            info_events.iter().map(|(id, _)| *id).max().unwrap_or(0) + 1
+        } else {
+            event.event_id
+        };
+
+        let mut data = event.timestamps_us.clone();
+        data.extend_from_slice(&event.durations_us);
+        file.dataset(
+            &format!("{EVENT_STREAM_PATH}/EventEntity_{event_id}"),
+            &[2, event.timestamps_us.len()],
+            &data,
+        )?;
+        info_events.push((event_id, event.label));
+    }
+
+    file.info_events(&format!("{EVENT_STREAM_PATH}/InfoEvent"), &info_events)?;
+    Ok(())
+}
+
+/// Copy the peak trains of the active detection run, reopening the new file
+/// as a `Phase` to store them in the same layout it reads
+fn copy_peak_trains(phase: &impl PhaseHandler, filename: &str) -> Result<(), SpikeError> {
+    let labels = phase.peak_train_labels()?;
+    if labels.is_empty() {
+        return Ok(());
+    }
+
+    let mut exported = Phase::open(filename)?;
+    exported.set_peak_run(&phase.peak_run())?;
+    for label in labels.iter() {
+        let peak_train = phase.peak_train(label, None, None)?;
+        exported.set_peak_train(label, None, None, peak_train)?;
+        if let Some(metadata) = phase.peak_train_metadata(label)? {
+            exported.set_peak_train_metadata(label, &metadata)?;
+        }
+    }
+    Ok(())
+}
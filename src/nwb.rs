@@ -0,0 +1,499 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use spike_rs::{
+    analysis::digital::get_digital_intervals, error::SpikeError, layout::MeaLayout, time::DateTime,
+    types::PhaseHandler,
+};
+
+use crate::{
+    h5::{H5File, H5Type, CHUNK_LEN},
+    Error,
+};
+
+/// Version of the NWB schema followed by the exported files
+pub const NWB_VERSION: &str = "2.7.0";
+
+const DEVICE_PATH: &str = "/general/devices/MEA";
+const ELECTRODE_GROUP_PATH: &str = "/general/extracellular_ephys/MEA";
+const ELECTRODES_PATH: &str = "/general/extracellular_ephys/electrodes";
+const ELECTRICAL_SERIES_PATH: &str = "/acquisition/ElectricalSeries";
+const UNITS_PATH: &str = "/units";
+
+/// Returns a random version 4 UUID, used as identifier of the file and of
+/// its objects
+fn new_uuid() -> String {
+    let state = RandomState::new();
+    let mut bytes = [0u8; 16];
+    for (i, half) in bytes.chunks_mut(8).enumerate() {
+        let mut hasher = state.build_hasher();
+        hasher.write_usize(i);
+        hasher.write_i64(DateTime::now().unix_micros());
+        half.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|x| format!("{x:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Helpers to write the NWB and hdmf-common types
+impl H5File {
+    /// Mark `object` as an instance of `neurodata_type` from `namespace`,
+    /// with a new object id
+    fn neurodata_type(
+        &self,
+        object: &str,
+        namespace: &str,
+        neurodata_type: &str,
+    ) -> Result<(), Error> {
+        self.attribute(object, "namespace", namespace)?;
+        self.attribute(object, "neurodata_type", neurodata_type)?;
+        self.attribute(object, "object_id", &new_uuid())
+    }
+
+    /// Create a DynamicTable, or one of its subtypes, with the `id` column
+    /// for `n_rows` rows. The other columns are written by the caller.
+    fn table(
+        &self,
+        path: &str,
+        namespace: &str,
+        neurodata_type: &str,
+        description: &str,
+        colnames: &[&str],
+        n_rows: usize,
+    ) -> Result<(), Error> {
+        self.group(path)?;
+        self.neurodata_type(path, namespace, neurodata_type)?;
+        self.attribute(path, "description", description)?;
+        self.strings_attribute(path, "colnames", colnames)?;
+
+        let id_path = format!("{path}/id");
+        let ids: Vec<i64> = (0..n_rows as i64).collect();
+        self.dataset(&id_path, &[n_rows], &ids)?;
+        self.neurodata_type(&id_path, "hdmf-common", "ElementIdentifiers")
+    }
+
+    fn vector_data<T: H5Type>(
+        &self,
+        table: &str,
+        column: &str,
+        description: &str,
+        data: &[T],
+    ) -> Result<(), Error> {
+        let path = format!("{table}/{column}");
+        self.dataset(&path, &[data.len()], data)?;
+        self.neurodata_type(&path, "hdmf-common", "VectorData")?;
+        self.attribute(&path, "description", description)
+    }
+
+    fn string_vector_data(
+        &self,
+        table: &str,
+        column: &str,
+        description: &str,
+        values: &[String],
+    ) -> Result<(), Error> {
+        let path = format!("{table}/{column}");
+        self.string_dataset(&path, &[values.len()], values)?;
+        self.neurodata_type(&path, "hdmf-common", "VectorData")?;
+        self.attribute(&path, "description", description)
+    }
+
+    /// Write the index of the ragged `column` of `table`, `ends` being the
+    /// exclusive end of each row in the column data
+    fn vector_index(&self, table: &str, column: &str, ends: &[u64]) -> Result<(), Error> {
+        let path = format!("{table}/{column}_index");
+        self.dataset(&path, &[ends.len()], ends)?;
+        self.neurodata_type(&path, "hdmf-common", "VectorIndex")?;
+        self.attribute(
+            &path,
+            "description",
+            &format!("Index for VectorData '{column}'"),
+        )?;
+        self.reference_attribute(&path, "target", &format!("{table}/{column}"))
+    }
+
+    /// Write a region of the electrodes table, given the electrode indices
+    fn electrodes_region(
+        &self,
+        path: &str,
+        description: &str,
+        indices: &[i32],
+    ) -> Result<(), Error> {
+        self.dataset(path, &[indices.len()], indices)?;
+        self.neurodata_type(path, "hdmf-common", "DynamicTableRegion")?;
+        self.attribute(path, "description", description)?;
+        self.reference_attribute(path, "table", ELECTRODES_PATH)
+    }
+}
+
+/// Export a phase to a Neurodata Without Borders 2.x file, the format
+/// described at https://nwb-schema.readthedocs.io
+///
+/// The file contains:
+/// * the raw data as the `ElectricalSeries` acquisition, in volts, with a row
+///   for each sample and a column for each channel of `phase.labels()`;
+/// * the electrodes table, with the label and channel id of each channel and,
+///   if the recording matches a built-in MEA layout, its position on the
+///   array in micrometers (`rel_x`, `rel_y`);
+/// * a `TimeIntervals` table, `TTL_<index>`, with the active intervals of each
+///   digital channel;
+/// * the `Units` table with the peak train of each channel that has one, the
+///   times in seconds and the peak values in the `peak_values` column.
+///
+/// If the recording start is unknown, the session start is the unix epoch.
+/// NWB requires the times with a UTC offset while the acquisition software
+/// saves the local time of the recording computer, without a timezone: the
+/// session start is written with `utc_offset`, the offset of that local time
+/// as `+HH:MM` or `-HH:MM`, which the caller has to know. The creation date of
+/// the file is in UTC.
+pub fn export_nwb(
+    phase: &impl PhaseHandler,
+    filename: &str,
+    session_description: &str,
+    utc_offset: &str,
+) -> Result<(), SpikeError> {
+    let session_start = offset_datetime(
+        &phase
+            .recording_start()
+            .unwrap_or_else(|| DateTime::from_unix_micros(0)),
+        utc_offset,
+    )?;
+
+    let file = H5File::create(filename)?;
+    let res = write_nwb(&file, phase, session_description, session_start);
+    let closed = file.close();
+    res?;
+    Ok(closed?)
+}
+
+/// ISO 8601 representation of a local time with its UTC offset, as NWB
+/// requires. The offset must be `+HH:MM` or `-HH:MM`.
+pub(crate) fn offset_datetime(datetime: &DateTime, utc_offset: &str) -> Result<String, SpikeError> {
+    let number = |range: std::ops::Range<usize>| {
+        utc_offset
+            .get(range)
+            .filter(|digits| digits.bytes().all(|x| x.is_ascii_digit()))
+            .and_then(|digits| digits.parse::<u32>().ok())
+    };
+    let bytes = utc_offset.as_bytes();
+    let valid = bytes.len() == 6
+        && (bytes[0] == b'+' || bytes[0] == b'-')
+        && bytes[3] == b':'
+        && number(1..3).is_some_and(|hours| hours <= 14)
+        && number(4..6).is_some_and(|minutes| minutes < 60);
+    if !valid {
+        return Err(SpikeError::NwbInvalidUtcOffset(utc_offset.to_string()));
+    }
+    Ok(format!("{datetime}{utc_offset}"))
+}
+
+fn write_nwb(
+    file: &H5File,
+    phase: &impl PhaseHandler,
+    session_description: &str,
+    session_start: String,
+) -> Result<(), SpikeError> {
+    let labels = phase.labels();
+
+    file.neurodata_type("/", "core", "NWBFile")?;
+    file.attribute("/", "nwb_version", NWB_VERSION)?;
+    file.string_dataset(
+        "/file_create_date",
+        &[1],
+        &[offset_datetime(&DateTime::now(), "+00:00")?],
+    )?;
+    file.string_dataset("/identifier", &[], &[new_uuid()])?;
+    file.string_dataset(
+        "/session_description",
+        &[],
+        &[session_description.to_string()],
+    )?;
+    file.string_dataset(
+        "/session_start_time",
+        &[],
+        std::slice::from_ref(&session_start),
+    )?;
+    file.string_dataset("/timestamps_reference_time", &[], &[session_start])?;
+    for group in [
+        "/acquisition",
+        "/analysis",
+        "/processing",
+        "/stimulus/presentation",
+        "/stimulus/templates",
+        "/general/devices",
+        "/intervals",
+    ] {
+        file.group(group)?;
+    }
+
+    write_electrodes(file, phase, &labels)?;
+    write_electrical_series(file, phase, &labels)?;
+    write_ttl_intervals(file, phase)?;
+    write_units(file, phase, &labels)
+}
+
+fn write_electrodes(
+    file: &H5File,
+    phase: &impl PhaseHandler,
+    labels: &[String],
+) -> Result<(), SpikeError> {
+    let layout = MeaLayout::detect(labels);
+    let location = match &layout {
+        Some(layout) => layout.name.clone(),
+        None => "unknown".to_string(),
+    };
+
+    file.group(DEVICE_PATH)?;
+    file.neurodata_type(DEVICE_PATH, "core", "Device")?;
+    file.attribute(
+        DEVICE_PATH,
+        "description",
+        &format!("{location} microelectrode array"),
+    )?;
+
+    file.group(ELECTRODE_GROUP_PATH)?;
+    file.neurodata_type(ELECTRODE_GROUP_PATH, "core", "ElectrodeGroup")?;
+    file.attribute(
+        ELECTRODE_GROUP_PATH,
+        "description",
+        "Electrodes of the microelectrode array",
+    )?;
+    file.attribute(ELECTRODE_GROUP_PATH, "location", &location)?;
+    file.soft_link(&format!("{ELECTRODE_GROUP_PATH}/device"), DEVICE_PATH)?;
+
+    let mut colnames = vec!["location", "group", "group_name", "label", "channel_id"];
+    if layout.is_some() {
+        colnames.extend(["rel_x", "rel_y"]);
+    }
+    let n_electrodes = labels.len();
+    file.table(
+        ELECTRODES_PATH,
+        "hdmf-common",
+        "DynamicTable",
+        "Electrodes of the recording",
+        &colnames,
+        n_electrodes,
+    )?;
+
+    file.string_vector_data(
+        ELECTRODES_PATH,
+        "location",
+        "Location of the electrode",
+        &vec![location.clone(); n_electrodes],
+    )?;
+    let group_path = format!("{ELECTRODES_PATH}/group");
+    file.reference_dataset(&group_path, &vec![ELECTRODE_GROUP_PATH; n_electrodes])?;
+    file.neurodata_type(&group_path, "hdmf-common", "VectorData")?;
+    file.attribute(&group_path, "description", "Group of the electrode")?;
+    file.string_vector_data(
+        ELECTRODES_PATH,
+        "group_name",
+        "Name of the group of the electrode",
+        &vec!["MEA".to_string(); n_electrodes],
+    )?;
+    file.string_vector_data(ELECTRODES_PATH, "label", "Label of the channel", labels)?;
+    let channel_ids = labels
+        .iter()
+        .map(|label| Ok(phase.channel_info(label)?.channel_id))
+        .collect::<Result<Vec<i32>, SpikeError>>()?;
+    file.vector_data(
+        ELECTRODES_PATH,
+        "channel_id",
+        "Id of the channel in the recording",
+        &channel_ids,
+    )?;
+
+    if let Some(layout) = layout {
+        let electrodes: Vec<_> = labels
+            .iter()
+            .map(|label| layout.electrode(label).expect("detected layout"))
+            .collect();
+        let rel_x: Vec<f32> = electrodes
+            .iter()
+            .map(|electrode| electrode.column as f32 * layout.pitch)
+            .collect();
+        let rel_y: Vec<f32> = electrodes
+            .iter()
+            .map(|electrode| electrode.row as f32 * layout.pitch)
+            .collect();
+        file.vector_data(
+            ELECTRODES_PATH,
+            "rel_x",
+            "Horizontal position on the array, in micrometers",
+            &rel_x,
+        )?;
+        file.vector_data(
+            ELECTRODES_PATH,
+            "rel_y",
+            "Vertical position on the array, in micrometers",
+            &rel_y,
+        )?;
+    }
+    Ok(())
+}
+
+fn write_electrical_series(
+    file: &H5File,
+    phase: &impl PhaseHandler,
+    labels: &[String],
+) -> Result<(), SpikeError> {
+    let n_channels = labels.len();
+    let datalen = phase.datalen();
+
+    file.group(ELECTRICAL_SERIES_PATH)?;
+    file.neurodata_type(ELECTRICAL_SERIES_PATH, "core", "ElectricalSeries")?;
+    file.attribute(
+        ELECTRICAL_SERIES_PATH,
+        "description",
+        "Raw data of the recording",
+    )?;
+    file.attribute(ELECTRICAL_SERIES_PATH, "comments", "no comments")?;
+
+    let data_path = format!("{ELECTRICAL_SERIES_PATH}/data");
+    file.empty_dataset::<f32>(&data_path, &[datalen, n_channels])?;
+    file.float_attribute(&data_path, "conversion", 1f32)?;
+    file.float_attribute(&data_path, "offset", 0f32)?;
+    file.float_attribute(&data_path, "resolution", -1f32)?;
+    file.attribute(&data_path, "unit", "volts")?;
+
+    if n_channels > 0 {
+        let mut start = 0;
+        while start < datalen {
+            let end = usize::min(start + CHUNK_LEN, datalen);
+            let block = phase.raw_block(labels, Some(start), Some(end))?;
+            let n_samples = block.n_samples();
+            let data = block.data();
+            let mut chunk = Vec::with_capacity(data.len());
+            for sample in 0..n_samples {
+                for channel in 0..n_channels {
+                    chunk.push(data[channel * n_samples + sample]);
+                }
+            }
+            file.dataset_slice(&data_path, &[start, 0], &[n_samples, n_channels], &chunk)?;
+            start = end;
+        }
+    }
+
+    let starting_time_path = format!("{ELECTRICAL_SERIES_PATH}/starting_time");
+    file.dataset(&starting_time_path, &[], &[0f64])?;
+    file.float_attribute(&starting_time_path, "rate", phase.sampling_frequency())?;
+    file.attribute(&starting_time_path, "unit", "seconds")?;
+
+    let electrodes: Vec<i32> = (0..n_channels as i32).collect();
+    file.electrodes_region(
+        &format!("{ELECTRICAL_SERIES_PATH}/electrodes"),
+        "Electrodes of the columns of the data",
+        &electrodes,
+    )?;
+    Ok(())
+}
+
+fn write_ttl_intervals(file: &H5File, phase: &impl PhaseHandler) -> Result<(), SpikeError> {
+    let digital_labels = phase.digital_labels();
+    for index in 0..phase.n_digitals() {
+        let digital = phase.digital(index, None, None)?;
+        let intervals = get_digital_intervals(&digital);
+        let label = digital_labels
+            .get(index)
+            .cloned()
+            .unwrap_or_else(|| index.to_string());
+
+        let path = format!("/intervals/TTL_{index}");
+        file.table(
+            &path,
+            "core",
+            "TimeIntervals",
+            &format!("Active intervals of the digital channel {label}"),
+            &["start_time", "stop_time"],
+            intervals.len(),
+        )?;
+        let start_times: Vec<f64> = intervals
+            .iter()
+            .map(|(start, _)| phase.sample_to_seconds(*start))
+            .collect();
+        let stop_times: Vec<f64> = intervals
+            .iter()
+            .map(|(_, end)| phase.sample_to_seconds(*end))
+            .collect();
+        file.vector_data(
+            &path,
+            "start_time",
+            "Start time of the interval, in seconds",
+            &start_times,
+        )?;
+        file.vector_data(
+            &path,
+            "stop_time",
+            "Stop time of the interval, in seconds",
+            &stop_times,
+        )?;
+    }
+    Ok(())
+}
+
+fn write_units(
+    file: &H5File,
+    phase: &impl PhaseHandler,
+    labels: &[String],
+) -> Result<(), SpikeError> {
+    let peak_train_labels = phase.peak_train_labels()?;
+    let electrodes: Vec<usize> = (0..labels.len())
+        .filter(|i| peak_train_labels.contains(&labels[*i]))
+        .collect();
+    if electrodes.is_empty() {
+        return Ok(());
+    }
+
+    let mut spike_times = vec![];
+    let mut peak_values = vec![];
+    let mut ends = vec![];
+    for electrode in electrodes.iter() {
+        let (samples, values) = phase.peak_train(&labels[*electrode], None, None)?;
+        spike_times.extend(samples.iter().map(|x| phase.sample_to_seconds(*x)));
+        peak_values.extend(values);
+        ends.push(spike_times.len() as u64);
+    }
+
+    file.table(
+        UNITS_PATH,
+        "core",
+        "Units",
+        "Peak trains of the channels of the recording",
+        &["spike_times", "electrodes", "peak_values"],
+        electrodes.len(),
+    )?;
+    file.vector_data(
+        UNITS_PATH,
+        "spike_times",
+        "Times of the peaks, in seconds",
+        &spike_times,
+    )?;
+    file.vector_index(UNITS_PATH, "spike_times", &ends)?;
+    file.vector_data(
+        UNITS_PATH,
+        "peak_values",
+        "Values of the peaks, in volts",
+        &peak_values,
+    )?;
+    file.vector_index(UNITS_PATH, "peak_values", &ends)?;
+
+    let electrodes: Vec<i32> = electrodes.iter().map(|x| *x as i32).collect();
+    file.electrodes_region(
+        &format!("{UNITS_PATH}/electrodes"),
+        "Electrode of the peak train",
+        &electrodes,
+    )?;
+    let electrodes_ends: Vec<u64> = (1..=electrodes.len() as u64).collect();
+    file.vector_index(UNITS_PATH, "electrodes", &electrodes_ends)?;
+    Ok(())
+}